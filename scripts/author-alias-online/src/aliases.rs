@@ -0,0 +1,62 @@
+use anyhow::{Context, Result};
+use crate::wikidata::strip_accents;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One label or alias of a resolved Wikidata entity.
+pub struct AliasRow {
+    pub wikidata_id: String,
+    pub alias: String,
+    pub lang: String,
+    pub folder: String,
+}
+
+pub const ALIASES_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS author_aliases (
+     wikidata_id TEXT NOT NULL,
+     alias TEXT NOT NULL,
+     alias_normalized TEXT NOT NULL,
+     lang TEXT NOT NULL,
+     folder TEXT NOT NULL,
+     author_id TEXT,
+     updated_at INTEGER NOT NULL,
+     PRIMARY KEY (wikidata_id, alias)
+ );
+ CREATE INDEX IF NOT EXISTS idx_author_aliases_normalized ON author_aliases(alias_normalized);";
+
+/// Upserts rows into `author_aliases`, keyed by (wikidata_id, alias). `author_id` is taken
+/// from `library_authors` (written by cleanup) when that table knows the folder.
+pub fn write_aliases(db_path: &Path, rows: &[AliasRow]) -> Result<usize> {
+    let mut conn = Connection::open(db_path).with_context(|| format!("Open database {:?}", db_path))?;
+    conn.execute_batch(ALIASES_SCHEMA)?;
+    let has_library: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'library_authors')",
+        [], |row| row.get(0),
+    )?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let tx = conn.transaction()?;
+    for row in rows {
+        let author_id: Option<String> = if has_library {
+            tx.query_row("SELECT author_id FROM library_authors WHERE folder = ?1", [&row.folder], |r| r.get(0)).optional()?
+        } else { None };
+        tx.execute(
+            "INSERT OR REPLACE INTO author_aliases (wikidata_id, alias, alias_normalized, lang, folder, author_id, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![row.wikidata_id, row.alias, normalize_alias(&row.alias), row.lang, row.folder, author_id, now],
+        )?;
+    }
+    tx.commit()?;
+    Ok(rows.len())
+}
+
+/// Same rules as `normalize_name` in the cleanup tool, so aliases can be looked up offline.
+pub fn normalize_alias(alias: &str) -> String {
+    strip_accents(alias)
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
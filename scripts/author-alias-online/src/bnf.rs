@@ -0,0 +1,61 @@
+use serde::Deserialize;
+
+use crate::http::CachedHttp;
+use crate::wikidata::{score_search_items, normalize_for_score, WikidataSearchItem};
+
+const BNF_SPARQL: &str = "https://data.bnf.fr/sparql";
+
+#[derive(Debug, Deserialize)]
+pub struct SparqlResponse { pub results: SparqlResults }
+
+#[derive(Debug, Deserialize)]
+pub struct SparqlResults { pub bindings: Vec<std::collections::HashMap<String, SparqlValue>> }
+
+#[derive(Debug, Deserialize)]
+pub struct SparqlValue { pub value: String }
+
+#[derive(Clone)]
+pub struct BnfClient { http: CachedHttp }
+
+impl BnfClient {
+    pub fn new(http: CachedHttp) -> Self { Self { http } }
+
+    /// Persons of data.bnf.fr whose family name contains the last token of `query`, scored like
+    /// Wikidata hits, best first: (ark, label, dates, score). BnF labels keep French accents and particles.
+    pub async fn search_candidates(&self, query: &str) -> anyhow::Result<Vec<(String, String, String, f64)>> {
+        let family: String = query.split_whitespace().last().unwrap_or_default().chars().filter(|c| c.is_alphanumeric()).collect();
+        if family.chars().count() < 2 { return Ok(Vec::new()); }
+        let sparql = format!(r#"PREFIX foaf: <http://xmlns.com/foaf/0.1/>
+PREFIX skos: <http://www.w3.org/2004/02/skos/core#>
+SELECT DISTINCT ?concept ?label WHERE {{
+  ?concept foaf:focus ?person ; skos:prefLabel ?label .
+  ?person a foaf:Person ; foaf:familyName ?family .
+  ?family bif:contains "'{}'" .
+}} LIMIT 25"#, family);
+        let key = normalize_for_score(query);
+        let body = self.http.get_text(BNF_SPARQL, "bnf", &key, &[("query", sparql.as_str()), ("format", "json")]).await?;
+        let data: SparqlResponse = serde_json::from_str(&body)?;
+        let items = data.results.bindings.into_iter()
+            .filter_map(|mut row| {
+                let concept = row.remove("concept")?.value;
+                let label = row.remove("label")?.value;
+                let (label, dates) = split_dates(&label);
+                Some(WikidataSearchItem { id: ark_of(&concept), label: Some(label), description: Some(dates) })
+            })
+            .collect();
+        Ok(score_search_items(query, items))
+    }
+}
+
+/// "http://data.bnf.fr/ark:/12148/cb11907966z" -> "ark:/12148/cb11907966z".
+fn ark_of(uri: &str) -> String {
+    uri.find("ark:/").map(|idx| uri[idx..].trim_end_matches('#').to_string()).unwrap_or_else(|| uri.to_string())
+}
+
+/// "Hugo, Victor (1802-1885)" -> ("Hugo, Victor", "1802-1885").
+fn split_dates(label: &str) -> (String, String) {
+    match label.rfind('(') {
+        Some(idx) if label.ends_with(')') => (label[..idx].trim().to_string(), label[idx + 1..label.len() - 1].trim().to_string()),
+        _ => (label.trim().to_string(), String::new()),
+    }
+}
@@ -0,0 +1,47 @@
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Raw API responses stored in SQLite, keyed by request kind + normalized query/QID.
+/// Only successful responses are stored, so failures are retried on the next run.
+pub struct ResponseCache { conn: Mutex<Connection>, ttl_secs: i64 }
+
+impl ResponseCache {
+    pub fn open(path: &Path, ttl_days: u32) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) { std::fs::create_dir_all(parent)?; }
+        let conn = Connection::open(path).with_context(|| format!("Open cache {:?}", path))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS responses (
+                 kind TEXT NOT NULL,
+                 key TEXT NOT NULL,
+                 body TEXT NOT NULL,
+                 fetched_at INTEGER NOT NULL,
+                 PRIMARY KEY (kind, key)
+             );",
+        )?;
+        Ok(Self { conn: Mutex::new(conn), ttl_secs: ttl_days as i64 * 86_400 })
+    }
+
+    /// Cached body if younger than the TTL.
+    pub fn get(&self, kind: &str, key: &str) -> Option<String> {
+        let conn = self.conn.lock().ok()?;
+        conn.query_row(
+            "SELECT body FROM responses WHERE kind = ?1 AND key = ?2 AND fetched_at >= ?3",
+            params![kind, key, now() - self.ttl_secs],
+            |row| row.get(0),
+        ).optional().ok()?
+    }
+
+    pub fn put(&self, kind: &str, key: &str, body: &str) {
+        let Ok(conn) = self.conn.lock() else { return; };
+        // A failed write only costs a refetch next time.
+        let _ = conn.execute(
+            "INSERT OR REPLACE INTO responses (kind, key, body, fetched_at) VALUES (?1, ?2, ?3, ?4)",
+            params![kind, key, body, now()],
+        );
+    }
+}
+
+fn now() -> i64 { SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0) }
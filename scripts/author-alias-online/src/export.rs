@@ -0,0 +1,74 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::aliases::{normalize_alias, AliasRow, ALIASES_SCHEMA};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// SQLite script filling `author_aliases`.
+    Sql,
+    /// `author_local,name,sort,link` rows for Calibre's author name, sort and link fields.
+    Calibre,
+}
+
+impl ExportFormat {
+    pub fn default_path(self) -> &'static str {
+        match self {
+            ExportFormat::Sql => "data/online_aliases.sql",
+            ExportFormat::Calibre => "data/online_calibre_authors.csv",
+        }
+    }
+}
+
+/// A resolved author as another library manager would name it.
+pub struct CalibreRow {
+    pub author_local: String,
+    /// "Victor Hugo"
+    pub name: String,
+    /// "Hugo, Victor"
+    pub sort: String,
+    /// Page of the matched id on its provider.
+    pub link: String,
+}
+
+/// Script with the same schema and upsert as --update-db. `author_id` is left NULL: it comes
+/// from `library_authors`, which the target database may not have.
+pub fn write_sql(path: &Path, rows: &[AliasRow]) -> Result<()> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let mut script = String::from("BEGIN;\n");
+    script.push_str(ALIASES_SCHEMA);
+    script.push('\n');
+    for row in rows {
+        script.push_str(&format!(
+            "INSERT OR REPLACE INTO author_aliases (wikidata_id, alias, alias_normalized, lang, folder, author_id, updated_at) VALUES ({}, {}, {}, {}, {}, NULL, {});\n",
+            quote(&row.wikidata_id), quote(&row.alias), quote(&normalize_alias(&row.alias)), quote(&row.lang), quote(&row.folder), now,
+        ));
+    }
+    script.push_str("COMMIT;\n");
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) { fs::create_dir_all(parent)?; }
+    fs::write(path, script).with_context(|| format!("Write SQL export {:?}", path))
+}
+
+pub fn write_calibre(path: &Path, rows: &[CalibreRow]) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) { fs::create_dir_all(parent)?; }
+    let mut w = csv::Writer::from_path(path).with_context(|| format!("Open Calibre export {:?}", path))?;
+    w.write_record(["author_local", "name", "sort", "link"])?;
+    for row in rows { w.write_record([&row.author_local, &row.name, &row.sort, &row.link])?; }
+    w.flush()?;
+    Ok(())
+}
+
+/// URL of `id` on the provider that returned it.
+pub fn provider_link(source: &str, id: &str) -> String {
+    match source {
+        "bnf" => format!("https://data.bnf.fr/{}", id),
+        "viaf" => format!("https://viaf.org/viaf/{}", id),
+        "openlibrary" => format!("https://openlibrary.org/authors/{}", id),
+        _ => format!("https://www.wikidata.org/wiki/{}", id),
+    }
+}
+
+fn quote(value: &str) -> String { format!("'{}'", value.replace('\'', "''")) }
@@ -0,0 +1,73 @@
+use serde::Deserialize;
+
+use crate::http::CachedHttp;
+use crate::wikidata::normalize_for_score;
+
+const GOOGLE_BOOKS_VOLUMES: &str = "https://www.googleapis.com/books/v1/volumes";
+
+#[derive(Debug, Deserialize)]
+pub struct VolumesResponse { pub items: Option<Vec<Volume>> }
+
+#[derive(Debug, Deserialize)]
+pub struct Volume {
+    pub id: String,
+    #[serde(rename = "volumeInfo")]
+    pub info: VolumeInfo,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VolumeInfo {
+    pub title: Option<String>,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    #[serde(rename = "industryIdentifiers", default)]
+    pub identifiers: Vec<IndustryIdentifier>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IndustryIdentifier {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub identifier: String,
+}
+
+impl VolumeInfo {
+    /// ISBN-13 when listed, else ISBN-10.
+    pub fn isbn(&self) -> Option<&str> {
+        ["ISBN_13", "ISBN_10"].iter()
+            .find_map(|kind| self.identifiers.iter().find(|id| id.kind == *kind))
+            .map(|id| id.identifier.as_str())
+    }
+}
+
+#[derive(Clone)]
+pub struct GoogleBooksClient { http: CachedHttp, key: Option<String> }
+
+impl GoogleBooksClient {
+    /// `key` is an optional API key (higher quota); anonymous requests work at low volume.
+    pub fn new(http: CachedHttp, key: Option<String>) -> Self { Self { http, key } }
+
+    /// Volumes whose title and/or author (empty ones are left out) match, in API relevance order.
+    pub async fn search(&self, title: &str, author: Option<&str>) -> anyhow::Result<Vec<Volume>> {
+        let mut terms = Vec::new();
+        if !title.trim().is_empty() { terms.push(format!("intitle:{}", title.trim())); }
+        if let Some(author) = author.map(str::trim).filter(|a| !a.is_empty()) { terms.push(format!("inauthor:{}", author)); }
+        if terms.is_empty() { return Ok(Vec::new()); }
+        self.volumes(&terms.join(" ")).await
+    }
+
+    /// Volumes carrying this ISBN (10 or 13 digits, hyphens ignored).
+    pub async fn search_isbn(&self, isbn: &str) -> anyhow::Result<Vec<Volume>> {
+        let digits: String = isbn.chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+        self.volumes(&format!("isbn:{}", digits)).await
+    }
+
+    async fn volumes(&self, query: &str) -> anyhow::Result<Vec<Volume>> {
+        let key = normalize_for_score(query);
+        let mut params = vec![("q", query), ("maxResults", "10"), ("printType", "books")];
+        if let Some(api_key) = &self.key { params.push(("key", api_key.as_str())); }
+        let body = self.http.get_text(GOOGLE_BOOKS_VOLUMES, "googlebooks", &key, &params).await?;
+        let data: VolumesResponse = serde_json::from_str(&body)?;
+        Ok(data.items.unwrap_or_default())
+    }
+}
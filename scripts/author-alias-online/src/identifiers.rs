@@ -0,0 +1,61 @@
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use sortbook_common::identity::{self, IdentityRecord};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One external identifier of an author folder (`scheme` is `wikidata`, `viaf`, `isni`, ...).
+pub struct IdentifierRow {
+    pub folder: String,
+    pub scheme: &'static str,
+    pub identifier: String,
+}
+
+/// Upserts rows into `author_identifiers`, one identifier per (folder, scheme).
+pub fn write_identifiers(db_path: &Path, rows: &[IdentifierRow]) -> Result<usize> {
+    let mut conn = Connection::open(db_path).with_context(|| format!("Open database {:?}", db_path))?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS author_identifiers (
+             folder TEXT NOT NULL,
+             scheme TEXT NOT NULL,
+             identifier TEXT NOT NULL,
+             updated_at INTEGER NOT NULL,
+             PRIMARY KEY (folder, scheme)
+         );
+         CREATE INDEX IF NOT EXISTS idx_author_identifiers_value ON author_identifiers(scheme, identifier);",
+    )?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let tx = conn.transaction()?;
+    for row in rows {
+        tx.execute(
+            "INSERT OR REPLACE INTO author_identifiers (folder, scheme, identifier, updated_at) VALUES (?1, ?2, ?3, ?4)",
+            params![row.folder, row.scheme, row.identifier, now],
+        )?;
+    }
+    tx.commit()?;
+    Ok(rows.len())
+}
+
+/// Records each match in `author_identity`, with the OpenLibrary author_id `library_authors` (written by
+/// cleanup) knows for its folder. Returns the number of records stored.
+pub fn write_identity(db_path: &Path, rows: Vec<IdentityRecord>) -> Result<usize> {
+    let mut conn = Connection::open(db_path).with_context(|| format!("Open database {:?}", db_path))?;
+    let has_library: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'library_authors')",
+        [], |row| row.get(0),
+    )?;
+    let mut written = 0;
+    for mut row in rows {
+        if has_library {
+            for folder in &row.folders {
+                let author_id: Option<String> =
+                    conn.query_row("SELECT author_id FROM library_authors WHERE folder = ?1", [folder], |r| r.get(0)).optional()?;
+                if let Some(author_id) = author_id.filter(|id| !row.ids.iter().any(|(_, known)| known == id)) {
+                    row.ids.push(("openlibrary", author_id));
+                }
+            }
+        }
+        if identity::record(&mut conn, &row, "author-alias-online")?.is_some() { written += 1; }
+    }
+    Ok(written)
+}
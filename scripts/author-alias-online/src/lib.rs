@@ -0,0 +1,16 @@
+//! Online author lookups (Wikidata, BnF, VIAF, OpenLibrary). `wikidata::BlockingWikidata` is also used by
+//! the cleanup tool to cross-check merges and group authors; `metadata` puts the book sources behind one provider trait.
+mod aliases;
+pub mod bnf;
+pub mod cache;
+pub mod cli;
+mod export;
+pub mod googlebooks;
+pub mod http;
+mod identifiers;
+pub mod metadata;
+pub mod openlibrary;
+mod output;
+mod plan;
+pub mod viaf;
+pub mod wikidata;
@@ -0,0 +1,585 @@
+//! `MetadataProvider`: title, author and ISBN searches plus author aliases behind one interface, so a tool asks
+//! the sources in the order chosen for the run (`--providers`) instead of being tied to one. Implemented for the
+//! OpenLibrary dump in SQLite (`--db`), the OpenLibrary API, Wikidata and Google Books; `Registry` holds the
+//! providers of a run.
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use clap::ValueEnum;
+use rusqlite::{params, Connection, OpenFlags};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::googlebooks::{GoogleBooksClient, Volume};
+use crate::http::{CachedHttp, ClientOptions};
+use crate::openlibrary::{OpenLibraryClient, WorkDoc};
+use crate::wikidata::{normalize_for_score, score_label, WikidataClient};
+
+/// Rows read per SQLite search (the online APIs return 10 as well).
+const SQLITE_LIMIT: i64 = 10;
+/// Words of a Wikidata description that mark a written work (films, albums... are left out).
+const WRITTEN_WORK_WORDS: [&str; 10] = [
+    "novel",
+    "book",
+    "poem",
+    "play",
+    "literary",
+    "written work",
+    "short story",
+    "essay",
+    "comic",
+    "manga",
+];
+
+/// A work found by a provider.
+#[derive(Debug, Clone, Serialize)]
+pub struct BookHit {
+    /// OpenLibrary work key (`OL45883W`), Wikidata QID or Google Books volume id.
+    pub work_id: String,
+    pub title: String,
+    /// OpenLibrary author key or Wikidata QID; empty when the provider has none.
+    pub author_id: String,
+    pub author: Option<String>,
+    pub isbn: Option<String>,
+    /// Title match against the query (0-1, 1 = same normalized title); 1 for an ISBN hit.
+    pub score: f64,
+}
+
+/// An author found by a provider.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthorHit {
+    /// OpenLibrary author key or Wikidata QID; empty when the provider has none (Google Books).
+    pub author_id: String,
+    pub name: String,
+    /// Other ids of the same person (`authors.alternate_id` of the dump).
+    pub alternate_ids: Vec<String>,
+    /// Scored like the alias resolver's candidates (`score_label`).
+    pub score: f64,
+}
+
+/// One metadata source. Searches return hits best first; an empty list means "not found", `Err` a failed
+/// request or query.
+#[async_trait]
+pub trait MetadataProvider: Send + Sync {
+    fn kind(&self) -> ProviderKind;
+    async fn search_title(&self, title: &str, author: Option<&str>) -> Result<Vec<BookHit>>;
+    async fn search_author(&self, name: &str) -> Result<Vec<AuthorHit>>;
+    async fn search_isbn(&self, isbn: &str) -> Result<Vec<BookHit>>;
+    /// Other names of an author (pen names, spellings, translations), by the provider's id.
+    async fn author_aliases(&self, author_id: &str) -> Result<Vec<String>>;
+}
+
+/// Value of `--providers`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ProviderKind {
+    /// OpenLibrary dump imported in SQLite (`--db`), offline
+    OpenlibrarySqlite,
+    /// openlibrary.org search API
+    OpenlibraryHttp,
+    /// wikidata.org search API
+    Wikidata,
+    /// Google Books volumes API (optional key in GOOGLE_BOOKS_API_KEY)
+    GoogleBooks,
+}
+
+impl ProviderKind {
+    /// Name on the command line and in logs.
+    pub fn name(self) -> &'static str {
+        match self {
+            ProviderKind::OpenlibrarySqlite => "openlibrary-sqlite",
+            ProviderKind::OpenlibraryHttp => "openlibrary-http",
+            ProviderKind::Wikidata => "wikidata",
+            ProviderKind::GoogleBooks => "google-books",
+        }
+    }
+
+    /// Ids are OpenLibrary keys, comparable with the `works`/`authors` tables of the dump.
+    pub fn is_openlibrary(self) -> bool {
+        matches!(
+            self,
+            ProviderKind::OpenlibrarySqlite | ProviderKind::OpenlibraryHttp
+        )
+    }
+}
+
+/// Settings the providers of a registry are built from.
+pub struct RegistryOptions<'a> {
+    /// OpenLibrary SQLite database, for `openlibrary-sqlite`.
+    pub db: &'a Path,
+    /// Normalization `title_normalized` and `name_normalized` were built with.
+    pub normalize: fn(&str) -> String,
+    /// HTTP timeout of the online providers, in seconds.
+    pub timeout: u64,
+    /// Wikidata label languages, in order of preference.
+    pub langs: Vec<String>,
+}
+
+/// The providers of a run, in the order they are asked.
+pub struct Registry {
+    providers: Vec<Box<dyn MetadataProvider>>,
+}
+
+impl Registry {
+    /// One provider per kind, in the given order (repeats ignored). The SQLite database is opened read-only.
+    pub fn new(kinds: &[ProviderKind], options: &RegistryOptions) -> Result<Self> {
+        let http = match kinds
+            .iter()
+            .any(|kind| *kind != ProviderKind::OpenlibrarySqlite)
+        {
+            true => Some(CachedHttp::with_options(
+                options.timeout,
+                &ClientOptions::default(),
+            )?),
+            false => None,
+        };
+        let mut providers: Vec<Box<dyn MetadataProvider>> = Vec::new();
+        for &kind in kinds {
+            if providers.iter().any(|provider| provider.kind() == kind) {
+                continue;
+            }
+            let http = || http.clone().expect("HTTP client of the online providers");
+            providers.push(match kind {
+                ProviderKind::OpenlibrarySqlite => {
+                    Box::new(OpenLibrarySqlite::open(options.db, options.normalize)?)
+                }
+                ProviderKind::OpenlibraryHttp => {
+                    Box::new(OpenLibraryHttp(OpenLibraryClient::new(http())))
+                }
+                ProviderKind::Wikidata => Box::new(Wikidata(
+                    WikidataClient::new(http()).with_langs(options.langs.clone()),
+                )),
+                ProviderKind::GoogleBooks => {
+                    let key = std::env::var("GOOGLE_BOOKS_API_KEY")
+                        .ok()
+                        .filter(|key| !key.is_empty());
+                    Box::new(GoogleBooks(GoogleBooksClient::new(http(), key)))
+                }
+            });
+        }
+        Ok(Self { providers })
+    }
+
+    /// The provider of this kind, when the registry has one.
+    pub fn get(&self, kind: ProviderKind) -> Option<&dyn MetadataProvider> {
+        self.iter().find(|provider| provider.kind() == kind)
+    }
+
+    /// Providers in order.
+    pub fn iter(&self) -> impl Iterator<Item = &dyn MetadataProvider> {
+        self.providers.iter().map(|provider| provider.as_ref())
+    }
+
+    /// Best title hit of the first provider whose best hit scores at least `min_score`. A failed provider is
+    /// skipped; its error is returned only when no provider found anything.
+    pub async fn find_title(
+        &self,
+        title: &str,
+        author: Option<&str>,
+        min_score: f64,
+    ) -> Result<Option<(ProviderKind, BookHit)>> {
+        let mut error = None;
+        for provider in self.iter() {
+            match provider.search_title(title, author).await {
+                Ok(hits) => {
+                    if let Some(hit) = hits.into_iter().find(|hit| hit.score >= min_score) {
+                        return Ok(Some((provider.kind(), hit)));
+                    }
+                }
+                Err(e) => error = Some(e.context(provider.kind().name())),
+            }
+        }
+        error.map_or(Ok(None), Err)
+    }
+
+    /// Best author hit of the first provider whose best hit scores at least `min_score` (same error rule).
+    pub async fn find_author(
+        &self,
+        name: &str,
+        min_score: f64,
+    ) -> Result<Option<(ProviderKind, AuthorHit)>> {
+        let mut error = None;
+        for provider in self.iter() {
+            match provider.search_author(name).await {
+                Ok(hits) => {
+                    if let Some(hit) = hits.into_iter().find(|hit| hit.score >= min_score) {
+                        return Ok(Some((provider.kind(), hit)));
+                    }
+                }
+                Err(e) => error = Some(e.context(provider.kind().name())),
+            }
+        }
+        error.map_or(Ok(None), Err)
+    }
+}
+
+/// Title similarity: 1 for the same normalized title, else the token F1 of the two titles.
+fn title_score(query: &str, title: &str) -> f64 {
+    let breakdown = score_label(query, title, "");
+    if breakdown.exact {
+        1.0
+    } else {
+        breakdown.overlap
+    }
+}
+
+fn best_first<T>(mut hits: Vec<T>, score: impl Fn(&T) -> f64) -> Vec<T> {
+    hits.sort_by(|a, b| score(b).total_cmp(&score(a)));
+    hits
+}
+
+fn glob_escape(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if matches!(c, '*' | '?' | '[') {
+                format!("[{c}]")
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+/// The OpenLibrary dump of `scripts/import`: prefix (GLOB) searches on the normalized columns.
+pub struct OpenLibrarySqlite {
+    conn: Arc<Mutex<Connection>>,
+    normalize: fn(&str) -> String,
+}
+
+impl OpenLibrarySqlite {
+    pub fn open(db: &Path, normalize: fn(&str) -> String) -> Result<Self> {
+        let conn = Connection::open_with_flags(db, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .with_context(|| format!("Open database {:?}", db))?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            normalize,
+        })
+    }
+
+    /// Runs `query` on tokio's blocking threads, so a slow SQLite read does not hold up the async workers.
+    async fn query<T: Send + 'static>(
+        &self,
+        query: impl FnOnce(&Connection) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || query(&conn.lock().unwrap_or_else(|e| e.into_inner())))
+            .await?
+    }
+}
+
+#[async_trait]
+impl MetadataProvider for OpenLibrarySqlite {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::OpenlibrarySqlite
+    }
+
+    /// Works whose normalized title is the title, then works whose normalized title starts with it, in table
+    /// order; `SQLITE_LIMIT` in all.
+    async fn search_title(&self, title: &str, _author: Option<&str>) -> Result<Vec<BookHit>> {
+        let title = title.to_string();
+        let title_norm = (self.normalize)(&title);
+        if title_norm.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.query(move |conn| {
+            let select = "SELECT w.work_id, w.title, w.author_id, a.name FROM works w LEFT JOIN authors a ON a.author_id = w.author_id";
+            let hit = |row: &rusqlite::Row| {
+                let found: String = row.get(1)?;
+                Ok(BookHit {
+                    work_id: row.get(0)?,
+                    score: title_score(&title, &found),
+                    title: found,
+                    author_id: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                    author: row.get(3)?,
+                    isbn: None,
+                })
+            };
+            let mut stmt = conn.prepare(&format!("{select} WHERE w.title_normalized = ?1 LIMIT ?2"))?;
+            let mut hits: Vec<BookHit> = stmt.query_map(params![title_norm, SQLITE_LIMIT], hit)?.collect::<rusqlite::Result<_>>()?;
+            let rest = SQLITE_LIMIT - hits.len() as i64;
+            let mut stmt = conn.prepare(&format!("{select} WHERE w.title_normalized GLOB ?1 AND w.title_normalized <> ?2 LIMIT ?3"))?;
+            let prefixed = stmt.query_map(params![format!("{}*", glob_escape(&title_norm)), title_norm, rest], hit)?;
+            hits.extend(prefixed.collect::<rusqlite::Result<Vec<_>>>()?);
+            Ok(hits)
+        })
+        .await
+    }
+
+    /// Authors whose normalized name starts with the name, in table order.
+    async fn search_author(&self, name: &str) -> Result<Vec<AuthorHit>> {
+        let name = name.to_string();
+        let name_norm = (self.normalize)(&name);
+        if name_norm.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.query(move |conn| {
+            let mut stmt = conn.prepare("SELECT author_id, name, alternate_id FROM authors WHERE name_normalized GLOB ?1 LIMIT ?2")?;
+            let rows = stmt.query_map(params![format!("{}*", glob_escape(&name_norm)), SQLITE_LIMIT], |row| {
+                let found: String = row.get(1)?;
+                let alternates: Option<String> = row.get(2)?;
+                Ok(AuthorHit {
+                    author_id: row.get(0)?,
+                    score: score_label(&name, &found, "").score,
+                    name: found,
+                    alternate_ids: alternates.unwrap_or_default().split(',').map(str::trim).filter(|id| !id.is_empty()).map(str::to_string).collect(),
+                })
+            })?;
+            Ok(rows.collect::<rusqlite::Result<_>>()?)
+        })
+        .await
+    }
+
+    /// The dump has no ISBN column.
+    async fn search_isbn(&self, _isbn: &str) -> Result<Vec<BookHit>> {
+        Ok(Vec::new())
+    }
+
+    /// Names of the alternate ids, and the aliases `author-alias-online --update-db` stored for the author.
+    async fn author_aliases(&self, author_id: &str) -> Result<Vec<String>> {
+        let author_id = author_id.to_string();
+        self.query(move |conn| aliases(conn, &author_id)).await
+    }
+}
+
+/// Names of the alternate ids of `author_id`, then its `author_aliases` rows when the table exists.
+fn aliases(conn: &Connection, author_id: &str) -> Result<Vec<String>> {
+    let mut names: Vec<String> = Vec::new();
+    let mut stmt = conn.prepare(
+            "SELECT b.name FROM authors a JOIN authors b ON (',' || a.alternate_id || ',') GLOB ('*,' || b.author_id || ',*')
+             WHERE a.author_id = ?1 AND b.name IS NOT NULL",
+        )?;
+    names.extend(
+        stmt.query_map([author_id], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?,
+    );
+    let has_aliases: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'author_aliases')", [], |row| row.get(0),
+        )?;
+    if has_aliases {
+        let mut stmt = conn.prepare("SELECT alias FROM author_aliases WHERE author_id = ?1")?;
+        names.extend(
+            stmt.query_map([author_id], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?,
+        );
+    }
+    names.dedup();
+    Ok(names)
+}
+
+/// openlibrary.org: `search.json` for works and ISBNs, `search/authors.json`, author records for aliases.
+pub struct OpenLibraryHttp(pub OpenLibraryClient);
+
+fn work_hit(query: Option<&str>, doc: WorkDoc) -> BookHit {
+    let title = doc.title.clone().unwrap_or_default();
+    BookHit {
+        work_id: doc.work_id().to_string(),
+        score: query.map_or(1.0, |query| title_score(query, &title)),
+        title,
+        author_id: doc.author_key.into_iter().next().unwrap_or_default(),
+        author: doc.author_name.into_iter().next(),
+        isbn: doc.isbn.into_iter().next(),
+    }
+}
+
+#[async_trait]
+impl MetadataProvider for OpenLibraryHttp {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::OpenlibraryHttp
+    }
+
+    async fn search_title(&self, title: &str, author: Option<&str>) -> Result<Vec<BookHit>> {
+        let docs = self.0.search_works(title, author).await?;
+        Ok(best_first(
+            docs.into_iter()
+                .map(|doc| work_hit(Some(title), doc))
+                .collect(),
+            |hit| hit.score,
+        ))
+    }
+
+    async fn search_author(&self, name: &str) -> Result<Vec<AuthorHit>> {
+        Ok(self
+            .0
+            .search_candidates(name)
+            .await?
+            .into_iter()
+            .map(|(id, label, _, score)| AuthorHit {
+                author_id: id,
+                name: label,
+                alternate_ids: Vec::new(),
+                score,
+            })
+            .collect())
+    }
+
+    async fn search_isbn(&self, isbn: &str) -> Result<Vec<BookHit>> {
+        Ok(self
+            .0
+            .search_isbn(isbn)
+            .await?
+            .into_iter()
+            .map(|doc| work_hit(None, doc))
+            .collect())
+    }
+
+    async fn author_aliases(&self, author_id: &str) -> Result<Vec<String>> {
+        self.0.alternate_names(author_id).await
+    }
+}
+
+/// wikidata.org: `wbsearchentities` for works and people, labels and aliases of the item.
+pub struct Wikidata(pub WikidataClient);
+
+/// "1862 novel by Victor Hugo" -> "Victor Hugo".
+fn described_author(description: &str) -> Option<String> {
+    description
+        .rsplit_once(" by ")
+        .map(|(_, author)| author.trim().to_string())
+        .filter(|a| !a.is_empty())
+}
+
+#[async_trait]
+impl MetadataProvider for Wikidata {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::Wikidata
+    }
+
+    /// Items described as a written work; with `author`, the author named in the description must share a word with it.
+    async fn search_title(&self, title: &str, author: Option<&str>) -> Result<Vec<BookHit>> {
+        let wanted = author.map(normalize_for_score).filter(|a| !a.is_empty());
+        let hits = self
+            .0
+            .search_candidates(title)
+            .await?
+            .into_iter()
+            .filter(|(_, _, desc, _)| {
+                let desc = desc.to_lowercase();
+                WRITTEN_WORK_WORDS.iter().any(|word| desc.contains(word))
+            })
+            .map(|(id, label, desc, _)| BookHit {
+                work_id: id,
+                score: title_score(title, &label),
+                title: label,
+                author_id: String::new(),
+                author: described_author(&desc),
+                isbn: None,
+            })
+            .filter(|hit| match (&wanted, &hit.author) {
+                (Some(wanted), Some(found)) => {
+                    let found = normalize_for_score(found);
+                    wanted
+                        .split(' ')
+                        .any(|word| found.split(' ').any(|other| other == word))
+                }
+                _ => true,
+            })
+            .collect();
+        Ok(best_first(hits, |hit: &BookHit| hit.score))
+    }
+
+    async fn search_author(&self, name: &str) -> Result<Vec<AuthorHit>> {
+        Ok(self
+            .0
+            .search_candidates(name)
+            .await?
+            .into_iter()
+            .map(|(id, label, _, score)| AuthorHit {
+                author_id: id,
+                name: label,
+                alternate_ids: Vec::new(),
+                score,
+            })
+            .collect())
+    }
+
+    /// ISBN claims are stored hyphenated, which a bare ISBN cannot be matched against reliably.
+    async fn search_isbn(&self, _isbn: &str) -> Result<Vec<BookHit>> {
+        Ok(Vec::new())
+    }
+
+    async fn author_aliases(&self, author_id: &str) -> Result<Vec<String>> {
+        let data = self
+            .0
+            .entities(author_id, "labels|aliases", &self.0.label_langs().join("|"))
+            .await?;
+        let Some(entity) = data.entity(author_id) else {
+            return Ok(Vec::new());
+        };
+        let mut names: Vec<String> = Vec::new();
+        let labels = entity.labels.iter().flat_map(|labels| labels.values());
+        let aliases = entity
+            .aliases
+            .iter()
+            .flat_map(|aliases| aliases.values().flatten());
+        for name in labels.chain(aliases).map(|label| label.value.clone()) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        Ok(names)
+    }
+}
+
+/// Google Books volumes: no author records, so authors are the names credited on matching volumes.
+pub struct GoogleBooks(pub GoogleBooksClient);
+
+fn volume_hit(query: Option<&str>, volume: Volume) -> BookHit {
+    let title = volume.info.title.clone().unwrap_or_default();
+    BookHit {
+        work_id: volume.id,
+        score: query.map_or(1.0, |query| title_score(query, &title)),
+        title,
+        author_id: String::new(),
+        author: volume.info.authors.first().cloned(),
+        isbn: volume.info.isbn().map(str::to_string),
+    }
+}
+
+#[async_trait]
+impl MetadataProvider for GoogleBooks {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::GoogleBooks
+    }
+
+    async fn search_title(&self, title: &str, author: Option<&str>) -> Result<Vec<BookHit>> {
+        let volumes = self.0.search(title, author).await?;
+        Ok(best_first(
+            volumes
+                .into_iter()
+                .map(|volume| volume_hit(Some(title), volume))
+                .collect(),
+            |hit| hit.score,
+        ))
+    }
+
+    async fn search_author(&self, name: &str) -> Result<Vec<AuthorHit>> {
+        let mut hits: Vec<AuthorHit> = Vec::new();
+        for volume in self.0.search("", Some(name)).await? {
+            for author in volume.info.authors {
+                if hits.iter().any(|hit| hit.name == author) {
+                    continue;
+                }
+                hits.push(AuthorHit {
+                    author_id: String::new(),
+                    score: score_label(name, &author, "").score,
+                    name: author,
+                    alternate_ids: Vec::new(),
+                });
+            }
+        }
+        Ok(best_first(hits, |hit| hit.score))
+    }
+
+    async fn search_isbn(&self, isbn: &str) -> Result<Vec<BookHit>> {
+        Ok(self
+            .0
+            .search_isbn(isbn)
+            .await?
+            .into_iter()
+            .map(|volume| volume_hit(None, volume))
+            .collect())
+    }
+
+    async fn author_aliases(&self, _author_id: &str) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+}
@@ -0,0 +1,97 @@
+use serde::Deserialize;
+
+use crate::http::CachedHttp;
+use crate::wikidata::{score_search_items, normalize_for_score, WikidataSearchItem};
+
+const OPENLIBRARY_AUTHORS: &str = "https://openlibrary.org/search/authors.json";
+const OPENLIBRARY_WORKS: &str = "https://openlibrary.org/search.json";
+const OPENLIBRARY_AUTHOR: &str = "https://openlibrary.org/authors";
+
+#[derive(Debug, Deserialize)]
+pub struct AuthorSearchResponse { pub docs: Vec<AuthorDoc> }
+
+#[derive(Debug, Deserialize)]
+pub struct AuthorDoc {
+    pub key: String,
+    pub name: Option<String>,
+    pub birth_date: Option<String>,
+    pub top_work: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WorkSearchResponse { pub docs: Vec<WorkDoc> }
+
+#[derive(Debug, Deserialize)]
+pub struct WorkDoc {
+    /// `/works/OL45883W`.
+    pub key: String,
+    pub title: Option<String>,
+    #[serde(default)]
+    pub author_key: Vec<String>,
+    #[serde(default)]
+    pub author_name: Vec<String>,
+    #[serde(default)]
+    pub isbn: Vec<String>,
+}
+
+impl WorkDoc {
+    /// Work key without the `/works/` prefix, as in the `works` table of the database.
+    pub fn work_id(&self) -> &str { self.key.trim_start_matches("/works/") }
+}
+
+#[derive(Debug, Deserialize)]
+struct AuthorRecord {
+    #[serde(default)]
+    alternate_names: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct OpenLibraryClient { http: CachedHttp }
+
+impl OpenLibraryClient {
+    pub fn new(http: CachedHttp) -> Self { Self { http } }
+
+    /// Author search scored like Wikidata hits, best first: (OL author key such as `OL2162284A`, name,
+    /// birth date / top work, score). The key is the `author_id` of the sort tool's database.
+    pub async fn search_candidates(&self, query: &str) -> anyhow::Result<Vec<(String, String, String, f64)>> {
+        let key = normalize_for_score(query);
+        let body = self.http.get_text(OPENLIBRARY_AUTHORS, "openlibrary", &key, &[("q", query), ("limit", "10")]).await?;
+        let data: AuthorSearchResponse = serde_json::from_str(&body)?;
+        let items = data.docs.into_iter()
+            .map(|doc| {
+                let details: Vec<String> = [doc.birth_date, doc.top_work.map(|w| format!("top work: {}", w))].into_iter().flatten().collect();
+                WikidataSearchItem { id: doc.key.trim_start_matches("/authors/").to_string(), label: doc.name, description: Some(details.join(", ")) }
+            })
+            .collect();
+        Ok(score_search_items(query, items))
+    }
+
+    /// Works matching `title` (and `author`), in API relevance order.
+    pub async fn search_works(&self, title: &str, author: Option<&str>) -> anyhow::Result<Vec<WorkDoc>> {
+        let author = author.filter(|a| !a.trim().is_empty());
+        let key = normalize_for_score(&format!("{} | {}", title, author.unwrap_or_default()));
+        let mut query = vec![("title", title), ("limit", "10"), ("fields", "key,title,author_key,author_name,isbn")];
+        if let Some(author) = author { query.push(("author", author)); }
+        let body = self.http.get_text(OPENLIBRARY_WORKS, "openlibrary-works", &key, &query).await?;
+        let data: WorkSearchResponse = serde_json::from_str(&body)?;
+        Ok(data.docs)
+    }
+
+    /// Works with an edition carrying this ISBN.
+    pub async fn search_isbn(&self, isbn: &str) -> anyhow::Result<Vec<WorkDoc>> {
+        let digits: String = isbn.chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+        let body = self.http.get_text(OPENLIBRARY_WORKS, "openlibrary-isbn", &digits, &[
+            ("isbn", digits.as_str()), ("limit", "10"), ("fields", "key,title,author_key,author_name,isbn"),
+        ]).await?;
+        let data: WorkSearchResponse = serde_json::from_str(&body)?;
+        Ok(data.docs)
+    }
+
+    /// `alternate_names` of an author record (`OL2162284A`).
+    pub async fn alternate_names(&self, author_id: &str) -> anyhow::Result<Vec<String>> {
+        let url = format!("{}/{}.json", OPENLIBRARY_AUTHOR, author_id);
+        let body = self.http.get_text(&url, "openlibrary-author", author_id, &[]).await?;
+        let record: AuthorRecord = serde_json::from_str(&body)?;
+        Ok(record.alternate_names)
+    }
+}
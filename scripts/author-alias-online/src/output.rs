@@ -0,0 +1,186 @@
+use anyhow::{Context, Result};
+use crate::wikidata::score_label;
+use clap::ValueEnum;
+use serde_json::json;
+use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::cli::Hit;
+
+const CSV_HEADER: [&str; 8] = ["author_local", "wikidata_id", "label", "description", "score", "source", "isni", "original_label"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// One row per author: the winning candidate only.
+    Csv,
+    /// One JSON object per line with every candidate and its score breakdown.
+    Jsonl,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Miss,
+    Error,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Miss => "miss",
+            Status::Error => "error",
+        }
+    }
+}
+
+/// Everything known about one author at the end of its lookup.
+pub struct AuthorRecord<'a> {
+    pub name: &'a str,
+    pub query: &'a str,
+    pub status: Status,
+    pub hit: Option<&'a Hit>,
+    pub source: &'a str,
+    /// Label in its original script when `hit` carries a Latin-script one (--latin-names).
+    pub original_label: Option<&'a str>,
+    pub isni: Option<&'a str>,
+    pub error: Option<&'a str>,
+    /// Scored candidates per provider queried, best first.
+    pub candidates: &'a [(&'static str, Vec<Hit>)],
+}
+
+/// A record of a previous run, kept verbatim when resuming.
+pub struct PreviousRecord {
+    pub name: String,
+    pub status: Status,
+    raw: Raw,
+}
+
+enum Raw {
+    Csv([String; 8]),
+    Jsonl(String),
+}
+
+/// Records of an existing output file; empty when absent. Older CSVs lacking `isni` or `original_label` are padded.
+pub fn read_previous(path: &Path, format: Format) -> Result<Vec<PreviousRecord>> {
+    if !path.exists() { return Ok(Vec::new()); }
+    let mut records = Vec::new();
+    match format {
+        Format::Csv => {
+            let mut reader = csv::ReaderBuilder::new().flexible(true).from_path(path)
+                .with_context(|| format!("Read previous CSV {:?}", path))?;
+            let headers = reader.headers()?.clone();
+            let columns: Vec<Option<usize>> = CSV_HEADER.iter().map(|name| headers.iter().position(|h| h == *name)).collect();
+            for record in reader.records() {
+                let record = record?;
+                let row: [String; 8] = std::array::from_fn(|i| columns[i].and_then(|c| record.get(c)).unwrap_or("").to_string());
+                if row[0].is_empty() { continue; }
+                let status = if row[5] == "error" { Status::Error } else if row[1].is_empty() { Status::Miss } else { Status::Ok };
+                records.push(PreviousRecord { name: row[0].clone(), status, raw: Raw::Csv(row) });
+            }
+        }
+        Format::Jsonl => {
+            let file = fs::File::open(path).with_context(|| format!("Read previous JSONL {:?}", path))?;
+            for line in BufReader::new(file).lines() {
+                let line = line?;
+                // A line cut by a crash is dropped; its author is queried again.
+                let Ok(value) = serde_json::from_str::<serde_json::Value>(&line) else { continue; };
+                let Some(name) = value["author_local"].as_str().filter(|n| !n.is_empty()) else { continue; };
+                let status = match value["status"].as_str() { Some("ok") => Status::Ok, Some("error") => Status::Error, _ => Status::Miss };
+                records.push(PreviousRecord { name: name.to_string(), status, raw: Raw::Jsonl(line) });
+            }
+        }
+    }
+    Ok(records)
+}
+
+/// The run's output file, rewritten with the kept records of a previous run first.
+pub enum Output {
+    Csv(Box<csv::Writer<fs::File>>),
+    Jsonl(BufWriter<fs::File>),
+}
+
+impl Output {
+    pub fn create(path: &Path, format: Format, previous: &[PreviousRecord]) -> Result<Self> {
+        let mut output = match format {
+            Format::Csv => {
+                let mut w = csv::WriterBuilder::new().has_headers(true).from_path(path)
+                    .with_context(|| format!("Open output CSV {:?}", path))?;
+                w.write_record(CSV_HEADER)?;
+                Output::Csv(Box::new(w))
+            }
+            Format::Jsonl => Output::Jsonl(BufWriter::new(
+                fs::File::create(path).with_context(|| format!("Open output JSONL {:?}", path))?,
+            )),
+        };
+        for record in previous {
+            match (&mut output, &record.raw) {
+                (Output::Csv(w), Raw::Csv(row)) => w.write_record(row)?,
+                (Output::Jsonl(w), Raw::Jsonl(line)) => writeln!(w, "{}", line)?,
+                _ => {}
+            }
+        }
+        output.flush()?;
+        Ok(output)
+    }
+
+    /// Writes and flushes one author, so an interrupted run can resume from the file.
+    pub fn write(&mut self, record: &AuthorRecord) -> Result<()> {
+        match self {
+            Output::Csv(w) => {
+                let row = match (record.status, record.hit) {
+                    (Status::Ok, Some((id, label, desc, score))) => [record.name, id, label, desc, &format!("{score:.2}"), record.source, record.isni.unwrap_or(""), record.original_label.unwrap_or("")].map(str::to_string),
+                    (Status::Error, _) => [record.name, "", "", "", "0.00", "error", "", ""].map(str::to_string),
+                    _ => [record.name, "", "", "", "0.00", "", "", ""].map(str::to_string),
+                };
+                w.write_record(&row)?;
+            }
+            Output::Jsonl(w) => writeln!(w, "{}", jsonl_record(record))?,
+        }
+        self.flush()
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        match self {
+            Output::Csv(w) => w.flush()?,
+            Output::Jsonl(w) => w.flush()?,
+        }
+        Ok(())
+    }
+}
+
+fn jsonl_record(record: &AuthorRecord) -> serde_json::Value {
+    let candidates: Vec<serde_json::Value> = record.candidates.iter()
+        .flat_map(|(provider, hits)| hits.iter().map(move |hit| (*provider, hit)))
+        .map(|(provider, (id, label, desc, score))| {
+            let breakdown = score_label(record.query, label, desc);
+            json!({
+                "provider": provider,
+                "id": id,
+                "label": label,
+                "description": desc,
+                "score": score,
+                // Disambiguation moves the final score away from the label score.
+                "breakdown": {
+                    "role_bonus": breakdown.role_bonus,
+                    "overlap": breakdown.overlap,
+                    "exact": breakdown.exact,
+                    "label_score": breakdown.score,
+                    "adjustment": score - breakdown.score,
+                },
+            })
+        })
+        .collect();
+    json!({
+        "author_local": record.name,
+        "query": record.query,
+        "status": record.status.as_str(),
+        "source": record.source,
+        "winner": record.hit.map(|(id, label, desc, score)| json!({ "id": id, "label": label, "description": desc, "score": score })),
+        "original_label": record.original_label,
+        "isni": record.isni,
+        "error": record.error,
+        "candidates": candidates,
+    })
+}
@@ -0,0 +1,31 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// One folder change of the run: applied live, or what a dry-run would do.
+#[derive(Serialize)]
+pub struct PlanEntry {
+    pub source: String,
+    pub target: String,
+    pub wikidata_id: String,
+    /// Match score; empty for pen-name merges.
+    pub score: Option<f64>,
+    /// `rename`, `merge` (target already exists), or `hold` (under --move-threshold, left as is).
+    pub action: &'static str,
+}
+
+/// Writes the plan as a JSON array when `path` ends in `.json`, as CSV otherwise.
+pub fn write_plan(path: &Path, entries: &[PlanEntry]) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) { fs::create_dir_all(parent)?; }
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")) {
+        let body = serde_json::to_string_pretty(entries)?;
+        fs::write(path, body + "\n").with_context(|| format!("Write plan {:?}", path))?;
+        return Ok(());
+    }
+    let mut w = csv::Writer::from_path(path).with_context(|| format!("Open plan CSV {:?}", path))?;
+    if entries.is_empty() { w.write_record(["source", "target", "wikidata_id", "score", "action"])?; }
+    for entry in entries { w.serialize(entry)?; }
+    w.flush()?;
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use serde::Deserialize;
+
+use crate::http::CachedHttp;
+use crate::wikidata::{score_search_items, WikidataSearchItem};
+
+const VIAF_AUTOSUGGEST: &str = "https://viaf.org/viaf/AutoSuggest";
+
+#[derive(Debug, Deserialize)]
+pub struct ViafSuggestResponse { pub result: Option<Vec<ViafSuggestItem>> }
+
+#[derive(Debug, Deserialize)]
+pub struct ViafSuggestItem {
+    #[serde(rename = "displayForm")]
+    pub display_form: String,
+    pub nametype: Option<String>,
+    pub viafid: String,
+}
+
+#[derive(Clone)]
+pub struct ViafClient { http: CachedHttp }
+
+impl ViafClient {
+    pub fn new(http: CachedHttp) -> Self { Self { http } }
+
+    /// Personal names from AutoSuggest, scored like Wikidata hits and best first: (VIAF id, label, dates, score).
+    pub async fn search_candidates(&self, query: &str) -> anyhow::Result<Vec<(String, String, String, f64)>> {
+        let key = crate::wikidata::normalize_for_score(query);
+        let body = self.http.get_text(VIAF_AUTOSUGGEST, "viaf", &key, &[("query", query)]).await?;
+        let data: ViafSuggestResponse = serde_json::from_str(&body)?;
+        let items = data.result.unwrap_or_default().into_iter()
+            .filter(|item| item.nametype.as_deref() == Some("personal"))
+            .map(|item| {
+                let (label, dates) = split_dates(&item.display_form);
+                WikidataSearchItem { id: item.viafid, label: Some(label), description: Some(dates) }
+            })
+            .collect();
+        Ok(score_search_items(query, items))
+    }
+}
+
+/// "King, Stephen, 1947-" -> ("King, Stephen", "1947-"): date parts would hurt token scoring.
+fn split_dates(display: &str) -> (String, String) {
+    let (names, dates): (Vec<&str>, Vec<&str>) = display.split(',').map(str::trim).filter(|p| !p.is_empty())
+        .partition(|part| !part.chars().any(|c| c.is_ascii_digit()));
+    (names.join(", "), dates.join(", "))
+}
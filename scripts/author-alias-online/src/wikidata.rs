@@ -0,0 +1,455 @@
+use chrono::Datelike;
+use regex::Regex;
+use serde::Deserialize;
+use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::bnf::SparqlResponse;
+use crate::http::CachedHttp;
+
+#[derive(Debug, Deserialize)]
+pub struct WikidataSearchResponse {
+    pub search: Vec<WikidataSearchItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WikidataSearchItem {
+    pub id: String,
+    pub label: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WikidataEntityResponse {
+    pub entities: std::collections::HashMap<String, WikidataEntity>,
+}
+
+impl WikidataEntityResponse {
+    /// Entity requested as `id`. A merged item comes back under its target QID with a
+    /// `redirects` note, so it is found through that too.
+    pub fn entity(&self, id: &str) -> Option<&WikidataEntity> {
+        self.entities.get(id)
+            .or_else(|| self.entities.values().find(|e| e.redirects.as_ref().is_some_and(|r| r.from == id)))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WikidataRedirect { pub from: String, pub to: String }
+
+#[derive(Debug, Deserialize)]
+pub struct WikidataEntity {
+    /// Canonical QID (the redirect target for a merged item).
+    pub id: Option<String>,
+    pub redirects: Option<WikidataRedirect>,
+    pub claims: Option<std::collections::HashMap<String, Vec<WikidataClaim>>>,
+    pub labels: Option<std::collections::HashMap<String, WikidataLabel>>, // fallback
+    pub aliases: Option<std::collections::HashMap<String, Vec<WikidataLabel>>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WikidataLabel { pub value: String }
+
+#[derive(Debug, Deserialize)]
+pub struct WikidataClaim { pub mainsnak: WikidataSnak }
+
+#[derive(Debug, Deserialize)]
+pub struct WikidataSnak { pub datavalue: Option<WikidataValue> }
+
+#[derive(Debug, Deserialize)]
+pub struct WikidataValue { pub value: serde_json::Value }
+
+const WIKIDATA_API: &str = "https://www.wikidata.org/w/api.php";
+const WIKIDATA_SPARQL: &str = "https://query.wikidata.org/sparql";
+/// `wbgetentities` accepts at most 50 ids per request.
+const MAX_ENTITY_IDS: usize = 50;
+
+/// P106 values treated as writer-like: writer, novelist, poet, author, playwright, screenwriter,
+/// essayist, journalist, children's writer, science fiction writer, translator, historian,
+/// philosopher, literary critic, comics artist, biographer.
+const WRITER_OCCUPATIONS: [&str; 16] = [
+    "Q36180", "Q6625963", "Q49757", "Q482980", "Q214917", "Q28389", "Q11774202", "Q1930187",
+    "Q4853732", "Q18844224", "Q333634", "Q201788", "Q4964182", "Q4263842", "Q715301", "Q864380",
+];
+/// P31 (instance of) values accepted by `retain_persons`: human, pseudonym, collective pseudonym.
+const PERSON_CLASSES: [&str; 3] = ["Q5", "Q61002", "Q16017119"];
+const DISAMBIGUATION_STEP: f64 = 0.2;
+/// Candidates born less than this many years ago are unlikely to be the author of a library book.
+const MIN_AUTHOR_AGE: i32 = 16;
+/// Languages written in Latin script, tried for a folder name when an entity's label is not (`mul` is the default label).
+const LATIN_LANGS: [&str; 8] = ["en", "fr", "de", "es", "it", "pt", "nl", "mul"];
+
+pub fn strip_accents(s: &str) -> String { s.nfkd().filter(|c| !is_combining_mark(*c)).collect() }
+
+/// True when every letter of `s` is Latin script, accents allowed (false for Cyrillic, Greek, CJK...).
+pub fn is_latin(s: &str) -> bool {
+    strip_accents(s).chars().filter(|c| c.is_alphabetic())
+        .all(|c| c.is_ascii_alphabetic() || matches!(c, '\u{00C0}'..='\u{024F}' | '\u{1E00}'..='\u{1EFF}'))
+}
+
+pub fn normalize_query(name: &str) -> String {
+    // Remove brackets/parentheses, collapse whitespace, prefer "Last, First" -> "First Last"
+    let bracket_re = Regex::new(r"\[[^\]]+\]").unwrap();
+    let paren_re = Regex::new(r"\([^\)]+\)").unwrap();
+    let mut s = bracket_re.replace_all(name, "").to_string();
+    s = paren_re.replace_all(&s, "").to_string();
+    s = s.nfkd().collect::<String>();
+    s = s
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c.is_whitespace() || c == ',' { c } else { ' ' })
+        .collect::<String>();
+    s = s.split_whitespace().collect::<Vec<_>>().join(" ");
+    if let Some(idx) = s.find(',') {
+        let last = s[..idx].trim();
+        let first = s[idx + 1..].trim();
+        if !first.is_empty() && !last.is_empty() {
+            return format!("{} {}", first, last);
+        }
+    }
+    s
+}
+
+/// Best-scoring search hit for `query` as (id, label, description, score).
+pub fn best_search_item(query: &str, items: Vec<WikidataSearchItem>) -> Option<(String, String, String, f64)> {
+    score_search_items(query, items).into_iter().next()
+}
+
+/// Every search hit as (id, label, description, score), best first (ties keep API order).
+pub fn score_search_items(query: &str, items: Vec<WikidataSearchItem>) -> Vec<(String, String, String, f64)> {
+    let mut scored: Vec<(String, String, String, f64)> = items.into_iter()
+        .map(|item| {
+            let label = item.label.unwrap_or_default();
+            let desc = item.description.unwrap_or_default();
+            let score = score_label(query, &label, &desc).score;
+            (item.id, label, desc, score)
+        })
+        .collect();
+    scored.sort_by(|a, b| b.3.total_cmp(&a.3));
+    scored
+}
+
+/// How a candidate label scored against the query.
+#[derive(Debug, Clone)]
+pub struct ScoreBreakdown {
+    /// 0.5 when the description reads like a writer's.
+    pub role_bonus: f64,
+    /// Best token F1 of the query against the label, as "First Last" or "Last, First".
+    pub overlap: f64,
+    /// Normalized label equals the query in either form (score 1.0).
+    pub exact: bool,
+    pub score: f64,
+}
+
+pub fn score_label(query: &str, label: &str, desc: &str) -> ScoreBreakdown {
+    // Scoring: normalize query and candidate labels; try both First Last and Last, First forms
+    let q = normalize_for_score(query);
+    let d = desc.to_ascii_lowercase();
+    // normalize label as First Last and as Last, First
+    let label_fl = normalize_for_score(label);
+    let inv_raw = invert_first_last(label);
+    let label_lf = inv_raw.as_ref().map(|s| normalize_for_score(s));
+    let mut score = 0.0;
+    let mut role_bonus = 0.0;
+    // role bonus
+    if d.contains("writer") || d.contains("author") || d.contains("novelist") || d.contains("poet") || d.contains("écrivain") {
+        role_bonus = 0.5;
+        score += role_bonus;
+    }
+    // exact token equality after normalization yields full score
+    let exact = label_fl == q || label_lf.as_deref() == Some(&q);
+    if exact { score = 1.0; }
+    // choose best overlap against query (F1-like instead of Jaccard)
+    let ov_fl = token_overlap_f1(&q, &label_fl);
+    let ov_lf = label_lf.as_deref().map(|s| token_overlap_f1(&q, s)).unwrap_or(0.0);
+    let ov = ov_fl.max(ov_lf);
+    if score < 1.0 {
+        let mut partial = 0.5 * ov;
+        if ov >= 0.90 { partial = 0.5; }
+        score += partial;
+    }
+    ScoreBreakdown { role_bonus, overlap: ov, exact, score }
+}
+
+/// Async Wikidata client used by the binary's concurrent lookups. Clones share the label cache.
+#[derive(Clone)]
+pub struct WikidataClient { http: CachedHttp, langs: Arc<Vec<String>>, labels: Arc<Mutex<HashMap<String, Option<String>>>> }
+
+impl WikidataClient {
+    pub fn new(http: CachedHttp) -> Self { Self { http, langs: Arc::new(vec!["en".to_string()]), labels: Arc::default() } }
+
+    /// Languages in order of preference for searches and labels (default `en`).
+    pub fn with_langs(mut self, langs: Vec<String>) -> Self {
+        if !langs.is_empty() { self.langs = Arc::new(langs); }
+        self
+    }
+
+    /// The preferred languages, then English and French, which most entities are labelled in.
+    pub fn label_langs(&self) -> Vec<&str> {
+        let mut langs: Vec<&str> = Vec::new();
+        for lang in self.langs.iter().map(String::as_str).chain(["en", "fr"]) {
+            if !langs.contains(&lang) { langs.push(lang); }
+        }
+        langs
+    }
+
+    /// Label of `labels` in the first available preferred language, else English, else French.
+    pub fn pick_label<'a>(&self, labels: &'a HashMap<String, WikidataLabel>) -> Option<&'a str> {
+        self.label_langs().into_iter().find_map(|lang| labels.get(lang)).map(|l| l.value.as_str())
+    }
+
+    /// `label_langs`, then the other `LATIN_LANGS`: the languages to fetch for `latin_label`.
+    pub fn latin_langs(&self) -> Vec<&str> {
+        let mut langs = self.label_langs();
+        for lang in LATIN_LANGS {
+            if !langs.contains(&lang) { langs.push(lang); }
+        }
+        langs
+    }
+
+    /// First Latin-script label of `entity` in `latin_langs`, else alias, else its Hepburn romanization
+    /// (P2125, family name first, turned into "Given Family"). The entity must be fetched in `latin_langs`.
+    pub fn latin_label(&self, entity: &WikidataEntity) -> Option<String> {
+        let langs = self.latin_langs();
+        let label = entity.labels.as_ref()
+            .and_then(|labels| langs.iter().filter_map(|lang| labels.get(*lang)).find(|l| is_latin(&l.value)));
+        let alias = || entity.aliases.as_ref()
+            .and_then(|aliases| langs.iter().filter_map(|lang| aliases.get(*lang)).flatten().find(|l| is_latin(&l.value)));
+        if let Some(l) = label.or_else(alias) { return Some(l.value.clone()); }
+        let romanized = entity.claims.as_ref()?.get("P2125")?.iter()
+            .find_map(|c| c.mainsnak.datavalue.as_ref()?.value.as_str())?;
+        let mut tokens: Vec<&str> = romanized.split_whitespace().collect();
+        if tokens.len() >= 2 { tokens.rotate_left(1); }
+        Some(tokens.join(" ")).filter(|s| !s.is_empty())
+    }
+
+    /// Best-scoring search hit; `Err` on network/API failures, `Ok(None)` on no hit.
+    pub async fn search(&self, query: &str) -> anyhow::Result<Option<(String, String, String, f64)>> {
+        Ok(self.search_candidates(query).await?.into_iter().next())
+    }
+
+    /// All scored search hits, best first, from the first preferred language that has any.
+    pub async fn search_candidates(&self, query: &str) -> anyhow::Result<Vec<(String, String, String, f64)>> {
+        for lang in self.langs.iter() {
+            let key = format!("{}|{}", lang, normalize_for_score(query));
+            let body = self.http.get_text(WIKIDATA_API, "search", &key, &[
+                ("action", "wbsearchentities"), ("search", query), ("format", "json"),
+                ("type", "item"), ("language", lang), ("limit", "10"),
+            ]).await?;
+            let data: WikidataSearchResponse = serde_json::from_str(&body)?;
+            let candidates = score_search_items(query, data.search);
+            if !candidates.is_empty() { return Ok(candidates); }
+        }
+        Ok(Vec::new())
+    }
+
+    /// Humans (P31 Q5) with a writer-like occupation whose label or alias in a preferred language
+    /// or English is exactly the query, scored like search hits, best first. Precise for common
+    /// names, but misses spelling variants.
+    pub async fn sparql_candidates(&self, query: &str) -> anyhow::Result<Vec<(String, String, String, f64)>> {
+        let name = query.trim().replace('\\', "\\\\").replace('"', "\\\"");
+        if name.is_empty() { return Ok(Vec::new()); }
+        let occupations: Vec<String> = WRITER_OCCUPATIONS.iter().map(|q| format!("wd:{}", q)).collect();
+        let langs = self.label_langs();
+        let names: Vec<String> = langs.iter().map(|lang| format!("\"{}\"@{}", name, lang)).collect();
+        // The label service picks ?itemLabel / ?itemDescription in the first available language.
+        let sparql = format!(r#"SELECT DISTINCT ?item ?itemLabel ?itemDescription WHERE {{
+  VALUES ?name {{ {names} }}
+  VALUES ?occupation {{ {occupations} }}
+  ?item rdfs:label|skos:altLabel ?name ; wdt:P31 wd:Q5 ; wdt:P106 ?occupation .
+  SERVICE wikibase:label {{ bd:serviceParam wikibase:language "{langs}" . }}
+}} LIMIT 10"#, names = names.join(" "), occupations = occupations.join(" "), langs = langs.join(","));
+        let key = format!("{}|{}", self.langs.join(","), normalize_for_score(query));
+        let body = self.http.get_text(WIKIDATA_SPARQL, "sparql", &key, &[("query", sparql.as_str()), ("format", "json")]).await?;
+        let data: SparqlResponse = serde_json::from_str(&body)?;
+        let items = data.results.bindings.into_iter()
+            .filter_map(|mut row| {
+                let uri = row.remove("item")?.value;
+                Some(WikidataSearchItem {
+                    id: uri.rsplit('/').next()?.to_string(),
+                    label: Some(row.remove("itemLabel").map(|l| l.value).unwrap_or_else(|| query.trim().to_string())),
+                    description: row.remove("itemDescription").map(|d| d.value),
+                })
+            })
+            .collect();
+        Ok(score_search_items(query, items))
+    }
+
+    /// Drops candidates whose P31 (instance of) is not a person class: bands, publishers,
+    /// fictional characters and book series can otherwise win on label overlap alone.
+    /// Candidates without any P31 claim are kept.
+    pub async fn retain_persons(&self, candidates: &mut Vec<(String, String, String, f64)>) -> anyhow::Result<()> {
+        if candidates.is_empty() { return Ok(()); }
+        let ids: Vec<&str> = candidates.iter().map(|c| c.0.as_str()).collect();
+        let data = self.entities(&ids.join("|"), "claims", "en").await?;
+        candidates.retain(|candidate| {
+            let classes: Vec<&str> = data.entity(&candidate.0).and_then(|e| e.claims.as_ref())
+                .and_then(|claims| claims.get("P31")).into_iter().flatten()
+                .filter_map(|c| c.mainsnak.datavalue.as_ref()?.value.get("id")?.as_str())
+                .collect();
+            classes.is_empty() || classes.iter().any(|class| PERSON_CLASSES.contains(class))
+        });
+        Ok(())
+    }
+
+    /// Re-ranks the candidates scoring within `margin` of the best one using their claims:
+    /// writer-like occupations (P106) gain `DISAMBIGUATION_STEP`, other occupations lose it, and
+    /// so do implausible dates (P569 birth too recent, P570 death before birth).
+    pub async fn disambiguate(&self, candidates: &mut [(String, String, String, f64)], margin: f64) -> anyhow::Result<()> {
+        let Some(top) = candidates.first().map(|c| c.3) else { return Ok(()); };
+        let close = candidates.iter().take_while(|c| top - c.3 <= margin).count();
+        if close < 2 { return Ok(()); }
+        let ids: Vec<&str> = candidates[..close].iter().map(|c| c.0.as_str()).collect();
+        let data = self.entities(&ids.join("|"), "claims", "en").await?;
+        for candidate in candidates[..close].iter_mut() {
+            let Some(claims) = data.entity(&candidate.0).and_then(|e| e.claims.as_ref()) else { continue; };
+            let occupations: Vec<&str> = claims.get("P106").into_iter().flatten()
+                .filter_map(|c| c.mainsnak.datavalue.as_ref()?.value.get("id")?.as_str())
+                .collect();
+            let mut adjust = 0.0;
+            if occupations.iter().any(|o| WRITER_OCCUPATIONS.contains(o)) { adjust += DISAMBIGUATION_STEP; }
+            else if !occupations.is_empty() { adjust -= DISAMBIGUATION_STEP; }
+            let birth = claim_year(claims.get("P569"));
+            let death = claim_year(claims.get("P570"));
+            let too_young = birth.is_some_and(|b| b > current_year() - MIN_AUTHOR_AGE);
+            let died_before_birth = matches!((birth, death), (Some(b), Some(d)) if d < b);
+            if too_young || died_before_birth { adjust -= DISAMBIGUATION_STEP; }
+            candidate.3 = (candidate.3 + adjust).clamp(0.0, 1.0);
+        }
+        candidates.sort_by(|a, b| b.3.total_cmp(&a.3));
+        Ok(())
+    }
+
+    /// "1802-1885" / "1947-" per id, from the birth (P569) and death (P570) claims.
+    pub async fn life_dates(&self, ids: &[&str]) -> anyhow::Result<std::collections::HashMap<String, String>> {
+        let mut dates = std::collections::HashMap::new();
+        if ids.is_empty() { return Ok(dates); }
+        let data = self.entities(&ids.join("|"), "claims", "en").await?;
+        for (id, entity) in data.entities {
+            // Keyed by the id asked for, even when it was merged into another item.
+            let id = entity.redirects.as_ref().map(|r| r.from.clone()).unwrap_or(id);
+            let Some(claims) = entity.claims.as_ref() else { continue; };
+            let (birth, death) = (claim_year(claims.get("P569")), claim_year(claims.get("P570")));
+            if birth.is_none() && death.is_none() { continue; }
+            let year = |y: Option<i32>| y.map(|y| y.to_string()).unwrap_or_default();
+            dates.insert(id, format!("{}-{}", year(birth), year(death)));
+        }
+        Ok(dates)
+    }
+
+    /// Label per id in the first available of `label_langs`. Ids not seen before are fetched `MAX_ENTITY_IDS` at a
+    /// time and remembered for the whole run, label or not; ids without a label are left out.
+    pub async fn labels(&self, ids: &[&str]) -> anyhow::Result<HashMap<String, String>> {
+        let missing: Vec<&str> = {
+            let known = self.labels.lock().unwrap();
+            let mut missing: Vec<&str> = ids.iter().copied().filter(|id| !known.contains_key(*id)).collect();
+            missing.sort_unstable();
+            missing.dedup();
+            missing
+        };
+        for chunk in missing.chunks(MAX_ENTITY_IDS) {
+            let data = self.entities(&chunk.join("|"), "labels", &self.label_langs().join("|")).await?;
+            let mut known = self.labels.lock().unwrap();
+            for id in chunk {
+                let label = data.entity(id).and_then(|e| e.labels.as_ref())
+                    .and_then(|labels| self.pick_label(labels))
+                    .map(str::to_string);
+                known.insert(id.to_string(), label);
+            }
+        }
+        let known = self.labels.lock().unwrap();
+        Ok(ids.iter().filter_map(|id| Some((id.to_string(), known.get(*id)?.clone()?))).collect())
+    }
+
+    /// Labels (see `labels`) of the items referenced by the `property` claims of `qid`, e.g. P27
+    /// (country of citizenship) or P1412 (languages spoken or written), in claim order; empty when
+    /// the entity has no such claim.
+    pub async fn claim_labels(&self, qid: &str, property: &str) -> anyhow::Result<Vec<String>> {
+        let data = self.entities(qid, "claims", "en").await?;
+        let ids: Vec<String> = data.entity(qid)
+            .and_then(|e| e.claims.as_ref())
+            .and_then(|claims| claims.get(property))
+            .map(|items| items.iter()
+                .filter_map(|c| c.mainsnak.datavalue.as_ref()?.value.get("id")?.as_str().map(str::to_string))
+                .collect())
+            .unwrap_or_default();
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        let labels = self.labels(&ids).await?;
+        Ok(ids.iter().filter_map(|id| labels.get(*id).cloned()).collect())
+    }
+
+    /// `wbgetentities` for `ids` (joined with `|`), restricted to `props` and `languages`.
+    pub async fn entities(&self, ids: &str, props: &str, languages: &str) -> anyhow::Result<WikidataEntityResponse> {
+        let key = format!("{}|{}|{}", ids, props, languages);
+        let body = self.http.get_text(WIKIDATA_API, "entities", &key, &[
+            ("action", "wbgetentities"), ("ids", ids), ("format", "json"),
+            ("languages", languages), ("props", props),
+        ]).await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+}
+
+/// `WikidataClient` for synchronous callers (the cleanup tool): every call runs to completion on a
+/// private current-thread runtime, with the client's cache, pacing, retries and identity.
+pub struct BlockingWikidata { runtime: tokio::runtime::Runtime, client: WikidataClient }
+
+impl BlockingWikidata {
+    pub fn new(client: WikidataClient) -> anyhow::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        Ok(Self { runtime, client })
+    }
+
+    /// See `WikidataClient::search`.
+    pub fn search(&self, query: &str) -> anyhow::Result<Option<(String, String, String, f64)>> {
+        self.runtime.block_on(self.client.search(query))
+    }
+
+    /// See `WikidataClient::claim_labels`.
+    pub fn claim_labels(&self, qid: &str, property: &str) -> anyhow::Result<Vec<String>> {
+        self.runtime.block_on(self.client.claim_labels(qid, property))
+    }
+}
+
+/// Year of the first time claim (`+1947-09-21T00:00:00Z` -> 1947; BCE years are negative).
+pub fn claim_year(claims: Option<&Vec<WikidataClaim>>) -> Option<i32> {
+    let time = claims?.iter().find_map(|c| c.mainsnak.datavalue.as_ref()?.value.get("time")?.as_str())?;
+    let (sign, rest) = match time.strip_prefix('-') { Some(rest) => (-1, rest), None => (1, time.trim_start_matches('+')) };
+    rest.split('-').next()?.parse::<i32>().ok().map(|y| sign * y)
+}
+
+fn current_year() -> i32 {
+    chrono::Local::now().year()
+}
+
+fn token_overlap_f1(a: &str, b: &str) -> f64 {
+    let a_tokens: Vec<&str> = a.split_whitespace().collect();
+    let b_tokens: Vec<&str> = b.split_whitespace().collect();
+    if a_tokens.is_empty() || b_tokens.is_empty() { return 0.0; }
+    let a_set: std::collections::HashSet<&str> = a_tokens.iter().copied().collect();
+    let b_set: std::collections::HashSet<&str> = b_tokens.iter().copied().collect();
+    let inter = a_set.intersection(&b_set).count() as f64;
+    let prec = if b_set.is_empty() { 0.0 } else { inter / b_set.len() as f64 };
+    let rec = if a_set.is_empty() { 0.0 } else { inter / a_set.len() as f64 };
+    if prec + rec == 0.0 { 0.0 } else { 2.0 * prec * rec / (prec + rec) }
+}
+
+pub(crate) fn normalize_for_score(s: &str) -> String {
+    let stripped = strip_accents(s);
+    stripped
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn invert_first_last(label: &str) -> Option<String> {
+    // Try to interpret label as "First Last [Middle...]" and invert to "Last, First"
+    let tokens: Vec<&str> = label.split_whitespace().collect();
+    if tokens.len() >= 2 {
+        let last = tokens.last().unwrap();
+        let first = tokens[..tokens.len() - 1].join(" ");
+        Some(format!("{}, {}", last, first))
+    } else { None }
+}
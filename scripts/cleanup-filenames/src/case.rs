@@ -0,0 +1,76 @@
+use clap::ValueEnum;
+use regex::Regex;
+use unidecode::unidecode;
+
+/// Casse des noms cibles (--case).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum CaseStyle {
+    /// Capitale sur la première lettre uniquement (comportement historique)
+    FirstUpper,
+    /// Capitale à chaque mot, sauf articles et particules ("le", "de", "et", "the", "of"...) hors premier mot
+    Title,
+    /// Première lettre en capitale, et les noms propres du nom d'origine ("Notre dame de Paris")
+    Sentence,
+    /// Casse des mots du nom d'origine ("DUNE messiah" reste tel quel)
+    Keep,
+}
+
+/// Mots laissés en minuscules par `title` (sans accents: comparés à la forme dé-accentuée).
+const SMALL_WORDS: [&str; 40] = [
+    "le", "la", "les", "l", "un", "une", "des", "de", "du", "d", "et", "ou", "ni", "a", "au", "aux", "en", "sur", "sous", "dans",
+    "par", "pour", "avec", "sans", "chez", "entre", "vers", "the", "an", "of", "and", "or", "in", "on", "at", "to", "for", "by", "from", "with",
+];
+
+/// Applique le style à `text` (clé normalisée, en minuscules); `original` (nom de fichier ou titre EPUB) donne la casse
+/// d'origine pour `sentence` et `keep`.
+pub fn apply(style: CaseStyle, text: &str, original: &str) -> String {
+    let words: Vec<&str> = text.split(' ').filter(|w| !w.is_empty()).collect();
+    let cased: Vec<String> = match style {
+        CaseStyle::FirstUpper => return capitalize(text),
+        CaseStyle::Title => words.iter().enumerate()
+            .map(|(i, word)| if i > 0 && SMALL_WORDS.contains(&unidecode(word).as_str()) { word.to_string() } else { capitalize(word) })
+            .collect(),
+        CaseStyle::Sentence | CaseStyle::Keep => {
+            let Some(source) = aligned_words(&words, original) else { return capitalize(text); };
+            // Nom d'origine tout en capitales ou capitalisé mot à mot: aucune information sur les noms propres
+            let uninformative = source.iter().filter(|w| w.chars().count() > 1).all(|w| starts_upper(w));
+            words.iter().zip(&source).enumerate()
+                .map(|(i, (word, src))| match style {
+                    CaseStyle::Keep if src.chars().count() > 1 && src.chars().all(|c| !c.is_lowercase()) => word.to_uppercase(),
+                    CaseStyle::Keep if starts_upper(src) => capitalize(word),
+                    CaseStyle::Keep => word.to_string(),
+                    _ if i == 0 || (!uninformative && starts_upper(src)) => capitalize(word),
+                    _ => word.to_string(),
+                })
+                .collect()
+        }
+    };
+    cased.join(" ")
+}
+
+/// Mots d'origine correspondant, dans l'ordre, à ceux de la clé (recherche d'une suite contiguë:
+/// l'auteur ou les motifs retirés de la clé peuvent encadrer le titre).
+fn aligned_words(words: &[&str], original: &str) -> Option<Vec<String>> {
+    let sep_re = Regex::new(r"[\s_\-]+").unwrap();
+    let punct_re = Regex::new(r"[^\p{L}\p{N} ]+").unwrap();
+    let spaced = sep_re.replace_all(original, " ");
+    let cleaned = punct_re.replace_all(&spaced, "");
+    let source: Vec<&str> = cleaned.split_whitespace().collect();
+    // Ponctuation ignorée: "(EN)" dans la clé (--edition-markers) répond à "EN" dans le nom d'origine
+    let fold = |w: &str| unidecode(&w.to_lowercase()).chars().filter(|c| c.is_alphanumeric()).collect::<String>();
+    let keys: Vec<String> = words.iter().map(|w| fold(w)).collect();
+    if keys.is_empty() || source.len() < keys.len() { return None; }
+    (0..=source.len() - keys.len())
+        .find(|&start| source[start..start + keys.len()].iter().zip(&keys).all(|(src, key)| fold(src) == *key))
+        .map(|start| source[start..start + keys.len()].iter().map(|w| w.to_string()).collect())
+}
+
+fn starts_upper(word: &str) -> bool { word.chars().next().is_some_and(char::is_uppercase) }
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        None => String::new(),
+        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+    }
+}
@@ -0,0 +1,51 @@
+use regex::Regex;
+
+/// Codes de langue reconnus entre parenthèses ou crochets, et leur forme canonique.
+const LANGUAGES: [(&str, &str); 14] = [
+    ("en", "EN"), ("eng", "EN"), ("fr", "FR"), ("fra", "FR"), ("fre", "FR"), ("de", "DE"), ("deu", "DE"),
+    ("ger", "DE"), ("es", "ES"), ("spa", "ES"), ("it", "IT"), ("ita", "IT"), ("vo", "VO"), ("vf", "VF"),
+];
+
+/// Mentions d'édition (comparées sans accents) et leur forme canonique.
+const EDITIONS: [(&str, &str); 12] = [
+    ("integrale", "intégrale"), ("illustre", "illustré"), ("illustree", "illustré"), ("illustrated", "illustrated"),
+    ("unabridged", "unabridged"), ("abridged", "abridged"), ("annote", "annoté"), ("annotee", "annoté"),
+    ("annotated", "annotated"), ("bilingue", "bilingue"), ("bilingual", "bilingual"), ("collector", "collector"),
+];
+
+/// Retire d'un nom ses marqueurs d'édition et de langue ("(EN)", "[fr]", "Intégrale", "illustrée", "abridged"...).
+/// Rend le reste et les marqueurs sous forme canonique (langues puis mentions, sans doublon).
+pub fn extract(stem: &str) -> (String, Vec<String>) {
+    let language_re = Regex::new(r"(?i)[(\[]\s*([a-z]{2,3})\s*[)\]]").unwrap();
+    let word_re = Regex::new(r"\p{L}+").unwrap();
+    let mut languages: Vec<&str> = Vec::new();
+    let mut editions: Vec<&str> = Vec::new();
+
+    let rest = language_re.replace_all(stem, |caps: &regex::Captures| {
+        match LANGUAGES.iter().find(|(code, _)| caps[1].eq_ignore_ascii_case(code)) {
+            Some((_, canonical)) => {
+                if !languages.contains(canonical) { languages.push(canonical); }
+                " ".to_string()
+            }
+            None => caps[0].to_string(),
+        }
+    }).into_owned();
+
+    // Mot par mot (un mot collé à un chiffre n'est pas une mention): seul le mot reconnu disparaît
+    let mut rest_out = String::with_capacity(rest.len());
+    let mut last = 0;
+    for word in word_re.find_iter(&rest) {
+        let glued = rest[..word.start()].chars().next_back().is_some_and(|c| c.is_numeric())
+            || rest[word.end()..].chars().next().is_some_and(|c| c.is_numeric());
+        let folded = unidecode::unidecode(&word.as_str().to_lowercase());
+        let Some((_, canonical)) = EDITIONS.iter().find(|(name, _)| *name == folded).filter(|_| !glued) else { continue; };
+        if !editions.contains(canonical) { editions.push(canonical); }
+        rest_out.push_str(&rest[last..word.start()]);
+        rest_out.push(' ');
+        last = word.end();
+    }
+    rest_out.push_str(&rest[last..]);
+
+    let markers = languages.into_iter().chain(editions).map(String::from).collect();
+    (rest_out, markers)
+}
@@ -0,0 +1,80 @@
+use regex::Regex;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Taille maximale lue pour container.xml et l'OPF (protection contre les archives piégées).
+const MAX_ENTRY_BYTES: u64 = 1 << 20;
+
+/// Titre `dc:title` de l'OPF d'un EPUB (chemin donné par META-INF/container.xml); None si illisible ou vide.
+pub fn epub_title(path: &Path) -> Option<String> {
+    let opf = read_opf(path)?;
+    let title_re = Regex::new(r"(?s)<dc:title\b[^>]*>(.*?)</dc:title>").unwrap();
+    let raw = title_re.captures(&opf)?.get(1)?.as_str();
+    let raw = raw.trim().strip_prefix("<![CDATA[").and_then(|t| t.strip_suffix("]]>")).unwrap_or(raw);
+    let title = unescape(raw).split_whitespace().collect::<Vec<_>>().join(" ");
+    if title.is_empty() { None } else { Some(title) }
+}
+
+/// ISBN (10 ou 13 caractères, sans tirets) d'un `dc:identifier` de l'OPF: `opf:scheme="ISBN"`, préfixe `urn:isbn:`
+/// ou `isbn:`, ou 13 chiffres commençant par 978/979; None si aucun.
+pub fn epub_isbn(path: &Path) -> Option<String> {
+    let opf = read_opf(path)?;
+    let id_re = Regex::new(r"(?s)<dc:identifier\b([^>]*)>(.*?)</dc:identifier>").unwrap();
+    let isbn = id_re.captures_iter(&opf).find_map(|caps| {
+        let scheme = caps[1].to_ascii_lowercase().contains("isbn");
+        let value = unescape(caps[2].trim()).to_ascii_lowercase();
+        let (prefixed, value) = match value.strip_prefix("urn:isbn:").or_else(|| value.strip_prefix("isbn:")) {
+            Some(rest) => (true, rest),
+            None => (false, value.as_str()),
+        };
+        let isbn: String = value.chars().filter(|c| !matches!(c, '-' | ' ')).collect::<String>().to_ascii_uppercase();
+        let shaped = matches!(isbn.len(), 10 | 13)
+            && isbn.chars().enumerate().all(|(i, c)| c.is_ascii_digit() || (c == 'X' && i == isbn.len() - 1));
+        let bare = isbn.len() == 13 && (isbn.starts_with("978") || isbn.starts_with("979"));
+        (shaped && (scheme || prefixed || bare)).then_some(isbn)
+    });
+    isbn
+}
+
+/// Contenu de l'OPF (chemin donné par META-INF/container.xml).
+fn read_opf(path: &Path) -> Option<String> {
+    let mut archive = zip::ZipArchive::new(File::open(path).ok()?).ok()?;
+    let container = read_entry(&mut archive, "META-INF/container.xml")?;
+    let rootfile_re = Regex::new(r#"<rootfile\b[^>]*\bfull-path\s*=\s*["']([^"']+)["']"#).unwrap();
+    let opf_path = rootfile_re.captures(&container)?.get(1)?.as_str().to_string();
+    read_entry(&mut archive, &opf_path)
+}
+
+fn read_entry(archive: &mut zip::ZipArchive<File>, name: &str) -> Option<String> {
+    let entry = archive.by_name(name).ok()?;
+    let mut text = String::new();
+    entry.take(MAX_ENTRY_BYTES).read_to_string(&mut text).ok()?;
+    Some(text)
+}
+
+/// Entités XML prédéfinies et numériques (`&amp;`, `&#233;`, `&#xE9;`).
+fn unescape(s: &str) -> String {
+    let entity_re = Regex::new(r"&(#x[0-9a-fA-F]+|#[0-9]+|amp|lt|gt|quot|apos);").unwrap();
+    entity_re
+        .replace_all(s, |caps: &regex::Captures| {
+            let entity = &caps[1];
+            let code = if let Some(hex) = entity.strip_prefix("#x") {
+                u32::from_str_radix(hex, 16).ok()
+            } else if let Some(dec) = entity.strip_prefix('#') {
+                dec.parse().ok()
+            } else {
+                None
+            };
+            match (entity, code) {
+                ("amp", _) => "&".to_string(),
+                ("lt", _) => "<".to_string(),
+                ("gt", _) => ">".to_string(),
+                ("quot", _) => "\"".to_string(),
+                ("apos", _) => "'".to_string(),
+                (_, Some(code)) => char::from_u32(code).map(String::from).unwrap_or_default(),
+                _ => String::new(),
+            }
+        })
+        .into_owned()
+}
@@ -0,0 +1,40 @@
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::fs;
+use std::path::Path;
+use unicode_normalization::UnicodeNormalization;
+
+/// Noms de dossiers ou de fichiers (ou globs) laissés entièrement de côté.
+/// Comparaison insensible à la casse (accents compris: "À trier" = "à trier"), sur le nom seul (pas le chemin).
+pub struct ExcludeList {
+    set: GlobSet,
+    len: usize,
+}
+
+impl ExcludeList {
+    /// Motifs de --exclude, puis ceux du fichier --exclude-file (un par ligne, lignes vides et `#` ignorés).
+    pub fn build(patterns: &[String], file: Option<&Path>) -> Result<Self> {
+        let mut all: Vec<String> = patterns.to_vec();
+        if let Some(path) = file {
+            let content = fs::read_to_string(path).with_context(|| format!("Lecture du fichier d'exclusion {:?}", path))?;
+            all.extend(content.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')).map(String::from));
+        }
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &all {
+            let glob = GlobBuilder::new(&fold(pattern)).case_insensitive(true).literal_separator(true).build()
+                .with_context(|| format!("Motif d'exclusion invalide {:?}", pattern))?;
+            builder.add(glob);
+        }
+        Ok(Self { set: builder.build()?, len: all.len() })
+    }
+
+    pub fn pattern_count(&self) -> usize { self.len }
+
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        if self.len == 0 { return false; }
+        path.file_name().is_some_and(|name| self.set.is_match(fold(name.to_string_lossy().trim())))
+    }
+}
+
+/// globset ne replie la casse que pour l'ASCII: motifs et noms passent en NFC minuscule (noms NFD sous macOS).
+fn fold(s: &str) -> String { s.nfc().collect::<String>().to_lowercase() }
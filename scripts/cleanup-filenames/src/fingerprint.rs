@@ -0,0 +1,83 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Fichier d'état de --incremental, par défaut à la racine traitée.
+pub const STATE_NAME: &str = ".cleanup_filenames_state.json";
+
+/// Empreintes des dossiers d'auteurs laissés propres par le dernier lancement réel, pour un jeu d'options donné.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    options: String,
+    folders: BTreeMap<String, String>,
+}
+
+impl State {
+    /// État enregistré; vide s'il manque, s'il est illisible ou s'il a été produit avec d'autres options.
+    pub fn load(path: &Path, options: &str) -> Self {
+        let state: Option<State> = fs::read_to_string(path).ok().and_then(|body| serde_json::from_str(&body).ok());
+        match state {
+            Some(state) if state.options == options => state,
+            _ => State { options: options.to_string(), folders: BTreeMap::new() },
+        }
+    }
+
+    pub fn is_unchanged(&self, folder: &str, fingerprint: &str) -> bool {
+        self.folders.get(folder).is_some_and(|known| known == fingerprint)
+    }
+
+    pub fn set(&mut self, folder: &str, fingerprint: String) {
+        self.folders.insert(folder.to_string(), fingerprint);
+    }
+
+    /// Oublie les dossiers absents de `present` (supprimés, renommés ou exclus).
+    pub fn retain(&mut self, present: &[String]) {
+        self.folders.retain(|folder, _| present.contains(folder));
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)? + "\n").with_context(|| format!("Écriture de l'état {:?}", path))
+    }
+}
+
+/// SHA-256 (hexadécimal) d'un texte: options du lancement.
+pub fn digest(text: &str) -> String {
+    hex(&Sha256::digest(text.as_bytes()))
+}
+
+/// Empreinte d'un dossier: chemins relatifs et tailles de tous ses fichiers, triés (le contenu n'est pas lu).
+/// Les liens symboliques vers des dossiers ne sont pas suivis.
+pub fn folder(dir: &Path) -> String {
+    let mut entries = Vec::new();
+    collect(dir, dir, &mut entries);
+    entries.sort();
+    let mut hasher = Sha256::new();
+    for (path, size) in &entries {
+        hasher.update(format!("{}\t{}\n", path, size).as_bytes());
+    }
+    hex(&hasher.finalize())
+}
+
+fn collect(root: &Path, dir: &Path, out: &mut Vec<(String, u64)>) {
+    let Ok(read) = fs::read_dir(dir) else { return; };
+    for entry in read.flatten() {
+        let path = entry.path();
+        let Ok(kind) = entry.file_type() else { continue; };
+        if kind.is_dir() {
+            collect(root, &path, out);
+        } else {
+            let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            out.push((path.strip_prefix(root).unwrap_or(&path).to_string_lossy().into_owned(), size));
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
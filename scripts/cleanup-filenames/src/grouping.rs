@@ -0,0 +1,45 @@
+use clap::ValueEnum;
+use unicode_normalization::UnicodeNormalization;
+use unidecode::unidecode;
+
+/// Sévérité du regroupement des doublons (--group-by).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    /// Même nom exactement (hors extension et motifs --junk): "Dune" et "dune" restent deux titres
+    Exact,
+    /// Casse et accents ignorés, ponctuation conservée: "Les Misérables" = "les miserables", mais "1.2" ≠ "12"
+    DeAccented,
+    /// Casse, accents et ponctuation ignorés (comportement historique)
+    DePunctuated,
+    /// Comme de-punctuated, sans tenir compte de l'ordre des mots: "Dune Messiah" = "Messiah, Dune"
+    TokenSet,
+}
+
+/// Sépare la clé de nommage du discriminant des niveaux stricts dans l'identifiant de groupe.
+const SEPARATOR: char = '\u{1f}';
+
+impl GroupBy {
+    /// Identifiant de groupe d'un fichier. `key` est sa clé de nommage (title_key) et `stem` son nom sans les motifs --junk.
+    /// Les niveaux stricts ne font que séparer des fichiers de même clé; token-set réunit des clés aux mêmes mots.
+    pub fn id(self, key: &str, stem: &str) -> String {
+        match self {
+            GroupBy::Exact => format!("{}{}{}", key, SEPARATOR, stem.trim().nfc().collect::<String>()),
+            GroupBy::DeAccented => {
+                let folded = unidecode(&stem.nfc().collect::<String>().to_lowercase());
+                format!("{}{}{}", key, SEPARATOR, folded.split_whitespace().collect::<Vec<_>>().join(" "))
+            }
+            GroupBy::DePunctuated => key.to_string(),
+            GroupBy::TokenSet => {
+                let mut words: Vec<&str> = key.split(' ').filter(|w| !w.is_empty()).collect();
+                words.sort_unstable();
+                words.dedup();
+                words.join(" ")
+            }
+        }
+    }
+}
+
+/// Clé affichée (rapports, journal, mode interactif) d'un identifiant de groupe.
+pub fn label(id: &str) -> &str {
+    id.split(SEPARATOR).next().unwrap_or(id)
+}
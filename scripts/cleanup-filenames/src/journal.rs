@@ -0,0 +1,179 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sortbook_common::ledger::{Ledger, Op};
+use sortbook_common::say;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_JOURNAL_DIR: &str = "logs";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalOp {
+    Rename,
+    DeleteFile,
+    /// Corbeille du système: `dst` absent, le fichier est retrouvé par son chemin d'origine.
+    TrashFile,
+    QuarantineFile,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub op: JournalOp,
+    pub src: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dst: Option<PathBuf>,
+    pub ts: String,
+}
+
+/// Journal JSONL (ajout seul) de chaque renommage et suppression d'un lancement.
+/// Le fichier n'est créé qu'au premier changement enregistré. Avec --ledger, chaque changement va aussi au registre SQLite commun.
+pub struct Journal {
+    path: Option<PathBuf>,
+    file: Option<File>,
+    ledger: Ledger,
+}
+
+impl Journal {
+    pub fn disabled() -> Self { Self { path: None, file: None, ledger: Ledger::disabled() } }
+
+    pub fn at(path: PathBuf) -> Self { Self { path: Some(path), file: None, ledger: Ledger::disabled() } }
+
+    pub fn with_ledger(mut self, ledger: Ledger) -> Self { self.ledger = ledger; self }
+
+    /// Clôt le lancement dans le registre (sans effet sans --ledger); `stopped` après une demande d'arrêt.
+    pub fn finish(&mut self, stopped: bool) -> Result<()> {
+        self.ledger.finish(if stopped { "stopped" } else { "ok" })?;
+        Ok(())
+    }
+
+    /// Journal horodaté sous `logs/`, pour pouvoir annuler chaque lancement séparément.
+    pub fn default_path() -> PathBuf {
+        Path::new(DEFAULT_JOURNAL_DIR).join(format!("cleanup_filenames_journal_{}.jsonl", chrono::Local::now().format("%Y%m%d_%H%M%S")))
+    }
+
+    /// Chemin du journal si au moins un changement y a été écrit.
+    pub fn written_path(&self) -> Option<&Path> { self.file.as_ref().and(self.path.as_deref()) }
+
+    pub fn record(&mut self, op: JournalOp, src: &Path, dst: Option<&Path>) -> Result<()> {
+        let (kind, detail) = match op {
+            JournalOp::Rename => (Op::Move, "rename"),
+            JournalOp::DeleteFile => (Op::Delete, "delete_file"),
+            JournalOp::TrashFile => (Op::Trash, "trash_file"),
+            JournalOp::QuarantineFile => (Op::Move, "quarantine_file"),
+        };
+        self.ledger.record(kind, src, dst, detail)?;
+        let Some(path) = &self.path else { return Ok(()); };
+        if self.file.is_none() {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) { fs::create_dir_all(parent)?; }
+            let file = OpenOptions::new().create(true).append(true).open(path)
+                .with_context(|| format!("Ouverture du journal {:?}", path))?;
+            self.file = Some(file);
+        }
+        let entry = JournalEntry { op, src: absolute(src), dst: dst.map(absolute), ts: chrono::Utc::now().to_rfc3339() };
+        let file = self.file.as_mut().expect("journal ouvert ci-dessus");
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Rejoue un journal à l'envers: noms d'origine, fichiers sortis de quarantaine et de la corbeille.
+pub fn revert(journal_path: &Path, dry_run: bool) -> Result<()> {
+    let file = File::open(journal_path).with_context(|| format!("Ouverture du journal {:?}", journal_path))?;
+    let mut entries = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() { continue; }
+        let entry: JournalEntry = serde_json::from_str(&line).with_context(|| format!("Ligne {} du journal invalide", index + 1))?;
+        entries.push(entry);
+    }
+    say!("Annulation de {} opération(s) {}depuis {:?}", entries.len(), if dry_run { "(dry-run) " } else { "" }, journal_path);
+
+    let mut restored = 0usize;
+    let mut skipped = 0usize;
+    for entry in entries.iter().rev() {
+        if entry.src.exists() {
+            say!("  ignoré {} (existe déjà)", entry.src.display());
+            skipped += 1;
+            continue;
+        }
+        match (entry.op, &entry.dst) {
+            (JournalOp::Rename | JournalOp::QuarantineFile, Some(dst)) => {
+                if !dst.exists() {
+                    say!("  ignoré {} (introuvable)", dst.display());
+                    skipped += 1;
+                    continue;
+                }
+                if dry_run {
+                    say!("[DRY-RUN] restaure {} -> {}", dst.display(), entry.src.display());
+                } else {
+                    if let Some(parent) = entry.src.parent() { fs::create_dir_all(parent)?; }
+                    restore_path(dst, &entry.src)?;
+                    // .original_names.json suit le renommage inverse (seulement là où --original-names l'a créé)
+                    let tracked = dst.parent().is_some_and(|dir| dir.join(crate::sidecar::SIDECAR_NAME).exists());
+                    if entry.op == JournalOp::Rename && tracked { crate::sidecar::record(dst, &entry.src)?; }
+                }
+                restored += 1;
+            }
+            (JournalOp::TrashFile, _) => {
+                if dry_run {
+                    say!("[DRY-RUN] sort de la corbeille {}", entry.src.display());
+                    restored += 1;
+                } else if platform_trash::restore(&entry.src)? {
+                    restored += 1;
+                } else {
+                    say!("  absent de la corbeille: {}", entry.src.display());
+                    skipped += 1;
+                }
+            }
+            (JournalOp::DeleteFile, _) => {
+                say!("  suppression définitive, non restaurable: {}", entry.src.display());
+                skipped += 1;
+            }
+            _ => skipped += 1,
+        }
+    }
+    say!("Annulation terminée: {} restauré(s), {} ignoré(s).", restored, skipped);
+    Ok(())
+}
+
+fn restore_path(from: &Path, to: &Path) -> Result<()> {
+    if fs::rename(from, to).is_ok() { return Ok(()); }
+    fs::copy(from, to).with_context(|| format!("Restauration {:?} -> {:?}", from, to))?;
+    fs::remove_file(from).ok();
+    Ok(())
+}
+
+/// Restauration depuis la corbeille: possible sous Windows et Linux (freedesktop), pas sous macOS.
+#[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))))]
+mod platform_trash {
+    use anyhow::Result;
+    use std::path::Path;
+
+    /// Restaure l'élément le plus récemment mis à la corbeille depuis `path`; false s'il n'y est pas.
+    pub fn restore(path: &Path) -> Result<bool> {
+        // La corbeille enregistre le chemin canonique du dossier parent.
+        let parent = path.parent().map(|p| p.canonicalize().unwrap_or_else(|_| p.to_path_buf()));
+        let Some(name) = path.file_name() else { return Ok(false); };
+        let item = trash::os_limited::list()?.into_iter()
+            .filter(|item| item.name == name && Some(&item.original_parent) == parent.as_ref())
+            .max_by_key(|item| item.time_deleted);
+        let Some(item) = item else { return Ok(false); };
+        trash::os_limited::restore_all([item])?;
+        Ok(true)
+    }
+}
+
+#[cfg(not(any(target_os = "windows", all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))))]
+mod platform_trash {
+    use anyhow::Result;
+    use std::path::Path;
+
+    pub fn restore(_path: &Path) -> Result<bool> { Ok(false) }
+}
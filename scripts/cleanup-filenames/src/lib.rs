@@ -0,0 +1,1386 @@
+use anyhow::{Context, Result};
+use case::CaseStyle;
+use grouping::GroupBy;
+use clap::{Parser, ValueEnum};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{info, warn};
+use regex::Regex;
+use rusqlite::Connection;
+use sortbook_common::output::{self, Event, OutputFormat};
+use sortbook_common::sanitize::sanitize_component;
+use sortbook_common::say;
+use sortbook_common::scan::ScanIndex;
+use sortbook_common::snapshot::Snapshot;
+use std::cmp::Ordering;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
+use std::sync::Mutex;
+use unicode_normalization::UnicodeNormalization;
+use unidecode::unidecode;
+use rayon::prelude::*;
+
+mod case;
+mod edition;
+pub mod epub;
+mod exclude;
+mod fingerprint;
+mod grouping;
+mod journal;
+mod report;
+mod sidecar;
+mod stats;
+
+use exclude::ExcludeList;
+use journal::{Journal, JournalOp};
+use sortbook_common::ledger::Ledger;
+use report::PlanRow;
+use stats::Stats;
+
+#[derive(Parser, Debug, Clone)]
+#[command(name = "cleanup-filenames", about = "Normalise les noms de fichiers de livres par dossier d'auteur")] 
+struct Cli {
+    /// Racine où se trouvent les dossiers auteurs (par défaut output/sorted_book)
+    #[arg(long, default_value = "output/sorted_book")] 
+    root: PathBuf,
+
+    /// Extensions à considérer (séparées par des virgules). Vide = toutes.
+    #[arg(long, default_value = "")] 
+    exts: String,
+
+    /// Ne fait aucun changement, affiche seulement (true/false)
+    #[arg(long, default_value = "true", action = clap::ArgAction::Set)]
+    dry_run: bool,
+
+    /// Affichage détaillé
+    #[arg(long, default_value_t = false)]
+    verbose: bool,
+
+    /// Envoie les doublons écartés à la corbeille du système au lieu de les supprimer
+    #[arg(long, default_value_t = false, conflicts_with = "quarantine")]
+    trash: bool,
+
+    /// Déplace les doublons écartés dans ce dossier (sous-dossier horodaté par lancement) au lieu de les supprimer
+    #[arg(long)]
+    quarantine: Option<PathBuf>,
+
+    /// Doublons d'un même titre: `larger` garde la variante accentuée, sinon la plus lourde, toutes extensions
+    /// confondues; `format` garde d'abord le format le mieux classé dans --format-priority
+    #[arg(long, value_enum, default_value = "larger")]
+    keep: KeepPolicy,
+
+    /// Extensions de la meilleure à la moins bonne pour `--keep format` (les autres viennent après)
+    #[arg(long, value_delimiter = ',', default_value = "epub,azw3,mobi,pdf")]
+    format_priority: Vec<String>,
+
+    /// Taille minimale d'un fichier gardé (octets, ou suffixe K/M/G): en dessous, aucun doublon du groupe n'est écarté
+    #[arg(long, default_value = "0", value_parser = parse_size)]
+    min_keep_size: u64,
+
+    /// Ne pas écarter un doublon plus de N fois plus petit ou plus grand que le fichier gardé (autre édition probable);
+    /// en --interactive, le choisir explicitement reste possible
+    #[arg(long, value_name = "N", value_parser = parse_size_ratio)]
+    size_ratio: Option<f64>,
+
+    /// Nombre de formats conservés par titre avec `--keep format` (le meilleur fichier de chacun)
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    keep_formats: u32,
+
+    /// Modèle des noms de fichiers, ex. "{title} - {author}" (jetons: {title}, {author}, {first}, {last}, {author_sort});
+    /// par défaut le titre seul
+    #[arg(long)]
+    template: Option<String>,
+
+    /// Casse des noms: `first-upper` (première lettre), `title` (chaque mot, sauf articles et particules),
+    /// `sentence` (première lettre et noms propres du nom d'origine), `keep` (casse d'origine des mots)
+    #[arg(long, value_enum, default_value = "first-upper")]
+    case: CaseStyle,
+
+    /// Pour les EPUB, nomme le fichier d'après le titre dc:title de l'OPF plutôt que d'après le nom actuel
+    #[arg(long, default_value_t = false)]
+    epub_title: bool,
+
+    /// Normalise les marqueurs de tome ("T1", "tome 01", "vol. 2", "#3") selon ce modèle, ex. "T{n:02}" -> T01
+    #[arg(long)]
+    series_format: Option<String>,
+
+    /// Avec --series-format, range les tomes trouvés à la racine d'un auteur dans un sous-dossier par série
+    #[arg(long, default_value_t = false, requires = "series_format")]
+    series_folders: bool,
+
+    /// Ne regroupe jamais des fichiers qui diffèrent par un marqueur d'édition ou de langue ("(EN)", "intégrale",
+    /// "illustré", "abridged"...); le marqueur est réécrit sous une forme unique, ex. "Dune (EN) (intégrale)"
+    #[arg(long, default_value_t = false)]
+    edition_markers: bool,
+
+    /// Sévérité du regroupement: `exact` (même nom), `de-accented` (casse et accents ignorés),
+    /// `de-punctuated` (casse, accents et ponctuation ignorés), `token-set` (mots dans n'importe quel ordre)
+    #[arg(long, value_enum, default_value = "de-punctuated")]
+    group_by: GroupBy,
+
+    /// Fichier de motifs parasites retirés des noms avant le groupement ("[FR]", "(ebook)", "re:v\d+\.\d+"...), un par ligne
+    #[arg(long)]
+    junk: Option<PathBuf>,
+
+    /// Pour un fichier gardé sans accents, reprend l'orthographe accentuée du titre trouvé dans la base OpenLibrary (--db)
+    #[arg(long, default_value_t = false)]
+    restore_accents: bool,
+
+    /// Base SQLite OpenLibrary (table `works`) utilisée par --restore-accents
+    #[arg(long, default_value = DEFAULT_DB)]
+    db: PathBuf,
+
+    /// Dossier ou fichier à laisser de côté, par nom ou glob insensible à la casse (ex. "Anthologies", "À trier", "*.cbz"); répétable
+    #[arg(long)]
+    exclude: Vec<String>,
+
+    /// Fichier de motifs d'exclusion, un nom ou glob par ligne (`#` pour les commentaires)
+    #[arg(long)]
+    exclude_file: Option<PathBuf>,
+
+    /// Pour chaque groupe de doublons, montre le fichier gardé et les changements prévus et demande confirmation
+    /// ou un autre choix (auteurs traités l'un après l'autre)
+    #[arg(long, default_value_t = false)]
+    interactive: bool,
+
+    /// Profondeur de sous-dossiers traitée sous chaque auteur (0 = racine de l'auteur seulement)
+    #[arg(long, default_value_t = 1)]
+    max_depth: usize,
+
+    /// Écrit les noms cibles en Unicode NFC (forme composée) et signale les doublons qui ne diffèrent que par
+    /// la normalisation (noms NFD copiés depuis macOS)
+    #[arg(long, default_value_t = false)]
+    nfc: bool,
+
+    /// Remonte à la racine de l'auteur les livres d'un sous-dossier qui ne contient qu'un titre (dossier par livre,
+    /// copie manuelle); couverture, metadata.opf et autres annexes sont écartés et le dossier vidé supprimé
+    #[arg(long, default_value_t = false)]
+    flatten: bool,
+
+    /// Écrit en CSV les changements prévus (ancien nom, nouveau nom, action, clé de groupe, taille)
+    #[arg(long)]
+    report_csv: Option<PathBuf>,
+
+    /// Écrit les changements prévus en texte façon diff unifié, une section par auteur
+    #[arg(long)]
+    report_diff: Option<PathBuf>,
+
+    /// Tient dans chaque dossier renommé un fichier .original_names.json (nouveau nom -> nom d'origine)
+    #[arg(long, default_value_t = false)]
+    original_names: bool,
+
+    /// Journal texte horodaté, une ligne clé=valeur par renommage, suppression ou doublon laissé (old, new, size, reason)
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+
+    /// Résumé JSON du lancement (par défaut summary.json à côté de --log-file)
+    #[arg(long)]
+    summary: Option<PathBuf>,
+
+    /// Sortie: text, ou json pour un événement JSON par fichier sur stdout (les messages passent alors sur stderr)
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
+    /// Ignore les dossiers d'auteurs inchangés (mêmes fichiers, mêmes tailles) depuis le dernier lancement réel
+    /// avec les mêmes options
+    #[arg(long, default_value_t = false)]
+    incremental: bool,
+
+    /// Fichier d'état de --incremental (par défaut .cleanup_filenames_state.json à la racine)
+    #[arg(long, requires = "incremental")]
+    state: Option<PathBuf>,
+
+    /// Index de fichiers commun (ex. data/scan_index.sqlite3, taille, date, SHA-256): ignore les dossiers d'auteurs
+    /// dont aucun fichier n'est nouveau, modifié ou disparu depuis le dernier lancement réel avec les mêmes options
+    #[arg(long)]
+    scan_index: Option<PathBuf>,
+
+    /// Avant un lancement réel, note l'arborescence complète de la racine dans un nouveau dossier de ce répertoire
+    /// (ex. data/snapshots), restaurable avec `sortbook restore <instantané>`
+    #[arg(long)]
+    snapshot: Option<PathBuf>,
+
+    /// Avec --snapshot, garde aussi un lien physique (une copie entre systèmes de fichiers) de chaque fichier
+    /// supprimé ou mis à la corbeille, que `sortbook restore` pourra remettre en place
+    #[arg(long, requires = "snapshot")]
+    snapshot_keep_deleted: bool,
+
+    /// Journal des renommages/suppressions (par défaut logs/cleanup_filenames_journal_<horodatage>.jsonl)
+    #[arg(long)]
+    journal: Option<PathBuf>,
+
+    /// Registre SQLite commun (ex. data/sortbook_state.sqlite3): chaque changement d'un lancement réel y est aussi noté (voir `sortbook ledger`)
+    #[arg(long)]
+    ledger: Option<PathBuf>,
+
+    /// Annule un lancement à partir de son journal (respecte --dry-run)
+    #[arg(long, value_name = "JOURNAL")]
+    revert: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum KeepPolicy {
+    Larger,
+    Format,
+}
+
+/// Réglages d'un lancement, partagés par les dossiers traités en parallèle.
+struct Settings {
+    allowed_exts: Option<Vec<String>>,
+    discard: Discard,
+    keep: KeepPolicy,
+    /// Extensions en minuscules, de la meilleure à la moins bonne (`--keep format`).
+    format_priority: Vec<String>,
+    keep_formats: usize,
+    min_keep_size: u64,
+    size_ratio: Option<f64>,
+    template: Option<String>,
+    case: CaseStyle,
+    epub_title: bool,
+    series_format: Option<String>,
+    series_folders: bool,
+    edition_markers: bool,
+    group_by: GroupBy,
+    /// Motifs de --junk, insensibles à la casse.
+    junk: Vec<Regex>,
+    exclude: ExcludeList,
+    /// Base ouverte pour --restore-accents (une connexion partagée entre les threads).
+    accents_db: Option<Mutex<Connection>>,
+    max_depth: usize,
+    nfc: bool,
+    flatten: bool,
+    original_names: bool,
+    interactive: bool,
+    /// Mis à vrai quand l'utilisateur arrête le mode --interactive: les groupes restants ne sont pas touchés.
+    stop: AtomicBool,
+    /// Noms cibles déjà pris, résolus par un suffixe " (2)", " (3)"... (résumé de fin).
+    collisions: AtomicUsize,
+    dry_run: bool,
+    verbose: bool,
+    /// Instantané pris avant le lancement (--snapshot), qui garde les fichiers supprimés.
+    snapshot: Option<Snapshot>,
+}
+
+/// Sort des doublons écartés.
+#[derive(Debug, Clone)]
+enum Discard {
+    Delete,
+    Trash,
+    /// Dossier de ce lancement (`<quarantaine>/<horodatage>`); les chemins relatifs à la racine y sont conservés.
+    Quarantine { root: PathBuf, dir: PathBuf },
+}
+
+impl Discard {
+    /// Action inscrite dans les rapports --report-csv / --report-diff.
+    fn action(&self) -> &'static str {
+        match self {
+            Discard::Delete => "delete",
+            Discard::Trash => "trash",
+            Discard::Quarantine { .. } => "quarantine",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct FileEntry {
+    path: PathBuf,
+    size: u64,
+    /// Clé de nommage (title_key); elle peut différer d'un fichier à l'autre d'un groupe avec --group-by token-set.
+    key: String,
+}
+
+/// Lance l'outil sur une ligne de commande (`args[0]` = nom du programme), pour le binaire
+/// `cleanup-filenames` comme pour `sortbook filenames`.
+pub fn run_from_args<I, T>(args: I) -> Result<()>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString> + Clone,
+{
+    let cli: Cli = sortbook_common::config::parse("filenames", args.into_iter().map(Into::into).collect())?;
+    output::init(cli.output);
+    if cli.interactive && output::json() {
+        anyhow::bail!("--interactive pose ses questions sur la sortie standard: incompatible avec --output json");
+    }
+    if let Some(path) = &cli.revert {
+        return journal::revert(path, cli.dry_run);
+    }
+    if let Some(template) = &cli.template {
+        check_template(template)?;
+    }
+    if let Some(format) = &cli.series_format {
+        if !Regex::new(SERIES_NUMBER_TOKEN).unwrap().is_match(format) {
+            anyhow::bail!("--series-format doit contenir {{n}} ou {{n:0<largeur>}}, ex. \"T{{n:02}}\"");
+        }
+    }
+    ensure_dir(&cli.root)?;
+
+    let discard = match &cli.quarantine {
+        Some(dir) => Discard::Quarantine {
+            root: cli.root.clone(),
+            dir: dir.join(chrono::Local::now().format("%Y%m%d_%H%M%S").to_string()),
+        },
+        None if cli.trash => Discard::Trash,
+        None => Discard::Delete,
+    };
+    let settings = Settings {
+        allowed_exts: parse_exts(&cli.exts),
+        discard,
+        keep: cli.keep,
+        format_priority: cli.format_priority.iter().map(|ext| ext.trim().trim_start_matches('.').to_ascii_lowercase()).collect(),
+        keep_formats: cli.keep_formats as usize,
+        min_keep_size: cli.min_keep_size,
+        size_ratio: cli.size_ratio,
+        template: cli.template.clone(),
+        case: cli.case,
+        epub_title: cli.epub_title,
+        series_format: cli.series_format.clone(),
+        series_folders: cli.series_folders,
+        edition_markers: cli.edition_markers,
+        group_by: cli.group_by,
+        junk: match &cli.junk {
+            Some(path) => read_junk_patterns(path)?,
+            None => Vec::new(),
+        },
+        exclude: ExcludeList::build(&cli.exclude, cli.exclude_file.as_deref())?,
+        accents_db: if cli.restore_accents {
+            if !cli.db.exists() {
+                anyhow::bail!("--restore-accents: base introuvable {:?}", cli.db);
+            }
+            Some(Mutex::new(Connection::open(&cli.db).with_context(|| format!("Ouverture de la base {:?}", cli.db))?))
+        } else {
+            None
+        },
+        max_depth: cli.max_depth,
+        nfc: cli.nfc,
+        flatten: cli.flatten,
+        original_names: cli.original_names,
+        interactive: cli.interactive,
+        stop: AtomicBool::new(false),
+        collisions: AtomicUsize::new(0),
+        dry_run: cli.dry_run,
+        verbose: cli.verbose,
+        snapshot: match &cli.snapshot {
+            Some(base) if !cli.dry_run => {
+                let (snapshot, files) = Snapshot::take(base, "cleanup-filenames", std::slice::from_ref(&cli.root), cli.snapshot_keep_deleted)
+                    .map_err(|e| anyhow::anyhow!("Instantané dans {:?}: {}", base, e))?;
+                say!("Instantané de {} fichier(s): {:?} (restaurer avec `sortbook restore {}`)", files, snapshot.dir(), snapshot.dir().display());
+                Some(snapshot)
+            }
+            _ => None,
+        },
+    };
+    if settings.exclude.pattern_count() > 0 {
+        say!("Exclusion: {} motif(s).", settings.exclude.pattern_count());
+    }
+    if let Some(path) = &cli.log_file {
+        init_log_file(path);
+        info!("start root={:?} dry_run={}", cli.root, cli.dry_run);
+    }
+    let started = chrono::Local::now();
+    let journal = Mutex::new(if cli.dry_run {
+        Journal::disabled()
+    } else {
+        Journal::at(cli.journal.clone().unwrap_or_else(Journal::default_path))
+            .with_ledger(Ledger::for_run(cli.ledger.as_deref(), "cleanup-filenames", false)?)
+    });
+    let report = Mutex::new(Vec::new());
+
+    // On parcourt uniquement les dossiers de premier niveau (auteurs)
+    // Collecte des dossiers auteurs
+    let author_dirs: Vec<PathBuf> = fs::read_dir(&cli.root)
+        .with_context(|| format!("Lecture du dossier {:?}", &cli.root))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        // Une quarantaine placée sous la racine n'est pas un dossier d'auteur
+        .filter(|p| cli.quarantine.as_ref().is_none_or(|q| !p.starts_with(q)))
+        .filter(|p| !settings.exclude.is_excluded(p))
+        .collect();
+
+    // Barre de progression sur les dossiers d'auteurs (masquée en --interactive, où elle gênerait les questions)
+    let bar = if settings.interactive { ProgressBar::hidden() } else { ProgressBar::new(author_dirs.len() as u64) };
+    bar.set_style(
+        ProgressStyle::with_template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_bar()),
+    );
+    // --incremental: empreintes du dernier lancement réel, pour ces options
+    let state_path = cli.state.clone().unwrap_or_else(|| cli.root.join(fingerprint::STATE_NAME));
+    let state = Mutex::new(if cli.incremental { fingerprint::State::load(&state_path, &options_fingerprint(&cli)) } else { fingerprint::State::default() });
+    // --scan-index: fichiers vus au dernier lancement réel, pour ces options
+    let scan = match &cli.scan_index {
+        Some(path) => Some(Mutex::new(
+            ScanIndex::open(path, &format!("cleanup-filenames:{}", options_fingerprint(&cli)))
+                .with_context(|| format!("Ouverture de l'index {:?}", path))?,
+        )),
+        None => None,
+    };
+    let unchanged = AtomicUsize::new(0);
+    let process = |dir: &PathBuf| {
+        let name = dir.file_name().and_then(OsStr::to_str).unwrap_or("<inconnu>").to_string();
+        bar.set_message(name.clone());
+        if cli.incremental {
+            let current = fingerprint::folder(dir);
+            if state.lock().unwrap().is_unchanged(&name, &current) {
+                unchanged.fetch_add(1, atomic::Ordering::Relaxed);
+                bar.inc(1);
+                return (name, Stats::default());
+            }
+        }
+        if let Some(scan) = &scan {
+            if !scan.lock().unwrap().folder_changed(dir).unwrap_or(true) {
+                unchanged.fetch_add(1, atomic::Ordering::Relaxed);
+                bar.inc(1);
+                return (name, Stats::default());
+            }
+        }
+        let result = process_author_dir(dir, &settings, &journal, &report);
+        // Empreinte prise après les changements; un dossier en erreur ou interrompu sera repris
+        if cli.incremental && !settings.dry_run && result.is_ok() && !settings.stop.load(atomic::Ordering::Relaxed) {
+            let current = fingerprint::folder(dir);
+            state.lock().unwrap().set(&name, current);
+        }
+        if let Some(scan) = &scan {
+            if !settings.dry_run && result.is_ok() && !settings.stop.load(atomic::Ordering::Relaxed) {
+                if let Err(e) = scan.lock().unwrap().record_folder(dir) {
+                    say!("Index: {:?} non enregistré: {}", dir, e);
+                }
+            }
+        }
+        bar.inc(1);
+        (name, result.unwrap_or_default())
+    };
+    // Traitement en parallèle par dossier d'auteur; en --interactive un auteur après l'autre pour ne pas mêler les questions
+    let per_author: Vec<(String, Stats)> = if settings.interactive {
+        author_dirs.iter().map(process).collect()
+    } else {
+        author_dirs.par_iter().map(process).collect()
+    };
+    bar.finish_and_clear();
+    let total_files: usize = per_author.iter().map(|(_, stats)| stats.groups).sum();
+    if let Some(scan) = scan {
+        let mut scan = scan.into_inner().unwrap();
+        say!("Index: {} dossier(s) d'auteur inchangé(s) ignoré(s), {} fichier(s) indexé(s).", unchanged.load(atomic::Ordering::Relaxed), scan.len());
+        if !settings.dry_run {
+            scan.save().map_err(|e| anyhow::anyhow!("Écriture de l'index: {}", e))?;
+        }
+    }
+    if cli.incremental {
+        say!("Incrémental: {} dossier(s) d'auteur inchangé(s) ignoré(s).", unchanged.load(atomic::Ordering::Relaxed));
+        if !settings.dry_run {
+            let mut state = state.into_inner().unwrap();
+            state.retain(&per_author.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>());
+            state.save(&state_path)?;
+        }
+    }
+    if settings.stop.load(atomic::Ordering::Relaxed) {
+        say!("Arrêt demandé: les groupes restants sont laissés tels quels.");
+    }
+
+    // Rapports triés par auteur puis par nom, quel que soit l'ordre du traitement parallèle
+    let mut rows = report.into_inner().unwrap();
+    rows.sort_by(|a, b| (&a.author, &a.old_name).cmp(&(&b.author, &b.old_name)));
+    if let Some(path) = &cli.report_csv {
+        report::write_csv(path, &rows)?;
+        say!("Rapport CSV: {:?} ({} changement(s))", path, rows.len());
+    }
+    if let Some(path) = &cli.report_diff {
+        report::write_diff(path, &rows)?;
+        say!("Rapport diff: {:?}", path);
+    }
+
+    stats::print_table(&per_author, settings.dry_run);
+    let summary_path = cli.summary.clone()
+        .or_else(|| cli.log_file.as_ref().map(|log| log.parent().unwrap_or(Path::new("")).join("summary.json")));
+    if let Some(path) = summary_path {
+        let collisions = settings.collisions.load(atomic::Ordering::Relaxed);
+        stats::write_summary(&path, &cli.root, settings.dry_run, started, collisions, &per_author)?;
+        say!("Résumé JSON: {:?}", path);
+    }
+    say!("Terminé. Total fichiers traités: {}", total_files);
+    let collisions = settings.collisions.load(atomic::Ordering::Relaxed);
+    if collisions > 0 {
+        say!("Collisions de noms: {} fichier(s) renommé(s) avec un suffixe (voir les lignes COLLISION)", collisions);
+    }
+    if let Some(snapshot) = settings.snapshot.as_ref().filter(|snapshot| snapshot.kept() > 0) {
+        say!("Instantané: {} fichier(s) supprimé(s) gardé(s) dans {:?}", snapshot.kept(), snapshot.dir());
+    }
+    let mut journal = journal.into_inner().unwrap();
+    if let Some(path) = journal.written_path() {
+        say!("Journal d'annulation: {:?} (annuler avec `--revert <fichier> --dry-run false`)", path);
+    }
+    journal.finish(settings.stop.load(atomic::Ordering::Relaxed))?;
+    Ok(())
+}
+
+/// Empreinte des options qui changent le résultat d'un lancement (sorties, affichage et dry-run exclus),
+/// avec le contenu des fichiers --junk et --exclude-file.
+fn options_fingerprint(cli: &Cli) -> String {
+    let mut options = cli.clone();
+    options.dry_run = false;
+    options.verbose = false;
+    options.report_csv = None;
+    options.report_diff = None;
+    options.log_file = None;
+    options.summary = None;
+    options.journal = None;
+    options.state = None;
+    options.scan_index = None;
+    options.snapshot = None;
+    options.snapshot_keep_deleted = false;
+    options.ledger = None;
+    let mut text = format!("{:?}", options);
+    for path in [&cli.junk, &cli.exclude_file].into_iter().flatten() {
+        text.push_str(&fs::read_to_string(path).unwrap_or_default());
+    }
+    fingerprint::digest(&text)
+}
+
+/// Journal texte comme celui du tri: horodatage RFC 3339, niveau, message clé=valeur.
+fn init_log_file(path: &Path) {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        let _ = fs::create_dir_all(parent);
+    }
+    match fs::File::create(path) {
+        Ok(file) => {
+            let cfg = simplelog::ConfigBuilder::new().set_time_format_rfc3339().set_target_level(simplelog::LevelFilter::Off).build();
+            if let Err(e) = simplelog::WriteLogger::init(simplelog::LevelFilter::Info, cfg, file) {
+                eprintln!("[warn] journal texte indisponible: {e}");
+            }
+        }
+        Err(e) => eprintln!("[warn] impossible de créer le journal {:?}: {e}", path),
+    }
+}
+
+fn ensure_dir(path: &Path) -> Result<()> {
+    if !path.exists() {
+        fs::create_dir_all(path).with_context(|| format!("Création du dossier {:?}", path))?;
+    }
+    Ok(())
+}
+
+fn parse_exts(exts: &str) -> Option<Vec<String>> {
+    let trimmed = exts.trim();
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(
+            trimmed
+                .split(',')
+                .map(|s| s.trim().trim_start_matches('.').to_ascii_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect(),
+        )
+    }
+}
+
+fn process_author_dir(dir: &Path, settings: &Settings, journal: &Mutex<Journal>, report: &Mutex<Vec<PlanRow>>) -> Result<Stats> {
+    // Collecte des fichiers directement dans ce dossier et dans ses sous-dossiers
+    // Traitement par sous-dossiers: on traite chaque sous-dossier indépendamment
+    let mut stats = Stats::default();
+    let author_name = dir.file_name().and_then(OsStr::to_str).unwrap_or("<inconnu>");
+
+    // --flatten: les dossiers d'un seul titre sont vidés dans la racine avant le groupement
+    let flattened = if settings.flatten { flatten_subdirs(dir, settings, journal, report)? } else { Vec::new() };
+
+    // Traiter les fichiers à la racine du dossier auteur comme un groupe séparé
+    stats += process_one_group(dir, dir, settings, journal, report)?;
+
+    stats += process_subdirs(dir, dir, 1, &flattened, settings, journal, report)?;
+
+    // Affichage par auteur (le tableau de fin les reprend tous)
+    if settings.verbose {
+        say!("Auteur: {} — fichiers traités: {}", author_name, stats.groups);
+    }
+    Ok(stats)
+}
+
+/// Chaque sous-dossier de `dir` est un groupe, puis ses propres sous-dossiers jusqu'à --max-depth.
+/// Les dossiers de `skip` (aplatis par --flatten, encore présents en dry-run) sont ignorés.
+fn process_subdirs(
+    dir: &Path,
+    author_dir: &Path,
+    depth: usize,
+    skip: &[PathBuf],
+    settings: &Settings,
+    journal: &Mutex<Journal>,
+    report: &Mutex<Vec<PlanRow>>,
+) -> Result<Stats> {
+    if depth > settings.max_depth {
+        return Ok(Stats::default());
+    }
+    let mut stats = Stats::default();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        // Au-delà du premier niveau, les liens symboliques ne sont pas suivis (boucles)
+        if path.is_dir() && (depth == 1 || !entry.file_type()?.is_symlink()) && !settings.exclude.is_excluded(&path) && !skip.contains(&path) {
+            stats += process_one_group(&path, author_dir, settings, journal, report)?;
+            stats += process_subdirs(&path, author_dir, depth + 1, skip, settings, journal, report)?;
+        }
+    }
+    Ok(stats)
+}
+
+/// Fichiers annexes d'un dossier par livre (Calibre, copies manuelles), écartés par --flatten.
+const COMPANION_NAMES: [&str; 7] = ["cover.jpg", "cover.jpeg", "cover.png", "folder.jpg", "thumbs.db", "desktop.ini", ".ds_store"];
+const COMPANION_EXTS: [&str; 4] = ["opf", "nfo", "url", "sfv"];
+
+fn is_companion(path: &Path) -> bool {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let ext = path.extension().and_then(OsStr::to_str).map(str::to_ascii_lowercase).unwrap_or_default();
+    COMPANION_NAMES.contains(&name.as_str()) || COMPANION_EXTS.contains(&ext.as_str())
+}
+
+/// --flatten: pour chaque sous-dossier direct de l'auteur sans sous-dossier, dont les livres ont tous la même clé
+/// de titre, remonte les livres à la racine (nom déjà pris: suffixe " (2)"), écarte les annexes et supprime le dossier vide.
+/// Rend les dossiers aplatis.
+fn flatten_subdirs(author_dir: &Path, settings: &Settings, journal: &Mutex<Journal>, report: &Mutex<Vec<PlanRow>>) -> Result<Vec<PathBuf>> {
+    let author_folder = author_dir.file_name().and_then(OsStr::to_str).unwrap_or("<inconnu>");
+    let mut flattened = Vec::new();
+    // Noms pris à la racine par les livres déjà remontés (le dry-run ne déplace rien)
+    let mut claimed: std::collections::HashSet<PathBuf> = std::collections::HashSet::new();
+    for entry in fs::read_dir(author_dir)? {
+        let entry = entry?;
+        let sub = entry.path();
+        if !entry.file_type()?.is_dir() || settings.exclude.is_excluded(&sub) {
+            continue;
+        }
+        let mut books = Vec::new();
+        let mut companions = Vec::new();
+        let mut other = false;
+        for file in fs::read_dir(&sub)? {
+            let path = file?.path();
+            if path.is_dir() || settings.exclude.is_excluded(&path) {
+                other = true;
+            } else if path.file_name() == Some(OsStr::new(sidecar::SIDECAR_NAME)) {
+                continue;
+            } else if is_companion(&path) {
+                companions.push(path);
+            } else {
+                let ext = path.extension().and_then(OsStr::to_str).map(|e| e.to_ascii_lowercase()).unwrap_or_default();
+                if settings.allowed_exts.as_ref().is_some_and(|exts| !exts.contains(&ext)) {
+                    other = true;
+                } else {
+                    books.push(path);
+                }
+            }
+        }
+        // Dossier de série, de plusieurs titres ou au contenu inconnu: laissé tel quel
+        let keys: std::collections::HashSet<String> = books.iter()
+            .map(|book| title_key(book.file_stem().and_then(OsStr::to_str).unwrap_or(""), settings).0)
+            .collect();
+        if other || books.is_empty() || keys.len() > 1 {
+            continue;
+        }
+
+        for book in &books {
+            let wanted_name = book.file_name().and_then(OsStr::to_str).unwrap_or("").to_string();
+            let mut target_name = wanted_name.clone();
+            let mut suffix = 1;
+            while claimed.contains(&author_dir.join(&target_name)) || author_dir.join(&target_name).exists() {
+                suffix += 1;
+                target_name = with_suffix(&wanted_name, suffix);
+            }
+            let target = author_dir.join(&target_name);
+            claimed.insert(target.clone());
+            let shown = book.strip_prefix(author_dir).unwrap_or(book);
+            if suffix > 1 {
+                settings.collisions.fetch_add(1, atomic::Ordering::Relaxed);
+                say!("COLLISION -> {:?} déjà pris à la racine, {:?} devient {:?}", wanted_name, shown, target_name);
+            } else if settings.verbose {
+                say!("APLATI -> {:?}  =>  {:?}", shown, target_name);
+            }
+            let size = file_size(book).unwrap_or(0);
+            report.lock().unwrap().push(PlanRow {
+                author: author_folder.to_string(),
+                old_name: shown.to_string_lossy().into_owned(),
+                new_name: target_name.clone(),
+                action: if suffix > 1 { "collision" } else { "flatten" },
+                group_key: String::new(),
+                size,
+            });
+            let reason = if suffix > 1 { format!("collision, {} déjà pris", wanted_name) } else { "dossier d'un seul titre".to_string() };
+            info!("flatten old={:?} new={:?} size={} reason={:?} dry_run={}", book, target, size, reason, settings.dry_run);
+            if !settings.dry_run {
+                fs::rename(book, &target).with_context(|| format!("Rename {:?} -> {:?}", book, target))?;
+                journal.lock().unwrap().record(JournalOp::Rename, book, Some(&target))?;
+                if settings.original_names {
+                    sidecar::record(book, &target)?;
+                }
+            }
+            file_event("rename", book, Some(&target), &reason, settings.dry_run);
+        }
+        for companion in &companions {
+            let size = file_size(companion).unwrap_or(0);
+            report.lock().unwrap().push(PlanRow {
+                author: author_folder.to_string(),
+                old_name: companion.strip_prefix(author_dir).unwrap_or(companion).to_string_lossy().into_owned(),
+                new_name: String::new(),
+                action: settings.discard.action(),
+                group_key: String::new(),
+                size,
+            });
+            if !settings.dry_run {
+                if let Err(e) = discard_file(companion, settings, journal) {
+                    eprintln!("Erreur suppression {:?}: {}", companion, e);
+                    warn!("{} failed old={:?} error={:?}", settings.discard.action(), companion, e.to_string());
+                    Event::new("cleanup-filenames", "discard", companion, "error").detail(e.to_string()).emit();
+                    continue;
+                }
+            }
+            info!("{} old={:?} size={} reason={:?} dry_run={}", settings.discard.action(), companion, size, "annexe d'un dossier aplati", settings.dry_run);
+            file_event("discard", companion, None, &format!("{}: annexe d'un dossier aplati", settings.discard.action()), settings.dry_run);
+        }
+        // Un dossier encore occupé (annexe non écartée, .original_names.json restant) est gardé
+        if !settings.dry_run {
+            let _ = fs::remove_dir(&sub);
+        }
+        flattened.push(sub);
+    }
+    Ok(flattened)
+}
+
+fn process_one_group(group_dir: &Path, author_dir: &Path, settings: &Settings, journal: &Mutex<Journal>, report: &Mutex<Vec<PlanRow>>) -> Result<Stats> {
+    // Map d'identifiant de groupe (baseNameNormalisé, selon --group-by) -> variantes, dans l'ordre de lecture
+    use std::collections::HashMap;
+    let mut originals_by_norm: HashMap<String, Vec<FileEntry>> = HashMap::new();
+    // Série détectée pour une clé (--series-format)
+    let mut series_by_norm: HashMap<String, String> = HashMap::new();
+    let author_folder = author_dir.file_name().and_then(OsStr::to_str).unwrap_or("<inconnu>");
+    // Ligne de rapport, chemins relatifs au dossier de l'auteur
+    let plan_row = |file: &FileEntry, new_path: Option<&Path>, action: &'static str, key: &str| PlanRow {
+        author: author_folder.to_string(),
+        old_name: file.path.strip_prefix(author_dir).unwrap_or(&file.path).to_string_lossy().into_owned(),
+        new_name: new_path.map(|p| p.strip_prefix(author_dir).unwrap_or(p).to_string_lossy().into_owned()).unwrap_or_default(),
+        action,
+        group_key: key.to_string(),
+        size: file.size,
+    };
+
+    let mut stats = Stats::default();
+
+    for entry in fs::read_dir(group_dir).unwrap_or_else(|_| fs::read_dir(group_dir).unwrap()) {
+        let entry = match entry {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        let path = entry.path();
+        // Le fichier des noms d'origine n'est pas un livre
+        if path.is_file() && path.file_name() != Some(OsStr::new(sidecar::SIDECAR_NAME)) && !settings.exclude.is_excluded(&path) {
+            if let Some(exts) = &settings.allowed_exts {
+                let ext = path.extension().and_then(OsStr::to_str).map(|s| s.to_ascii_lowercase());
+                if ext.is_none() || !exts.contains(&ext.unwrap()) {
+                    continue;
+                }
+            }
+
+            let file_name = match path.file_stem().and_then(OsStr::to_str) {
+                Some(s) => s,
+                None => continue,
+            };
+
+            let (norm_key, series) = title_key(file_name, settings);
+            if let Some(series) = series {
+                series_by_norm.insert(norm_key.clone(), series);
+            }
+            let size = file_size(&path).unwrap_or(0);
+            let id = settings.group_by.id(&norm_key, &strip_junk(file_name, settings));
+            originals_by_norm.entry(id).or_default().push(FileEntry { path: path.clone(), size, key: norm_key });
+        }
+    }
+
+    // Dossier et nom cibles d'un fichier conservé
+    let target_of = |best: &FileEntry, norm: &str| -> (PathBuf, String) {
+        let ext = best.path.extension().and_then(OsStr::to_str).unwrap_or("");
+        // Titre embarqué (dc:title) d'un EPUB, normalisé comme les noms de fichiers
+        let embedded = if settings.epub_title && ext.eq_ignore_ascii_case("epub") {
+            epub::epub_title(&best.path).map(|title| (title_key(&title, settings), title)).filter(|((key, _), _)| !key.is_empty())
+        } else {
+            None
+        };
+        let (title_key, series) = match &embedded {
+            Some(((key, series), _)) => (key.as_str(), series.as_deref()),
+            None => (norm, series_by_norm.get(norm).map(String::as_str)),
+        };
+        // Nom d'origine, source de la casse pour --case sentence/keep
+        let original = match &embedded {
+            Some((_, title)) => title.as_str(),
+            None => best.path.file_stem().and_then(OsStr::to_str).unwrap_or(""),
+        };
+        // --restore-accents: un nom accentué garde ses accents, un nom sans accents reprend ceux du titre de la base
+        let accented = settings.accents_db.as_ref().and_then(|db| {
+            let own = tidy_basename(best.path.file_stem().and_then(OsStr::to_str).unwrap_or(""));
+            if has_accents(&own) {
+                Some(own).filter(|own| normalize_basename_for_group(own) == title_key)
+            } else {
+                accented_title(&db.lock().unwrap(), title_key)
+            }
+        });
+        let title_key = accented.as_deref().unwrap_or(title_key);
+        // Nouveau nom: capitale sur la première lettre uniquement (ou selon --case), ou selon --template
+        let target_stem = match &settings.template {
+            Some(template) => render_template(template, &case::apply(settings.case, strip_author(title_key, author_folder), original), author_folder),
+            None => case::apply(settings.case, title_key, original),
+        };
+        let target_stem = if settings.nfc { target_stem.nfc().collect() } else { target_stem };
+        // Caractères interdits, noms réservés et points ou espaces en bord: mêmes règles que l'outil de nettoyage
+        let target_stem = sanitize_component(&target_stem);
+        let target_name = if ext.is_empty() { target_stem.clone() } else { format!("{}.{}", target_stem, ext) };
+        // --series-folders: un tome à la racine de l'auteur va dans le dossier de sa série
+        let target_dir = match series {
+            Some(series) if settings.series_folders && group_dir == author_dir => {
+                let folder = case::apply(settings.case, series, original);
+                group_dir.join(sanitize_component(&if settings.nfc { folder.nfc().collect() } else { folder }))
+            }
+            _ => best.path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+        };
+        (target_dir, target_name)
+    };
+
+    // Noms cibles déjà attribués dans ce dossier (le dry-run ne renomme rien sur le disque)
+    let mut claimed: std::collections::HashSet<PathBuf> = std::collections::HashSet::new();
+    // Appliquer suppressions des doublons puis renommages dans le groupe
+    for (id, all) in originals_by_norm.iter() {
+        let norm = grouping::label(id);
+        if settings.stop.load(atomic::Ordering::Relaxed) {
+            break;
+        }
+        if settings.nfc {
+            report_unicode_twins(all);
+        }
+        let mut kept = keep_variants(all, settings);
+        // Doublons retenus par --min-keep-size / --size-ratio: laissés en place, sous leur nom
+        let mut held = size_guard(all, &kept, settings);
+        if settings.interactive && all.len() > 1 {
+            let target = |file: &FileEntry| {
+                let (dir, name) = target_of(file, &file.key);
+                dir.join(name)
+            };
+            match ask_group(norm, author_dir, all, &kept, &held, settings, target)? {
+                GroupChoice::Keep => {}
+                // Un choix explicite vaut confirmation: les autres fichiers sont écartés, même retenus par la taille
+                GroupChoice::Choose(choice) => {
+                    kept = choice;
+                    held.clear();
+                }
+                GroupChoice::Skip => continue,
+                GroupChoice::Quit => {
+                    settings.stop.store(true, atomic::Ordering::Relaxed);
+                    break;
+                }
+            }
+        }
+
+        // Supprimer les autres doublons (conserver les meilleurs) avant de libérer leurs noms
+        for other in all {
+            if let Some((_, reason)) = held.iter().find(|(h, _)| h.path == other.path) {
+                say!("GARDE -> {:?} n'est pas écarté: {}", other.path.file_name().unwrap_or(OsStr::new("")), reason);
+                info!("hold old={:?} size={} key={:?} reason={:?} dry_run={}", other.path, other.size, norm, reason, settings.dry_run);
+                report.lock().unwrap().push(plan_row(other, None, "hold", norm));
+                Event::new("cleanup-filenames", "discard", &other.path, "skipped").detail(reason.as_str()).emit();
+                continue;
+            }
+            if !kept.iter().any(|k| k.path == other.path) {
+                if settings.verbose {
+                    say!("SUPPR -> {:?}", other.path.file_name().unwrap_or(OsStr::new("")));
+                }
+                report.lock().unwrap().push(plan_row(other, None, settings.discard.action(), norm));
+                if !settings.dry_run {
+                    // Garder le fichier le plus lourd: les autres sont supprimés, ou mis à la corbeille / en quarantaine
+                    if let Err(e) = discard_file(&other.path, settings, journal) {
+                        eprintln!("Erreur suppression {:?}: {}", &other.path, e);
+                        warn!("{} failed old={:?} error={:?}", settings.discard.action(), other.path, e.to_string());
+                        Event::new("cleanup-filenames", "discard", &other.path, "error").detail(e.to_string()).emit();
+                        continue;
+                    }
+                }
+                let keepers: Vec<String> = kept.iter().map(|k| k.path.file_name().unwrap_or_default().to_string_lossy().into_owned()).collect();
+                let reason = format!("doublon de {}", keepers.join(", "));
+                info!(
+                    "{} old={:?} size={} key={:?} reason={:?} dry_run={}",
+                    settings.discard.action(), other.path, other.size, norm, reason, settings.dry_run
+                );
+                file_event("discard", &other.path, None, &format!("{}: {}", settings.discard.action(), reason), settings.dry_run);
+                stats.discarded += 1;
+                stats.reclaimed += other.size;
+            }
+        }
+
+        // Un doublon laissé en place garde son nom: il n'est pas libéré pour le fichier gardé
+        claimed.extend(held.iter().map(|(file, _)| file.path.clone()));
+        for best in &kept {
+            let (target_dir, wanted_name) = target_of(best, &best.key);
+            let current_name = best.path.file_name().and_then(OsStr::to_str).unwrap_or("");
+            // Un autre titre (ex. même dc:title) occupe déjà ce nom: on ne l'écrase pas
+            let occupied = |path: &Path, name: &str| {
+                *path != best.path
+                    && (claimed.contains(path)
+                        || (path.exists() && !all.iter().any(|f| f.path == path) && !same_name(current_name, name)))
+            };
+            // Nom libre suivant: "Titre (2).epub", "Titre (3).epub"...
+            let mut target_name = wanted_name.clone();
+            let mut suffix = 1;
+            while occupied(&target_dir.join(&target_name), &target_name) {
+                suffix += 1;
+                target_name = with_suffix(&wanted_name, suffix);
+            }
+            let target_path = target_dir.join(&target_name);
+
+            // Renommer le meilleur si nécessaire
+            if best.path != target_path {
+                claimed.insert(target_path.clone());
+                let action = if suffix > 1 {
+                    settings.collisions.fetch_add(1, atomic::Ordering::Relaxed);
+                    say!("COLLISION -> {:?} déjà pris, {:?} devient {:?}", wanted_name, current_name, target_name);
+                    "collision"
+                } else {
+                    "rename"
+                };
+                report.lock().unwrap().push(plan_row(best, Some(&target_path), action, norm));
+                stats.renamed += 1;
+                let reason = if suffix > 1 {
+                    format!("collision, {} déjà pris", wanted_name)
+                } else if current_name != target_name && current_name.nfc().eq(target_name.nfc()) {
+                    "forme Unicode NFC".to_string()
+                } else {
+                    "normalisation".to_string()
+                };
+                info!(
+                    "{} old={:?} new={:?} size={} key={:?} reason={:?} dry_run={}",
+                    action, best.path, target_path, best.size, norm, reason, settings.dry_run
+                );
+                if settings.verbose {
+                    let shown = target_path.strip_prefix(group_dir).unwrap_or(&target_path);
+                    say!("RENOM -> {:?}  =>  {:?}", best.path.file_name().unwrap_or(OsStr::new("")), shown);
+                }
+                if !settings.dry_run {
+                    fs::create_dir_all(&target_dir)?;
+                    fs::rename(&best.path, &target_path).with_context(|| format!("Rename {:?} -> {:?}", &best.path, &target_path))?;
+                    journal.lock().unwrap().record(JournalOp::Rename, &best.path, Some(&target_path))?;
+                    if settings.original_names {
+                        sidecar::record(&best.path, &target_path)?;
+                    }
+                }
+                file_event("rename", &best.path, Some(&target_path), &reason, settings.dry_run);
+            }
+        }
+
+        stats.kept += kept.len() + held.len();
+        stats.groups += 1;
+    }
+
+    Ok(stats)
+}
+
+/// Événement --output json d'un fichier renommé ou écarté: "planned" en simulation, "ok" une fois fait.
+fn file_event(action: &'static str, path: &Path, target: Option<&Path>, detail: &str, dry_run: bool) {
+    let mut event = Event::new("cleanup-filenames", action, path, if dry_run { "planned" } else { "ok" }).detail(detail);
+    if let Some(target) = target {
+        event = event.target(target);
+    }
+    event.emit();
+}
+
+/// Réponse de l'utilisateur pour un groupe en mode --interactive.
+enum GroupChoice<'a> {
+    /// Plan affiché accepté.
+    Keep,
+    /// Fichiers à garder désignés par leur numéro.
+    Choose(Vec<&'a FileEntry>),
+    Skip,
+    Quit,
+}
+
+/// Affiche les variantes d'un groupe, le choix de l'heuristique et les changements prévus, puis lit la réponse sur stdin.
+fn ask_group<'a>(
+    norm: &str,
+    author_dir: &Path,
+    all: &'a [FileEntry],
+    kept: &[&'a FileEntry],
+    held: &[(&FileEntry, String)],
+    settings: &Settings,
+    target: impl Fn(&FileEntry) -> PathBuf,
+) -> Result<GroupChoice<'a>> {
+    let relative = |path: &Path| path.strip_prefix(author_dir).unwrap_or(path).to_string_lossy().into_owned();
+    let discard_label = match settings.discard {
+        Discard::Delete => "supprimer",
+        Discard::Trash => "corbeille",
+        Discard::Quarantine { .. } => "quarantaine",
+    };
+    say!();
+    say!("Groupe {:?} — {}", norm, author_dir.file_name().and_then(OsStr::to_str).unwrap_or("<inconnu>"));
+    for (index, file) in all.iter().enumerate() {
+        if kept.iter().any(|k| k.path == file.path) {
+            let target_path = target(file);
+            let plan = if target_path == file.path { "nom inchangé".to_string() } else { format!("-> {}", relative(&target_path)) };
+            say!("  {}. [garder]    {} ({} octets) {}", index + 1, relative(&file.path), file.size, plan);
+        } else if let Some((_, reason)) = held.iter().find(|(h, _)| h.path == file.path) {
+            say!("  {}. [laisser]   {} ({} octets) {}", index + 1, relative(&file.path), file.size, reason);
+        } else {
+            say!("  {}. [{}] {} ({} octets)", index + 1, discard_label, relative(&file.path), file.size);
+        }
+    }
+    loop {
+        print!("Entrée = valider, numéros à garder (ex. 2 ou 1,3), s = passer ce groupe, q = arrêter > ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        // Fin de l'entrée standard: on arrête plutôt que de valider sans réponse
+        if io::stdin().read_line(&mut line)? == 0 {
+            say!();
+            return Ok(GroupChoice::Quit);
+        }
+        let answer = line.trim();
+        match answer {
+            "" => return Ok(GroupChoice::Keep),
+            "s" | "S" => return Ok(GroupChoice::Skip),
+            "q" | "Q" => return Ok(GroupChoice::Quit),
+            _ => {}
+        }
+        let numbers: Option<Vec<usize>> = answer
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|part| !part.is_empty())
+            .map(|part| part.parse::<usize>().ok().filter(|n| (1..=all.len()).contains(n)))
+            .collect();
+        match numbers {
+            Some(numbers) if !numbers.is_empty() => {
+                let mut choice: Vec<&FileEntry> = Vec::new();
+                for n in numbers {
+                    if !choice.iter().any(|f| f.path == all[n - 1].path) {
+                        choice.push(&all[n - 1]);
+                    }
+                }
+                return Ok(GroupChoice::Choose(choice));
+            }
+            _ => say!("Choix invalide: numéros entre 1 et {}, s ou q.", all.len()),
+        }
+    }
+}
+
+/// `Titre.epub` -> `Titre (n).epub`
+/// Même nom à la casse ou à la normalisation Unicode près: sous macOS ou Windows c'est le même fichier,
+/// que l'on peut renommer vers sa nouvelle forme.
+fn same_name(a: &str, b: &str) -> bool {
+    a.eq_ignore_ascii_case(b) || a.nfc().eq(b.nfc())
+}
+
+/// --nfc: signale les fichiers d'un groupe dont les noms ne diffèrent que par la normalisation Unicode (NFD/NFC).
+fn report_unicode_twins(all: &[FileEntry]) {
+    let name = |file: &FileEntry| file.path.file_name().and_then(OsStr::to_str).unwrap_or("").to_string();
+    for (i, a) in all.iter().enumerate() {
+        for b in &all[i + 1..] {
+            let (name_a, name_b) = (name(a), name(b));
+            if name_a != name_b && name_a.nfc().eq(name_b.nfc()) {
+                say!("UNICODE -> {:?} et {:?} ne diffèrent que par la normalisation (NFD/NFC)", name_a, name_b);
+                info!("unicode old={:?} twin={:?} reason={:?}", a.path, b.path, "noms NFD/NFC identiques");
+            }
+        }
+    }
+}
+
+fn with_suffix(name: &str, n: usize) -> String {
+    let path = Path::new(name);
+    match (path.file_stem().and_then(OsStr::to_str), path.extension().and_then(OsStr::to_str)) {
+        (Some(stem), Some(ext)) => format!("{} ({}).{}", stem, n, ext),
+        _ => format!("{} ({})", name, n),
+    }
+}
+
+/// Doublons à ne pas écarter: tous si chaque fichier gardé est sous --min-keep-size, sinon ceux dont la taille
+/// s'écarte de plus de --size-ratio de celle de chaque fichier gardé. Chacun avec la raison affichée.
+fn size_guard<'a>(all: &'a [FileEntry], kept: &[&FileEntry], settings: &Settings) -> Vec<(&'a FileEntry, String)> {
+    let ratio = |a: u64, b: u64| a.max(b) as f64 / a.min(b).max(1) as f64;
+    all.iter()
+        .filter(|file| !kept.iter().any(|k| k.path == file.path))
+        .filter_map(|file| {
+            if !kept.is_empty() && kept.iter().all(|k| k.size < settings.min_keep_size) {
+                return Some((file, format!("fichier gardé sous --min-keep-size ({} octets)", settings.min_keep_size)));
+            }
+            let limit = settings.size_ratio?;
+            let closest = kept.iter().map(|k| ratio(k.size, file.size)).fold(f64::INFINITY, f64::min);
+            (!kept.is_empty() && closest > limit).then(|| (file, format!("taille x{:.1} par rapport au fichier gardé (--size-ratio {})", closest, limit)))
+        })
+        .collect()
+}
+
+/// Taille en octets, avec suffixe K, M ou G facultatif (puissances de 1024).
+fn parse_size(value: &str) -> std::result::Result<u64, String> {
+    let value = value.trim();
+    let (number, unit) = match value.char_indices().find(|(_, c)| c.is_ascii_alphabetic()) {
+        Some((i, _)) => (&value[..i], &value[i..]),
+        None => (value, ""),
+    };
+    let factor: u64 = match unit.to_ascii_uppercase().trim_end_matches(['B', 'O']) {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => return Err(format!("unité inconnue {:?} (K, M ou G)", unit)),
+    };
+    let number: f64 = number.trim().parse().map_err(|_| format!("taille invalide {:?}", value))?;
+    if number < 0.0 {
+        return Err(format!("taille invalide {:?}", value));
+    }
+    Ok((number * factor as f64) as u64)
+}
+
+fn parse_size_ratio(value: &str) -> std::result::Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(ratio) if ratio > 1.0 => Ok(ratio),
+        _ => Err(format!("rapport invalide {:?}: nombre supérieur à 1 attendu", value)),
+    }
+}
+
+/// Variantes conservées d'un titre: la meilleure toutes extensions confondues (`--keep larger`),
+/// ou la meilleure de chacun des `--keep-formats` formats les mieux classés présents (`--keep format`).
+fn keep_variants<'a>(all: &'a [FileEntry], settings: &Settings) -> Vec<&'a FileEntry> {
+    match settings.keep {
+        KeepPolicy::Larger => pick_best(all.iter()).into_iter().collect(),
+        KeepPolicy::Format => {
+            let mut formats: Vec<String> = Vec::new();
+            for ext in all.iter().map(extension_of) {
+                if !formats.contains(&ext) { formats.push(ext); }
+            }
+            // Tri stable: les extensions hors liste restent dans l'ordre de lecture, après les autres
+            formats.sort_by_key(|ext| settings.format_priority.iter().position(|p| p == ext).unwrap_or(usize::MAX));
+            formats.iter().take(settings.keep_formats)
+                .filter_map(|ext| pick_best(all.iter().filter(|f| extension_of(f) == *ext)))
+                .collect()
+        }
+    }
+}
+
+/// Règle: garder le plus lourd, mais si l'un contient des accents et l'autre non, on préfère celui avec accents
+fn pick_best<'a>(files: impl Iterator<Item = &'a FileEntry>) -> Option<&'a FileEntry> {
+    files.reduce(|best, candidate| {
+        let best_has_accents = has_accents(best.path.file_stem().and_then(OsStr::to_str).unwrap_or(""));
+        let cand_has_accents = has_accents(candidate.path.file_stem().and_then(OsStr::to_str).unwrap_or(""));
+
+        let choose_candidate = if best_has_accents && !cand_has_accents {
+            // garder best
+            false
+        } else if !best_has_accents && cand_has_accents {
+            // préférer accents
+            true
+        } else {
+            // Sinon, choisir le plus lourd
+            matches!(best.size.cmp(&candidate.size), Ordering::Less)
+        };
+        if choose_candidate { candidate } else { best }
+    })
+}
+
+/// Une ligne par motif: texte littéral, ou expression régulière après `re:`; `#` commente une ligne.
+fn read_junk_patterns(path: &Path) -> Result<Vec<Regex>> {
+    let text = fs::read_to_string(path).with_context(|| format!("Lecture des motifs {:?}", path))?;
+    let mut patterns = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let pattern = match line.strip_prefix("re:") {
+            Some(re) => re.to_string(),
+            None => regex::escape(line),
+        };
+        let re = Regex::new(&format!("(?i){}", pattern)).with_context(|| format!("Motif invalide ligne {} de {:?}", index + 1, path))?;
+        patterns.push(re);
+    }
+    Ok(patterns)
+}
+
+/// Retire les motifs de --junk; un nom qui serait vidé est gardé tel quel.
+fn strip_junk(stem: &str, settings: &Settings) -> String {
+    let mut cleaned = stem.to_string();
+    for re in &settings.junk {
+        cleaned = re.replace_all(&cleaned, " ").into_owned();
+    }
+    if normalize_basename_for_group(&cleaned).is_empty() { stem.to_string() } else { cleaned }
+}
+
+/// Clé complète d'un nom: marqueurs d'édition mis de côté (--edition-markers), motifs --junk retirés, puis group_key;
+/// les marqueurs reviennent en fin de clé sous leur forme canonique.
+fn title_key(stem: &str, settings: &Settings) -> (String, Option<String>) {
+    let (rest, markers) = if settings.edition_markers { edition::extract(stem) } else { (stem.to_string(), Vec::new()) };
+    let (key, series) = group_key(&strip_junk(&rest, settings), settings);
+    if markers.is_empty() {
+        return (key, series);
+    }
+    let markers = markers.iter().map(|m| format!("({})", m)).collect::<Vec<_>>().join(" ");
+    (if key.is_empty() { markers } else { format!("{} {}", key, markers) }, series)
+}
+
+/// `{n}` ou `{n:02}` dans --series-format.
+const SERIES_NUMBER_TOKEN: &str = r"\{n(?::0(\d))?\}";
+
+/// Clé de groupement d'un nom; avec --series-format, son marqueur de tome est remplacé par la forme choisie
+/// (conservée telle quelle dans la clé) et le texte qui le précède est rendu comme nom de série.
+fn group_key(stem: &str, settings: &Settings) -> (String, Option<String>) {
+    let Some(format) = &settings.series_format else {
+        return (normalize_basename_for_group(stem), None);
+    };
+    let marker_re = Regex::new(r"(?i)(?:^|[^\p{L}\p{N}])(?:tome|vol(?:ume)?|livre|book|t)\.?\s*(\d{1,3})(?:[^\p{L}\p{N}]|$)|#\s*(\d{1,3})(?:[^\p{N}]|$)").unwrap();
+    let Some(caps) = marker_re.captures(stem) else {
+        return (normalize_basename_for_group(stem), None);
+    };
+    let whole = caps.get(0).unwrap();
+    let number: u32 = caps.get(1).or_else(|| caps.get(2)).and_then(|m| m.as_str().parse().ok()).unwrap_or(0);
+    let marker = Regex::new(SERIES_NUMBER_TOKEN).unwrap().replace_all(format, |c: &regex::Captures| {
+        let width = c.get(1).and_then(|w| w.as_str().parse().ok()).unwrap_or(0);
+        format!("{:0width$}", number, width = width)
+    });
+    let series = normalize_basename_for_group(&stem[..whole.start()]);
+    let rest = normalize_basename_for_group(&stem[whole.end()..]);
+    let key = [series.as_str(), &marker, rest.as_str()].iter().filter(|part| !part.is_empty()).copied().collect::<Vec<_>>().join(" ");
+    (key, Some(series).filter(|s| !s.is_empty()))
+}
+
+const DEFAULT_DB: &str = "data/database/openlibrary.sqlite3";
+
+const TEMPLATE_TOKENS: [&str; 5] = ["title", "author", "first", "last", "author_sort"];
+
+fn check_template(template: &str) -> Result<()> {
+    let token_re = Regex::new(r"\{([^{}]*)\}").unwrap();
+    for cap in token_re.captures_iter(template) {
+        if !TEMPLATE_TOKENS.contains(&&cap[1]) {
+            anyhow::bail!("Jeton inconnu {{{}}} dans --template (jetons: {{{}}})", &cap[1], TEMPLATE_TOKENS.join("}, {"));
+        }
+    }
+    Ok(())
+}
+
+/// ("Hugo, Victor") -> ("Victor", "Hugo"); un dossier sans virgule est pris comme nom de famille.
+fn split_author_folder(folder: &str) -> (&str, &str) {
+    match folder.split_once(',') {
+        Some((last, first)) => (first.trim(), last.trim()),
+        None => ("", folder.trim()),
+    }
+}
+
+/// Clé de titre sans le nom de l'auteur en tête ou en fin ("les miserables victor hugo" -> "les miserables"),
+/// pour que le modèle ne le répète pas.
+fn strip_author<'a>(norm: &'a str, author_folder: &str) -> &'a str {
+    let (first, last) = split_author_folder(author_folder);
+    for author in [format!("{} {}", first, last), format!("{} {}", last, first)] {
+        let key = normalize_basename_for_group(&author);
+        if key.is_empty() { continue; }
+        if let Some(title) = norm.strip_suffix(key.as_str()).and_then(|t| t.strip_suffix(' ')) { return title; }
+        if let Some(title) = norm.strip_prefix(key.as_str()).and_then(|t| t.strip_prefix(' ')) { return title; }
+    }
+    norm
+}
+
+/// Remplit le modèle; les espaces en trop, les parenthèses vides et les tirets laissés en bord par un jeton vide sont retirés.
+fn render_template(template: &str, title: &str, author_folder: &str) -> String {
+    let (first, last) = split_author_folder(author_folder);
+    let author = format!("{} {}", first, last);
+    let rendered = template
+        .replace("{title}", title)
+        .replace("{author_sort}", author_folder)
+        .replace("{author}", author.trim())
+        .replace("{first}", first)
+        .replace("{last}", last)
+        .replace(['/', '\\'], "_")
+        .replace("()", "")
+        .replace("[]", "");
+    let compact = rendered.split_whitespace().collect::<Vec<_>>().join(" ");
+    compact.trim_matches(|c: char| c == '-' || c == ' ').to_string()
+}
+
+fn extension_of(entry: &FileEntry) -> String {
+    entry.path.extension().and_then(OsStr::to_str).unwrap_or("").to_ascii_lowercase()
+}
+
+fn discard_file(path: &Path, settings: &Settings, journal: &Mutex<Journal>) -> Result<()> {
+    // --snapshot-keep-deleted: lien gardé avant suppression ou corbeille (la quarantaine garde déjà le fichier)
+    if let (Some(snapshot), Discard::Delete | Discard::Trash) = (&settings.snapshot, &settings.discard) {
+        snapshot.keep(path).with_context(|| format!("Instantané de {:?}", path))?;
+    }
+    match &settings.discard {
+        Discard::Delete => {
+            fs::remove_file(path)?;
+            journal.lock().unwrap().record(JournalOp::DeleteFile, path, None)?;
+        }
+        Discard::Trash => {
+            trash::delete(path).with_context(|| format!("Corbeille {:?}", path))?;
+            journal.lock().unwrap().record(JournalOp::TrashFile, path, None)?;
+        }
+        Discard::Quarantine { root, dir } => {
+            let rel = path.strip_prefix(root).unwrap_or(path);
+            let mut target = dir.join(rel);
+            // Même nom déjà en quarantaine: suffixe numérique
+            let mut index = 1;
+            while target.exists() {
+                let stem = path.file_stem().and_then(OsStr::to_str).unwrap_or("");
+                let name = match path.extension().and_then(OsStr::to_str) {
+                    Some(ext) => format!("{}.{}.{}", stem, index, ext),
+                    None => format!("{}.{}", stem, index),
+                };
+                target.set_file_name(name);
+                index += 1;
+            }
+            if let Some(parent) = target.parent() { fs::create_dir_all(parent)?; }
+            // rename échoue entre deux systèmes de fichiers: copie puis suppression
+            if fs::rename(path, &target).is_err() {
+                fs::copy(path, &target).with_context(|| format!("Copie {:?} -> {:?}", path, target))?;
+                fs::remove_file(path)?;
+            }
+            journal.lock().unwrap().record(JournalOp::QuarantineFile, path, Some(&target))?;
+        }
+    }
+    Ok(())
+}
+
+fn file_size(path: &Path) -> io::Result<u64> {
+    Ok(fs::metadata(path)?.len())
+}
+
+fn has_accents(s: &str) -> bool {
+    // True si s contient des caractères non-ASCII une fois normalisés NFC
+    let nfc = s.nfc().collect::<String>();
+    !nfc.is_ascii()
+}
+
+fn normalize_basename_for_group(name: &str) -> String {
+    // Objectif: normaliser de façon robuste, supprimer ponctuation superflue, espaces multiples, conserver accents pour préférence
+    // mais clé de groupement sans accents pour rapprocher variantes.
+    let tmp = tidy_basename(name);
+
+    // Dé-accentuation pour la clé de groupement
+    let deaccent = unidecode(&tmp);
+
+    // Compacte espaces
+    let compact = deaccent.split_whitespace().collect::<Vec<_>>().join(" ");
+    compact
+}
+
+/// Minuscules, séparateurs et ponctuation retirés, accents conservés.
+fn tidy_basename(name: &str) -> String {
+    let lower = name.trim().to_lowercase();
+
+    // Remplace séparateurs communs par espace
+    let sep_re = Regex::new(r"[\s_\-]+").unwrap();
+    let tmp = sep_re.replace_all(&lower, " ");
+
+    // Supprime ponctuation
+    let punct_re = Regex::new(r"[^\p{L}\p{N} ]+").unwrap();
+    let tmp = punct_re.replace_all(&tmp, "");
+    tmp.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Clé de groupement avec les accents d'un titre `works` de même forme normalisée; None si la base n'en a pas.
+fn accented_title(db: &Connection, key: &str) -> Option<String> {
+    // La base garde les traits d'union ("notre-dame de paris") que la clé remplace par des espaces
+    let pattern = key.split(' ')
+        .map(|word| word.chars().map(|c| if matches!(c, '*' | '?' | '[' | ']') { format!("[{}]", c) } else { c.to_string() }).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("[- ]");
+    let mut stmt = db.prepare_cached("SELECT title FROM works WHERE title_normalized GLOB ?1 LIMIT 5").ok()?;
+    let titles: Vec<String> = stmt.query_map([pattern], |row| row.get(0)).ok()?.filter_map(|title| title.ok()).collect();
+    titles.iter()
+        .map(|title| tidy_basename(title))
+        // Le titre de la base doit redonner exactement la clé une fois dé-accentué
+        .find(|tidy| has_accents(tidy) && normalize_basename_for_group(tidy) == key)
+}
+
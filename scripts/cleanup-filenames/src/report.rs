@@ -0,0 +1,70 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Un changement prévu (dry-run) ou effectué; chemins relatifs au dossier de l'auteur.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanRow {
+    pub author: String,
+    pub old_name: String,
+    /// Vide pour un doublon écarté.
+    pub new_name: String,
+    /// `rename`, `collision` (renommage avec suffixe, nom cible déjà pris), `flatten` (remonté par --flatten),
+    /// `delete`, `trash`, `quarantine` ou `hold` (doublon laissé en place par --min-keep-size / --size-ratio).
+    pub action: &'static str,
+    pub group_key: String,
+    pub size: u64,
+}
+
+fn create_parent(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(())
+}
+
+pub fn write_csv(path: &Path, rows: &[PlanRow]) -> Result<()> {
+    create_parent(path)?;
+    let mut w = csv::Writer::from_path(path).with_context(|| format!("Ouverture du rapport {:?}", path))?;
+    if rows.is_empty() {
+        w.write_record(["author", "old_name", "new_name", "action", "group_key", "size"])?;
+    }
+    for row in rows {
+        w.serialize(row)?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Rapport façon diff unifié, une section par auteur: `-ancien` / `+nouveau` pour un renommage,
+/// `-ancien` seul pour un doublon écarté, ` ancien` pour un doublon laissé en place.
+pub fn write_diff(path: &Path, rows: &[PlanRow]) -> Result<()> {
+    create_parent(path)?;
+    let mut out = fs::File::create(path).with_context(|| format!("Ouverture du rapport {:?}", path))?;
+    let mut current: Option<&str> = None;
+    for row in rows {
+        if current != Some(row.author.as_str()) {
+            if current.is_some() {
+                writeln!(out)?;
+            }
+            writeln!(out, "--- {}/ (avant)", row.author)?;
+            writeln!(out, "+++ {}/ (après)", row.author)?;
+            current = Some(&row.author);
+        }
+        match row.action {
+            "rename" => {
+                writeln!(out, "-{}", row.old_name)?;
+                writeln!(out, "+{}", row.new_name)?;
+            }
+            "collision" | "flatten" => {
+                writeln!(out, "-{}", row.old_name)?;
+                writeln!(out, "+{}    # {}", row.new_name, row.action)?;
+            }
+            "hold" => writeln!(out, " {}    # hold", row.old_name)?,
+            action => writeln!(out, "-{}    # {}", row.old_name, action)?,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Fichier tenu dans chaque dossier renommé: nouveau nom -> nom d'origine.
+pub const SIDECAR_NAME: &str = ".original_names.json";
+
+type Names = BTreeMap<String, String>;
+
+fn read(dir: &Path) -> Result<Names> {
+    let path = dir.join(SIDECAR_NAME);
+    if !path.exists() {
+        return Ok(Names::new());
+    }
+    let body = fs::read_to_string(&path).with_context(|| format!("Lecture de {:?}", path))?;
+    serde_json::from_str(&body).with_context(|| format!("{:?} invalide", path))
+}
+
+fn write(dir: &Path, names: &Names) -> Result<()> {
+    let path = dir.join(SIDECAR_NAME);
+    if names.is_empty() {
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        return Ok(());
+    }
+    fs::write(&path, serde_json::to_string_pretty(names)? + "\n").with_context(|| format!("Écriture de {:?}", path))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+/// Note le renommage `from` -> `to`; un fichier déjà renommé garde son tout premier nom,
+/// et un fichier qui retrouve son nom d'origine (--revert) sort de la liste.
+pub fn record(from: &Path, to: &Path) -> Result<()> {
+    let (Some(from_dir), Some(to_dir)) = (from.parent(), to.parent()) else { return Ok(()); };
+    let mut names = read(from_dir)?;
+    let original = names.remove(&file_name(from)).unwrap_or_else(|| file_name(from));
+    if from_dir != to_dir {
+        write(from_dir, &names)?;
+        names = read(to_dir)?;
+    }
+    if original != file_name(to) {
+        names.insert(file_name(to), original);
+    }
+    write(to_dir, &names)
+}
@@ -0,0 +1,123 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sortbook_common::say;
+use std::fs;
+use std::ops::AddAssign;
+use std::path::Path;
+
+/// Compteurs d'un dossier d'auteur (changements prévus en dry-run).
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct Stats {
+    /// Groupes de titres traités.
+    pub groups: usize,
+    pub renamed: usize,
+    /// Doublons supprimés, mis à la corbeille ou en quarantaine.
+    pub discarded: usize,
+    pub kept: usize,
+    /// Taille cumulée des doublons écartés.
+    #[serde(rename = "reclaimed_bytes")]
+    pub reclaimed: u64,
+}
+
+impl AddAssign for Stats {
+    fn add_assign(&mut self, other: Self) {
+        self.groups += other.groups;
+        self.renamed += other.renamed;
+        self.discarded += other.discarded;
+        self.kept += other.kept;
+        self.reclaimed += other.reclaimed;
+    }
+}
+
+impl Stats {
+    fn changed(&self) -> bool { self.renamed > 0 || self.discarded > 0 }
+}
+
+/// Tableau de fin: une ligne par auteur avec au moins un changement, triée par nom, puis le total.
+pub fn print_table(per_author: &[(String, Stats)], dry_run: bool) {
+    let mut rows: Vec<&(String, Stats)> = per_author.iter().filter(|(_, stats)| stats.changed()).collect();
+    rows.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut total = Stats::default();
+    for (_, stats) in per_author {
+        total += *stats;
+    }
+    let width = rows.iter().map(|(author, _)| author.chars().count()).max().unwrap_or(0).clamp(10, 40);
+
+    say!();
+    say!("Résumé par auteur{}:", if dry_run { " (dry-run, changements prévus)" } else { "" });
+    say!("{:<width$}  {:>8}  {:>8}  {:>8}  {:>12}", "Auteur", "renommés", "écartés", "gardés", "récupéré");
+    for (author, stats) in &rows {
+        let shown: String = author.chars().take(width).collect();
+        say!("{:<width$}  {:>8}  {:>8}  {:>8}  {:>12}", shown, stats.renamed, stats.discarded, stats.kept, human_bytes(stats.reclaimed));
+    }
+    say!("{:<width$}  {:>8}  {:>8}  {:>8}  {:>12}", "TOTAL", total.renamed, total.discarded, total.kept, human_bytes(total.reclaimed));
+    let unchanged = per_author.len() - rows.len();
+    if unchanged > 0 {
+        say!("{} auteur(s) sans changement non listé(s).", unchanged);
+    }
+}
+
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["Ko", "Mo", "Go", "To"];
+    if bytes < 1024 {
+        return format!("{} o", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+#[derive(Serialize)]
+struct AuthorSummary<'a> {
+    author: &'a str,
+    #[serde(flatten)]
+    stats: Stats,
+}
+
+#[derive(Serialize)]
+struct Summary<'a> {
+    root: &'a Path,
+    dry_run: bool,
+    started: String,
+    finished: String,
+    authors: usize,
+    #[serde(flatten)]
+    total: Stats,
+    collisions: usize,
+    per_author: Vec<AuthorSummary<'a>>,
+}
+
+/// summary.json: totaux du lancement et compteurs de chaque auteur (triés par nom).
+pub fn write_summary(
+    path: &Path,
+    root: &Path,
+    dry_run: bool,
+    started: chrono::DateTime<chrono::Local>,
+    collisions: usize,
+    per_author: &[(String, Stats)],
+) -> Result<()> {
+    let mut total = Stats::default();
+    for (_, stats) in per_author {
+        total += *stats;
+    }
+    let mut authors: Vec<AuthorSummary> = per_author.iter().map(|(author, stats)| AuthorSummary { author, stats: *stats }).collect();
+    authors.sort_by(|a, b| a.author.cmp(b.author));
+    let summary = Summary {
+        root,
+        dry_run,
+        started: started.to_rfc3339(),
+        finished: chrono::Local::now().to_rfc3339(),
+        authors: per_author.len(),
+        total,
+        collisions,
+        per_author: authors,
+    };
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(&summary)? + "\n").with_context(|| format!("Écriture du résumé {:?}", path))
+}
@@ -0,0 +1,161 @@
+use anyhow::{Context, Result, anyhow};
+use sortbook_common::say;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::exclude::ExcludeList;
+use crate::report::Normalization;
+use crate::{
+    AuthorEntry, Cli, FsOps, count_files, merge_directories, normalize_author_display,
+    rename_entry_to_db_name, sanitize_component,
+};
+
+/// One row of the reviewed CSV: the folder and the decision typed by the operator.
+struct Decision {
+    folder: String,
+    author_id: String,
+    name: String,
+}
+
+/// Replays the `author_id` / `author_name_db` columns of an authors CSV edited by
+/// hand: folders sharing an author_id are merged, and the remaining folder is renamed
+/// to `author_name_db` when one is given. No matching, threshold, or heuristic runs.
+pub fn apply_csv(cli: &Cli, path: &Path) -> Result<()> {
+    say!(
+        "Applying {:?} {}on {:?}",
+        path,
+        if cli.dry_run { "(dry-run) " } else { "" },
+        cli.roots
+    );
+    let exclude = match &cli.exclude_file {
+        Some(path) => ExcludeList::load(path)?,
+        None => ExcludeList::empty(),
+    };
+    let mut ops = FsOps::from_cli(cli)?;
+    let mut groups: BTreeMap<String, Vec<Decision>> = BTreeMap::new();
+    for decision in read_decisions(path)? {
+        if decision.author_id.is_empty() {
+            continue;
+        }
+        groups
+            .entry(decision.author_id.clone())
+            .or_default()
+            .push(decision);
+    }
+
+    let mut merged = 0usize;
+    let mut normalizations: Vec<Normalization> = Vec::new();
+    for (author_id, decisions) in groups {
+        let mut members = Vec::new();
+        for decision in &decisions {
+            // With several roots, the first one holding the folder wins.
+            let Some(folder) = cli
+                .roots
+                .iter()
+                .map(|root| root.join(&decision.folder))
+                .find(|folder| folder.is_dir())
+            else {
+                say!("Skipping {:?}: folder not found", decision.folder);
+                continue;
+            };
+            if exclude.is_protected(&folder) {
+                say!("Skipping {:?}: protected", decision.folder);
+                continue;
+            }
+            let files = count_files(&folder)?;
+            members.push((decision, folder, files));
+        }
+        if members.is_empty() {
+            continue;
+        }
+        let name = decisions
+            .iter()
+            .map(|d| d.name.as_str())
+            .find(|name| !name.is_empty());
+        let canonical = name.map(|name| sanitize_component(&normalize_author_display(name)));
+        // Keep the folder already carrying the requested name, else the largest one.
+        members.sort_by(|(a, _, a_files), (b, _, b_files)| {
+            (Some(&b.folder) == canonical.as_ref())
+                .cmp(&(Some(&a.folder) == canonical.as_ref()))
+                .then_with(|| b_files.cmp(a_files))
+                .then_with(|| a.folder.cmp(&b.folder))
+        });
+        let destination = members[0].1.clone();
+        if members.len() > 1 {
+            say!(
+                "Merging author_id {} into {}",
+                author_id,
+                destination.display()
+            );
+        }
+        for (_, source, _) in members.iter().skip(1) {
+            say!("  - {} -> {}", source.display(), destination.display());
+            merge_directories(source, &destination, &mut ops)?;
+            merged += 1;
+        }
+        if let Some(name) = name {
+            let mut entry = AuthorEntry {
+                name: members[0].0.folder.clone(),
+                path: destination,
+                author_id: Some(author_id.clone()),
+                author_name_db: Some(name.to_string()),
+                probable: None,
+                alternate_ids: Vec::new(),
+            };
+            rename_entry_to_db_name(
+                &mut entry,
+                name,
+                "from CSV",
+                &exclude,
+                &mut ops,
+                &mut normalizations,
+            )?;
+        }
+    }
+    say!(
+        "Apply done: {} folder(s) merged{}, {} folder(s) renamed.",
+        merged,
+        if cli.dry_run { " (planned)" } else { "" },
+        normalizations.len()
+    );
+    if let Some(path) = ops.journal.written_path() {
+        say!(
+            "Undo journal: {:?} (revert with `cleanup revert --journal <file>`).",
+            path
+        );
+    }
+    ops.journal.finish()?;
+    Ok(())
+}
+
+fn read_decisions(path: &Path) -> Result<Vec<Decision>> {
+    let mut reader =
+        csv::Reader::from_path(path).with_context(|| format!("Read CSV {:?}", path))?;
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header.trim() == name)
+            .ok_or_else(|| anyhow!("{:?}: missing `{}` column", path, name))
+    };
+    let (folder_col, id_col, name_col) = (
+        column("author")?,
+        column("author_id")?,
+        column("author_name_db")?,
+    );
+    let mut decisions = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let field = |index: usize| record.get(index).unwrap_or("").trim().to_string();
+        let folder = field(folder_col);
+        if folder.is_empty() {
+            continue;
+        }
+        decisions.push(Decision {
+            folder,
+            author_id: field(id_col),
+            name: field(name_col),
+        });
+    }
+    Ok(decisions)
+}
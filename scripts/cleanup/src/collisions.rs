@@ -0,0 +1,200 @@
+use anyhow::Result;
+use sortbook_common::say;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use unicode_normalization::{UnicodeNormalization, is_nfc};
+use walkdir::WalkDir;
+
+use crate::exclude::ExcludeList;
+use crate::journal::JournalOp;
+use crate::report::{Normalization, NormalizeAction};
+use crate::{
+    FsOps, count_files, discard_file, file_digest, is_trash_dir, merge_directories, move_file,
+    op_event, rename_with_case_handling, suffixed_copy_path,
+};
+
+/// Names that collide on case-insensitive or normalization-insensitive filesystems
+/// (macOS, Windows) share this key.
+fn fold_key(name: &str) -> String {
+    name.nfc().collect::<String>().to_lowercase()
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Finds author folders that differ only by case or Unicode form. With `fix`, each
+/// group is merged into its NFC-named, largest member and lone NFD names become NFC.
+pub fn resolve_folder_collisions(
+    root: &Path,
+    exclude: &ExcludeList,
+    fix: bool,
+    ops: &mut FsOps,
+) -> Result<Vec<Normalization>> {
+    let mut groups: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for entry in fs::read_dir(root)?.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if !path.is_dir() || is_trash_dir(&path) || exclude.is_protected(&path) {
+            continue;
+        }
+        groups
+            .entry(fold_key(&file_name(&path)))
+            .or_default()
+            .push(path);
+    }
+
+    let mut normalizations = Vec::new();
+    for (_, mut members) in groups {
+        if members.len() < 2 {
+            let path = &members[0];
+            let name = file_name(path);
+            if is_nfc(&name) {
+                continue;
+            }
+            let target = path.with_file_name(name.nfc().collect::<String>());
+            say!(
+                "Non-NFC folder name {:?}{}",
+                name,
+                if fix { "" } else { " (use --fix-collisions)" }
+            );
+            if !fix {
+                continue;
+            }
+            if ops.dry_run {
+                say!(
+                    "[DRY-RUN] rename {} -> {}",
+                    path.display(),
+                    target.display()
+                );
+            } else {
+                rename_with_case_handling(path, &target)?;
+                ops.journal
+                    .record(JournalOp::RenameDir, path, Some(&target))?;
+            }
+            op_event("rename", path, Some(&target), ops.dry_run);
+            normalizations.push(Normalization {
+                from: path.clone(),
+                to: target,
+                action: NormalizeAction::Rename,
+            });
+            continue;
+        }
+
+        let mut ranked = Vec::new();
+        for path in members.drain(..) {
+            let files = count_files(&path)?;
+            ranked.push((path, files));
+        }
+        ranked.sort_by(|(a, a_files), (b, b_files)| {
+            is_nfc(&file_name(b))
+                .cmp(&is_nfc(&file_name(a)))
+                .then_with(|| b_files.cmp(a_files))
+                .then_with(|| a.cmp(b))
+        });
+        let names: Vec<String> = ranked.iter().map(|(path, _)| file_name(path)).collect();
+        say!("Colliding folders (case/Unicode): {:?}", names);
+        if !fix {
+            continue;
+        }
+        let destination = ranked[0].0.clone();
+        for (path, _) in ranked.iter().skip(1) {
+            say!("Merging {} into {}", path.display(), destination.display());
+            merge_directories(path, &destination, ops)?;
+            normalizations.push(Normalization {
+                from: path.clone(),
+                to: destination.clone(),
+                action: NormalizeAction::Merge,
+            });
+        }
+    }
+    Ok(normalizations)
+}
+
+/// Author folders of a root (trash and protected folders left out), sorted.
+pub fn author_folders(root: &Path, exclude: &ExcludeList) -> Result<Vec<PathBuf>> {
+    let mut folders: Vec<PathBuf> = fs::read_dir(root)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir() && !is_trash_dir(path) && !exclude.is_protected(path))
+        .collect();
+    folders.sort();
+    Ok(folders)
+}
+
+/// Within each author folder, finds files whose relative paths differ only by case or
+/// Unicode form. With `fix`, identical copies are discarded and the others get a
+/// `(2)` suffix. Folders in `unchanged` (per `--scan-index`) are not walked; folders
+/// whose collisions are left in place go into `pending`, so they are not recorded as handled.
+/// Returns the number of colliding files found.
+pub fn resolve_file_collisions(
+    root: &Path,
+    exclude: &ExcludeList,
+    fix: bool,
+    unchanged: &HashSet<PathBuf>,
+    pending: &mut HashSet<PathBuf>,
+    ops: &mut FsOps,
+) -> Result<usize> {
+    let mut found = 0usize;
+    for folder in author_folders(root, exclude)? {
+        if unchanged.contains(&folder) {
+            continue;
+        }
+        let mut seen: BTreeMap<String, PathBuf> = BTreeMap::new();
+        let mut files: Vec<PathBuf> = WalkDir::new(&folder)
+            .min_depth(1)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .collect();
+        files.sort();
+        for file in files {
+            let rel = file.strip_prefix(&folder)?.to_string_lossy().to_string();
+            let key = fold_key(&rel);
+            let Some(kept) = seen.get(&key) else {
+                seen.insert(key, file);
+                continue;
+            };
+            found += 1;
+            say!(
+                "Colliding files in {}: {:?} vs {:?}",
+                folder.display(),
+                file_name(kept),
+                file_name(&file)
+            );
+            if !fix {
+                pending.insert(folder.clone());
+                continue;
+            }
+            let identical = fs::metadata(kept)?.len() == fs::metadata(&file)?.len()
+                && file_digest(kept)? == file_digest(&file)?;
+            if ops.dry_run {
+                say!(
+                    "[DRY-RUN] {} {}",
+                    if identical { "discard" } else { "suffix" },
+                    file.display()
+                );
+                op_event(
+                    if identical { "discard" } else { "rename" },
+                    &file,
+                    None,
+                    true,
+                );
+                continue;
+            }
+            if identical {
+                discard_file(&file, ops)?;
+            } else {
+                let target = suffixed_copy_path(kept);
+                move_file(&file, &target)?;
+                ops.journal
+                    .record(JournalOp::MoveFile, &file, Some(&target))?;
+                op_event("rename", &file, Some(&target), false);
+            }
+        }
+    }
+    Ok(found)
+}
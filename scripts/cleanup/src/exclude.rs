@@ -0,0 +1,58 @@
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::fs;
+use std::path::Path;
+
+/// Folder names (or globs) that must never be renamed or merged.
+/// One pattern per line; blank lines and `#` comments are ignored.
+/// Matching is case-insensitive against the folder name only.
+pub struct ExcludeList {
+    set: GlobSet,
+    len: usize,
+}
+
+impl ExcludeList {
+    pub fn empty() -> Self {
+        Self {
+            set: GlobSet::empty(),
+            len: 0,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content =
+            fs::read_to_string(path).with_context(|| format!("Read exclude file {:?}", path))?;
+        let mut builder = GlobSetBuilder::new();
+        let mut len = 0;
+        for line in content.lines() {
+            let pattern = line.trim();
+            if pattern.is_empty() || pattern.starts_with('#') {
+                continue;
+            }
+            let glob = GlobBuilder::new(pattern)
+                .case_insensitive(true)
+                .literal_separator(true)
+                .build()
+                .with_context(|| format!("Invalid exclude pattern {:?}", pattern))?;
+            builder.add(glob);
+            len += 1;
+        }
+        Ok(Self {
+            set: builder.build()?,
+            len,
+        })
+    }
+
+    pub fn pattern_count(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_protected(&self, path: &Path) -> bool {
+        if self.len == 0 {
+            return false;
+        }
+        path.file_name()
+            .map(|name| self.set.is_match(name.to_string_lossy().trim()))
+            .unwrap_or(false)
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::{Context, Result};
+use log::debug;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::{
+    AuthorEntry, CandidateRow, SCORER_KEYS, SUGGESTION_FLOOR, Suggestion, has_trigram_index,
+    normalized_variants, score_candidates,
+};
+
+/// Candidates listed per unmatched folder.
+const EXPLAIN_TOP: usize = 3;
+
+/// Writes the per-metric scores of the best `EXPLAIN_TOP` candidates of every folder
+/// without an exact match, and what the pipeline did with the winner. The same rows
+/// go to the debug log.
+pub fn write_explain(
+    db_path: &Path,
+    path: &Path,
+    authors: &[AuthorEntry],
+    probable_threshold: f64,
+    expand_initials: bool,
+) -> Result<usize> {
+    let connection = Connection::open(db_path)
+        .with_context(|| format!("Impossible d'ouvrir la base {:?}", db_path))?;
+    let trigram = has_trigram_index(&connection);
+    let mut cache: HashMap<String, Vec<CandidateRow>> = HashMap::new();
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(true)
+        .quote_style(csv::QuoteStyle::Necessary)
+        .from_path(path)?;
+    let mut header = vec!["folder", "rank", "author_id", "author_name_db", "avg"];
+    header.extend(SCORER_KEYS);
+    header.push("outcome");
+    writer.write_record(&header)?;
+
+    let mut explained = 0usize;
+    for entry in authors.iter().filter(|entry| entry.author_id.is_none()) {
+        explained += 1;
+        let variants = normalized_variants(&entry.name);
+        let top = top_candidates(score_candidates(
+            &connection,
+            &variants,
+            trigram,
+            expand_initials,
+            &mut cache,
+        )?);
+        if top.is_empty() {
+            debug!("explain {:?}: no candidate", entry.name);
+            let mut row = vec![entry.name.clone(), "0".into()];
+            row.extend(std::iter::repeat_n(String::new(), 3 + SCORER_KEYS.len()));
+            row.push("no_candidate".into());
+            writer.write_record(&row)?;
+            continue;
+        }
+        for (rank, candidate) in top.iter().enumerate() {
+            let outcome = outcome(rank, candidate, probable_threshold);
+            debug!(
+                "explain {:?} #{}: {} {:?} avg={:.3} {:?} -> {}",
+                entry.name,
+                rank + 1,
+                candidate.author_id,
+                candidate.display_name,
+                candidate.avg_score,
+                candidate.per_metric,
+                outcome
+            );
+            let mut row = vec![
+                entry.name.clone(),
+                (rank + 1).to_string(),
+                candidate.author_id.clone(),
+                candidate.display_name.clone(),
+                format!("{:.3}", candidate.avg_score),
+            ];
+            row.extend(SCORER_KEYS.iter().map(|key| {
+                candidate
+                    .per_metric
+                    .get(*key)
+                    .map(|score| format!("{score:.3}"))
+                    .unwrap_or_default()
+            }));
+            row.push(outcome.into());
+            writer.write_record(&row)?;
+        }
+    }
+    writer.flush()?;
+    Ok(explained)
+}
+
+/// Best score per author_id, highest first (earlier candidates win ties, as in
+/// `suggest_author`).
+fn top_candidates(scored: Vec<Suggestion>) -> Vec<Suggestion> {
+    let mut best: Vec<Suggestion> = Vec::new();
+    for suggestion in scored {
+        match best
+            .iter_mut()
+            .find(|kept| kept.author_id == suggestion.author_id)
+        {
+            Some(kept) if suggestion.avg_score > kept.avg_score => *kept = suggestion,
+            Some(_) => {}
+            None => best.push(suggestion),
+        }
+    }
+    best.sort_by(|a, b| b.avg_score.total_cmp(&a.avg_score));
+    best.truncate(EXPLAIN_TOP);
+    best
+}
+
+fn outcome(rank: usize, candidate: &Suggestion, probable_threshold: f64) -> &'static str {
+    if rank > 0 {
+        "runner_up"
+    } else if candidate.avg_score < SUGGESTION_FLOOR {
+        "below_floor"
+    } else if candidate.score() >= probable_threshold {
+        "above_threshold"
+    } else {
+        "below_threshold"
+    }
+}
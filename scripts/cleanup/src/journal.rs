@@ -0,0 +1,203 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sortbook_common::ledger::{Ledger, Op};
+use sortbook_common::say;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_JOURNAL_DIR: &str = "logs";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalOp {
+    RenameDir,
+    MoveFile,
+    TrashFile,
+    DeleteFile,
+    RemoveDir,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub op: JournalOp,
+    pub src: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dst: Option<PathBuf>,
+    pub ts: String,
+}
+
+/// Append-only JSONL record of every filesystem change made by a run.
+/// The file is only created once the first change is recorded.
+/// With `--ledger`, every change is also recorded in the shared SQLite ledger.
+pub struct Journal {
+    path: Option<PathBuf>,
+    file: Option<File>,
+    ledger: Ledger,
+}
+
+impl Journal {
+    pub fn disabled() -> Self {
+        Self {
+            path: None,
+            file: None,
+            ledger: Ledger::disabled(),
+        }
+    }
+
+    pub fn at(path: PathBuf) -> Self {
+        Self {
+            path: Some(path),
+            file: None,
+            ledger: Ledger::disabled(),
+        }
+    }
+
+    pub fn with_ledger(mut self, ledger: Ledger) -> Self {
+        self.ledger = ledger;
+        self
+    }
+
+    /// Closes the ledger run (no-op without `--ledger`).
+    pub fn finish(&mut self) -> Result<()> {
+        self.ledger.finish("ok")?;
+        Ok(())
+    }
+
+    /// Timestamped journal under `logs/` so each run can be reverted on its own.
+    pub fn default_path() -> PathBuf {
+        Path::new(DEFAULT_JOURNAL_DIR).join(format!(
+            "cleanup_journal_{}.jsonl",
+            chrono::Local::now().format("%Y%m%d_%H%M%S")
+        ))
+    }
+
+    /// Path of the journal if at least one change was written to it.
+    pub fn written_path(&self) -> Option<&Path> {
+        self.file.as_ref().and(self.path.as_deref())
+    }
+
+    pub fn record(&mut self, op: JournalOp, src: &Path, dst: Option<&Path>) -> Result<()> {
+        let (kind, detail) = match op {
+            JournalOp::RenameDir => (Op::Move, "rename_dir"),
+            JournalOp::MoveFile => (Op::Move, "move_file"),
+            JournalOp::TrashFile => (Op::Move, "trash_file"),
+            JournalOp::DeleteFile => (Op::Delete, "delete_file"),
+            JournalOp::RemoveDir => (Op::RemoveDir, "remove_dir"),
+        };
+        self.ledger.record(kind, src, dst, detail)?;
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if self.file.is_none() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Open journal {:?}", path))?;
+            self.file = Some(file);
+        }
+        let entry = JournalEntry {
+            op,
+            src: absolute(src),
+            dst: dst.map(absolute),
+            ts: chrono::Utc::now().to_rfc3339(),
+        };
+        let file = self.file.as_mut().expect("journal file opened above");
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Replays a journal backwards, moving every file and folder back to its source.
+pub fn revert(journal_path: &Path, dry_run: bool) -> Result<()> {
+    let file =
+        File::open(journal_path).with_context(|| format!("Open journal {:?}", journal_path))?;
+    let mut entries = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: JournalEntry = serde_json::from_str(&line)
+            .with_context(|| format!("Invalid journal line {}", index + 1))?;
+        entries.push(entry);
+    }
+    say!(
+        "Reverting {} journal entries {}from {:?}",
+        entries.len(),
+        if dry_run { "(dry-run) " } else { "" },
+        journal_path
+    );
+
+    let mut restored = 0usize;
+    let mut skipped = 0usize;
+    for entry in entries.iter().rev() {
+        match entry.op {
+            JournalOp::RenameDir | JournalOp::MoveFile | JournalOp::TrashFile => {
+                let Some(dst) = &entry.dst else {
+                    skipped += 1;
+                    continue;
+                };
+                if !dst.exists() || entry.src.exists() {
+                    say!(
+                        "  skip {} -> {} (target missing or source occupied)",
+                        dst.display(),
+                        entry.src.display()
+                    );
+                    skipped += 1;
+                    continue;
+                }
+                if dry_run {
+                    say!(
+                        "[DRY-RUN] restore {} -> {}",
+                        dst.display(),
+                        entry.src.display()
+                    );
+                    restored += 1;
+                    continue;
+                }
+                if let Some(parent) = entry.src.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                restore_path(dst, &entry.src)?;
+                restored += 1;
+            }
+            JournalOp::DeleteFile => {
+                say!("  cannot restore deleted {}", entry.src.display());
+                skipped += 1;
+            }
+            JournalOp::RemoveDir => {
+                if entry.src.exists() {
+                    skipped += 1;
+                    continue;
+                }
+                if dry_run {
+                    say!("[DRY-RUN] recreate {}", entry.src.display());
+                } else {
+                    fs::create_dir_all(&entry.src)?;
+                }
+                restored += 1;
+            }
+        }
+    }
+    say!("Revert done: {restored} restored, {skipped} skipped.");
+    Ok(())
+}
+
+fn restore_path(from: &Path, to: &Path) -> Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to).with_context(|| format!("Restore {:?} -> {:?}", from, to))?;
+    fs::remove_file(from).ok();
+    Ok(())
+}
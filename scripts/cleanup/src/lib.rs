@@ -0,0 +1,2683 @@
+mod apply;
+mod collisions;
+mod exclude;
+mod explain;
+mod journal;
+mod library;
+mod lock;
+mod logging;
+mod match_cache;
+mod overrides;
+mod progress;
+mod prune;
+mod report;
+mod shelf;
+mod split;
+mod stats;
+mod verify;
+
+use anyhow::{Context, Result, anyhow};
+use author_alias_online::cache::ResponseCache;
+use author_alias_online::http::{CachedHttp, ClientOptions, RequestPolicy};
+use author_alias_online::wikidata::{self, BlockingWikidata, WikidataClient};
+use clap::{Parser, Subcommand, ValueEnum};
+use exclude::ExcludeList;
+use indicatif::ProgressIterator;
+use journal::{Journal, JournalOp};
+use log::{debug, error, info};
+use match_cache::{CachedMatch, MatchCache};
+use once_cell::sync::Lazy;
+use overrides::Overrides;
+use regex::Regex;
+use report::{MergeGroup, MergeMember, Normalization, NormalizeAction, ReportInput};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sortbook_common::identity;
+use sortbook_common::ledger::Ledger;
+use sortbook_common::output::{self, Event, OutputFormat};
+use sortbook_common::sanitize::sanitize_component;
+use sortbook_common::say;
+use sortbook_common::scan::ScanIndex;
+use sortbook_common::snapshot::Snapshot;
+use stats::MergeStats;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+use walkdir::WalkDir;
+
+pub use library::read_library_authors;
+
+const DEFAULT_ROOT: &str = "output/sorted_books";
+const DEFAULT_DB: &str = "data/database/openlibrary.sqlite3";
+const DEFAULT_CSV: &str = "data/authors.csv";
+const DEFAULT_REVIEW_CSV: &str = "data/authors_review.csv";
+const PROBABLE_MIN_SCORE: f64 = 0.90;
+const NEIGHBOR_LIMIT: i64 = 25;
+const TRIGRAM_TABLE: &str = "authors_trigram";
+const TRIGRAM_LIMIT: i64 = 50;
+const TRASH_DIR_NAME: &str = ".cleanup_trash";
+/// Days before a response in --wikidata-cache is fetched again (the author-alias-online default).
+const WIKIDATA_CACHE_TTL_DAYS: u32 = 30;
+
+/// Name particles kept with the given names in "Last, First" form
+/// (`Maupassant, Guy De`), whatever order the folder name uses.
+const NAME_PARTICLES: &[&str] = &["de", "du", "des", "van", "von", "der", "den", "le", "la"];
+
+/// Average score below which no suggestion is made at all.
+const SUGGESTION_FLOOR: f64 = 0.65;
+/// Average score that stops trying further name variants.
+const GOOD_ENOUGH_SCORE: f64 = 0.85;
+
+const SCORER_KEYS: [&str; 6] = ["seq", "token", "prefix", "suffix", "ngram", "lenratio"];
+
+static BRACKET_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[[^\]]+\]").unwrap());
+static PAREN_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\([^\)]+\)").unwrap());
+
+#[derive(Parser, Debug)]
+#[command(name = "cleanup", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Root directory containing author folders. Repeat to match and merge across
+    /// several trees (e.g. sorted_books and fail_author); the first is the main library.
+    #[arg(long = "root", default_value = DEFAULT_ROOT)]
+    roots: Vec<PathBuf>,
+
+    /// OpenLibrary SQLite database path.
+    #[arg(long, default_value = DEFAULT_DB)]
+    db: PathBuf,
+
+    /// Destination CSV (matches the historical sample layout).
+    #[arg(long = "csv", default_value = DEFAULT_CSV)]
+    csv_path: PathBuf,
+
+    /// Append file_count, total_bytes and max_depth columns to the CSV (library inventory).
+    #[arg(long)]
+    inventory: bool,
+
+    /// Minimum number of files required before merging a folder into an author_id group.
+    #[arg(long, default_value_t = 0)]
+    min_files: usize,
+
+    /// Threshold used to accept a probable author_id suggestion.
+    #[arg(long, default_value_t = PROBABLE_MIN_SCORE)]
+    probable_threshold: f64,
+
+    /// Lower tier: suggestions scoring between this and --probable-threshold are not
+    /// merged but listed in --review-csv for a human decision.
+    #[arg(long)]
+    review_threshold: Option<f64>,
+
+    /// "Needs review" CSV written when --review-threshold is set.
+    #[arg(long, default_value = DEFAULT_REVIEW_CSV)]
+    review_csv: PathBuf,
+
+    /// Probable-only merge groups scoring below this are confirmed against Wikidata
+    /// before merging (unset = never query Wikidata).
+    #[arg(long)]
+    verify_threshold: Option<f64>,
+
+    /// HTTP timeout in seconds for --verify-threshold and --group-by lookups.
+    #[arg(long, default_value_t = 5)]
+    verify_timeout: u64,
+
+    /// Contact (email or URL) sent in the user agent of Wikidata lookups, as Wikimedia asks of API clients.
+    #[arg(long)]
+    user_agent_contact: Option<String>,
+
+    /// Wikimedia OAuth 2 access token for higher rate limits.
+    #[arg(long, env = "WIKIMEDIA_API_TOKEN", hide_env_values = true)]
+    api_token: Option<String>,
+
+    /// HTTP(S) proxy for Wikidata lookups (default: HTTP_PROXY/HTTPS_PROXY).
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// SQLite file caching Wikidata responses across runs (the author-alias-online `--cache`).
+    #[arg(long)]
+    wikidata_cache: Option<PathBuf>,
+
+    /// Max Wikidata requests per second (0 = unlimited).
+    #[arg(long, default_value_t = 0.0)]
+    wikidata_rate: f64,
+
+    /// Retries of a Wikidata request after a network error, HTTP 429 or 5xx (exponential backoff).
+    #[arg(long, default_value_t = 3)]
+    wikidata_retries: u32,
+
+    /// Dry-run mode: log actions without touching the filesystem.
+    #[arg(long)]
+    dry_run: bool,
+
+    /// File listing folder names or globs (one per line) that are never renamed or merged.
+    #[arg(long)]
+    exclude_file: Option<PathBuf>,
+
+    /// How to resolve a file that already exists in the merge target:
+    /// `size` keeps the larger one, `hash` dedupes identical content and keeps
+    /// differing content side by side as a suffixed copy.
+    #[arg(long, value_enum, default_value_t = CompareMode::Size)]
+    compare: CompareMode,
+
+    /// Which copy wins a same-name conflict with `--compare size`: `larger`,
+    /// `newer` (modification time), or `format` (see --format-priority).
+    #[arg(long, value_enum, default_value_t = KeepPolicy::Larger)]
+    keep: KeepPolicy,
+
+    /// Extensions from best to worst for `--keep format`; a book whose title exists
+    /// in a better format in the target folder is dropped.
+    #[arg(long, value_delimiter = ',', default_value = "epub,azw3,mobi,pdf")]
+    format_priority: Vec<String>,
+
+    /// Merge folders (and rename files) whose names differ only by case or Unicode
+    /// normalization (NFC/NFD); without it such collisions are only reported.
+    #[arg(long)]
+    fix_collisions: bool,
+
+    /// Rename folders with an exact author_id match to the DB name ("Last, First").
+    #[arg(long)]
+    rename_to_db_name: bool,
+
+    /// Let initials match full given names ("Tolkien, J.R.R." ~ "John Ronald Reuel
+    /// Tolkien") and rename such folders to the DB name once the match is accepted.
+    #[arg(long)]
+    expand_initials: bool,
+
+    /// Build the FTS5 trigram index of author names in the database (`authors_trigram`) when the
+    /// import script could not; without it candidates come from the neighbor window.
+    #[arg(long)]
+    build_trigram_index: bool,
+
+    /// Also merge folders whose author_ids are linked through `authors.alternate_id`
+    /// (pseudonyms or duplicate OpenLibrary records).
+    #[arg(long)]
+    merge_alternates: bool,
+
+    /// Move replaced/duplicate files to a trash folder instead of deleting them.
+    #[arg(long)]
+    trash: bool,
+
+    /// Trash folder used with --trash (default: <root>/.cleanup_trash).
+    #[arg(long)]
+    trash_dir: Option<PathBuf>,
+
+    /// Before a live run (or `prune`), record the layout of the roots in a new folder under this
+    /// directory (e.g. data/snapshots), restorable with `sortbook restore <snapshot>`.
+    #[arg(long)]
+    snapshot: Option<PathBuf>,
+
+    /// With --snapshot, also keep a hard link (a copy across file systems) of every file the run
+    /// deletes or trashes, so `sortbook restore` can bring it back.
+    #[arg(long, requires = "snapshot")]
+    snapshot_keep_deleted: bool,
+
+    /// CSV of operator corrections (`folder,author_id,canonical_name`) applied before heuristics.
+    #[arg(long)]
+    overrides: Option<PathBuf>,
+
+    /// Record each folder's author_id in the `library_authors` table of --db
+    /// (skipped in dry-run) so the sorter can reuse existing folders.
+    #[arg(long)]
+    library_authors: bool,
+
+    /// Match folders recorded in the `author_identity` table of --db (written by
+    /// `author-alias-online --write-identity`) to their identity's OpenLibrary author_id, like an override.
+    #[arg(long)]
+    identity: bool,
+
+    /// SQLite cache of name matches reused across runs (cleared when the DB file changes).
+    #[arg(long)]
+    match_cache: Option<PathBuf>,
+
+    /// Shared scan index (e.g. data/scan_index.sqlite3): author folders whose files are unchanged
+    /// since the last non dry-run are not walked again for file collisions.
+    #[arg(long)]
+    scan_index: Option<PathBuf>,
+
+    /// Undo journal (JSONL) recording every rename and file move.
+    /// Default: logs/cleanup_journal_<timestamp>.jsonl.
+    #[arg(long)]
+    journal: Option<PathBuf>,
+
+    /// Shared SQLite ledger (e.g. data/sortbook_state.sqlite3) also recording every change of a
+    /// non dry-run, as a run of tool `cleanup`; see `sortbook ledger`.
+    #[arg(long)]
+    ledger: Option<PathBuf>,
+
+    /// Match file titles against `works` and write a CSV plan for splitting folders
+    /// that hold books of several authors (nothing is moved).
+    #[arg(long)]
+    split_plan: Option<PathBuf>,
+
+    /// Optional JSON report of normalizations, matches, suggestions, and merges.
+    #[arg(long)]
+    report: Option<PathBuf>,
+
+    /// Execute only the merges/renames described by an edited authors CSV
+    /// (`author_id` groups folders, `author_name_db` names the result).
+    #[arg(long)]
+    apply_csv: Option<PathBuf>,
+
+    /// Change nothing; report folders whose name drifted from their recorded or
+    /// matched DB author, with suggested corrections.
+    #[arg(long)]
+    verify: bool,
+
+    /// HTML preview of the planned merges (dry-run only).
+    #[arg(long)]
+    html_plan: Option<PathBuf>,
+
+    /// Execute at most this many author_id merge groups (most confident first).
+    #[arg(long)]
+    max_merges: Option<usize>,
+
+    /// Stop merging before the author_id merges would move more than this many files.
+    #[arg(long)]
+    max_files_moved: Option<usize>,
+
+    /// CSV with the per-metric scores of the top 3 candidates of every folder
+    /// without an exact match (also logged with --debug).
+    #[arg(long)]
+    explain: Option<PathBuf>,
+
+    /// Add a `group` column (author's language or country, from the `author_groups`
+    /// table or Wikidata) to the authors CSV.
+    #[arg(long, value_enum)]
+    group_by: Option<shelf::GroupBy>,
+
+    /// With --group-by, link every author folder as `<dir>/<group>/<folder>`
+    /// (symlinks; the library is not moved). Skipped in dry-run.
+    #[arg(long, requires = "group_by")]
+    shelve_dir: Option<PathBuf>,
+
+    /// Do not take the `.sortbook.lock` lock in the roots (shared with the sorter).
+    #[arg(long)]
+    no_lock: bool,
+
+    /// Write debug logs (match scores, merge decisions) to logs/cleanup.log
+    #[arg(long)]
+    debug: bool,
+
+    /// Explicit log file path (enables file logging at info level, debug with --debug)
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+
+    /// Output: text, or json for one JSON event per folder decision on stdout (messages then go to stderr)
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Restore the layout recorded in an undo journal.
+    Revert {
+        /// Journal written by a previous (non dry-run) cleanup run.
+        #[arg(long)]
+        journal: PathBuf,
+
+        /// Only print the restorations that would be performed.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Remove empty author folders under --root and list orphan files in the root.
+    Prune {
+        /// Only print the folders that would be removed.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum CompareMode {
+    Size,
+    Hash,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum KeepPolicy {
+    Larger,
+    Newer,
+    Format,
+}
+
+/// Filesystem side of a run: dry-run switch, undo journal, and optional trash.
+struct FsOps {
+    dry_run: bool,
+    compare: CompareMode,
+    keep: KeepPolicy,
+    /// Lowercase extensions, best first (`--keep format`).
+    format_priority: Vec<String>,
+    journal: Journal,
+    trash: Option<Trash>,
+    /// Layout taken before the run (`--snapshot`), keeping deleted files.
+    snapshot: Option<Snapshot>,
+    /// Filled by the dry-run merge simulation.
+    stats: MergeStats,
+}
+
+/// Per-run trash folder (`<trash_dir>/<timestamp>/`) mirroring paths relative to the root.
+struct Trash {
+    roots: Vec<PathBuf>,
+    dir: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+struct AuthorEntry {
+    name: String,
+    path: PathBuf,
+    author_id: Option<String>,
+    author_name_db: Option<String>,
+    probable: Option<Suggestion>,
+    /// `authors.alternate_id` of the matched (or suggested) author.
+    alternate_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Suggestion {
+    author_id: String,
+    display_name: String,
+    avg_score: f64,
+    seq_score: Option<f64>,
+    per_metric: BTreeMap<String, f64>,
+}
+
+impl Suggestion {
+    /// Score compared with `--probable-threshold`: the sequence ratio when computed, else the average.
+    fn score(&self) -> f64 {
+        self.seq_score.unwrap_or(self.avg_score)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CandidateRow {
+    author_id: String,
+    name: String,
+    name_normalized: String,
+}
+
+/// Runs the tool on a command line (`args[0]` is the program name), as the `cleanup` binary
+/// and `sortbook cleanup` do.
+pub fn run_from_args<I, T>(args: I) -> Result<()>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString> + Clone,
+{
+    let cli: Cli =
+        sortbook_common::config::parse("cleanup", args.into_iter().map(Into::into).collect())?;
+    output::init(cli.output);
+    logging::init(cli.debug, cli.log_file.as_deref());
+    let result = dispatch(cli);
+    if let Err(err) = &result {
+        error!("{err:#}");
+    }
+    result
+}
+
+fn dispatch(cli: Cli) -> Result<()> {
+    if let Some(Command::Revert { journal, dry_run }) = &cli.command {
+        return journal::revert(journal, *dry_run);
+    }
+    if let Some(missing) = cli.roots.iter().find(|root| !root.exists()) {
+        return Err(anyhow!("Root directory {:?} does not exist.", missing));
+    }
+    let writes = match &cli.command {
+        Some(Command::Prune { dry_run }) => !dry_run,
+        _ => !cli.dry_run && !cli.verify,
+    };
+    // Held until the run ends so a concurrent cleanup or sortbook run fails fast.
+    let _locks = if writes && !cli.no_lock {
+        cli.roots
+            .iter()
+            .map(|root| lock::RunLock::acquire(root, "cleanup"))
+            .collect::<Result<Vec<_>>>()?
+    } else {
+        Vec::new()
+    };
+    if let Some(Command::Prune { dry_run }) = &cli.command {
+        let exclude = match &cli.exclude_file {
+            Some(path) => ExcludeList::load(path)?,
+            None => ExcludeList::empty(),
+        };
+        let mut journal = if *dry_run {
+            Journal::disabled()
+        } else {
+            Journal::at(cli.journal.clone().unwrap_or_else(Journal::default_path))
+                .with_ledger(Ledger::for_run(cli.ledger.as_deref(), "cleanup", false)?)
+        };
+        let _snapshot = if *dry_run { None } else { take_snapshot(&cli)? };
+        prune::prune(cli.root(), &exclude, &mut journal, *dry_run)?;
+        journal.finish()?;
+        if let Some(path) = journal.written_path() {
+            say!("Undo journal: {:?}.", path);
+        }
+        return Ok(());
+    }
+    if let Some(path) = &cli.apply_csv {
+        return apply::apply_csv(&cli, path);
+    }
+    if cli.verify {
+        verify::verify(&cli)?;
+        return Ok(());
+    }
+    run(cli)
+}
+
+impl FsOps {
+    fn from_cli(cli: &Cli) -> Result<Self> {
+        Ok(FsOps {
+            dry_run: cli.dry_run,
+            compare: cli.compare,
+            keep: cli.keep,
+            format_priority: cli
+                .format_priority
+                .iter()
+                .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+                .collect(),
+            journal: if cli.dry_run {
+                Journal::disabled()
+            } else {
+                Journal::at(cli.journal.clone().unwrap_or_else(Journal::default_path))
+                    .with_ledger(Ledger::for_run(cli.ledger.as_deref(), "cleanup", false)?)
+            },
+            trash: cli.trash.then(|| Trash {
+                roots: cli.roots.clone(),
+                dir: cli
+                    .trash_dir
+                    .clone()
+                    .unwrap_or_else(|| cli.root().join(TRASH_DIR_NAME))
+                    .join(chrono::Local::now().format("%Y%m%d_%H%M%S").to_string()),
+            }),
+            snapshot: if cli.dry_run {
+                None
+            } else {
+                take_snapshot(cli)?
+            },
+            stats: MergeStats::default(),
+        })
+    }
+}
+
+/// `--snapshot`: records the layout of the roots before the first change.
+fn take_snapshot(cli: &Cli) -> Result<Option<Snapshot>> {
+    let Some(base) = &cli.snapshot else {
+        return Ok(None);
+    };
+    let (snapshot, files) = Snapshot::take(base, "cleanup", &cli.roots, cli.snapshot_keep_deleted)
+        .map_err(|e| anyhow!("Snapshot under {:?}: {e}", base))?;
+    say!(
+        "Snapshot of {} file(s): {:?} (restore with `sortbook restore {}`).",
+        files,
+        snapshot.dir(),
+        snapshot.dir().display()
+    );
+    Ok(Some(snapshot))
+}
+
+impl Cli {
+    /// Main library: the first `--root`, where folders from the other roots are folded.
+    fn root(&self) -> &Path {
+        &self.roots[0]
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    say!(
+        "Running cleanup {}on {:?}",
+        if cli.dry_run { "(dry-run) " } else { "" },
+        cli.roots
+    );
+    let mut ops = FsOps::from_cli(&cli)?;
+    let exclude = match &cli.exclude_file {
+        Some(path) => {
+            let list = ExcludeList::load(path)?;
+            say!("Protecting {} excluded pattern(s).", list.pattern_count());
+            list
+        }
+        None => ExcludeList::empty(),
+    };
+    let overrides = match &cli.overrides {
+        Some(path) => {
+            let loaded = Overrides::load(path)?;
+            say!("Loaded {} override(s).", loaded.rows());
+            loaded
+        }
+        None => Overrides::default(),
+    };
+    // Scan index: author folders untouched since the last run, before this run changes them
+    let scope = if cli.fix_collisions {
+        "cleanup:fix-collisions"
+    } else {
+        "cleanup"
+    };
+    let mut scan = match &cli.scan_index {
+        Some(path) => Some(ScanIndex::open(path, scope)?),
+        None => None,
+    };
+    let mut unchanged = HashSet::new();
+    if let Some(scan) = &scan {
+        for root in &cli.roots {
+            for folder in collisions::author_folders(root, &exclude)? {
+                if !scan.folder_changed(&folder)? {
+                    unchanged.insert(folder);
+                }
+            }
+        }
+        say!(
+            "Scan index: {} unchanged author folder(s).",
+            unchanged.len()
+        );
+    }
+    let mut normalizations = Vec::new();
+    let mut authors = Vec::new();
+    for root in &cli.roots {
+        normalizations.extend(collisions::resolve_folder_collisions(
+            root,
+            &exclude,
+            cli.fix_collisions,
+            &mut ops,
+        )?);
+        normalizations.extend(normalize_directories(root, &exclude, &overrides, &mut ops)?);
+        authors.extend(collect_author_dirs(root)?);
+    }
+    if authors.is_empty() {
+        say!("No author directories detected, aborting.");
+        return Ok(());
+    }
+    let mut match_cache = match &cli.match_cache {
+        Some(path) => MatchCache::open(path, &cli.db)?,
+        None => MatchCache::disabled(),
+    };
+    if cli.build_trigram_index {
+        build_trigram_index(&cli.db)?;
+    }
+    match_and_fill(
+        &cli.db,
+        &mut authors,
+        &overrides,
+        &mut match_cache,
+        cli.expand_initials,
+        cli.identity,
+    )?;
+    if cli.match_cache.is_some() {
+        say!(
+            "Match cache: {} of {} folder(s) reused.",
+            match_cache.hits(),
+            authors.len()
+        );
+    }
+    if let Some(path) = &cli.explain {
+        let explained = explain::write_explain(
+            &cli.db,
+            path,
+            &authors,
+            cli.probable_threshold,
+            cli.expand_initials,
+        )?;
+        say!(
+            "Scores of {} unmatched folder(s) explained in {:?}.",
+            explained,
+            path
+        );
+    }
+    if cli.expand_initials {
+        rename_expanded_initials(&cli, &mut authors, &exclude, &mut ops, &mut normalizations)?;
+    }
+    if cli.rename_to_db_name {
+        rename_exact_to_db_names(&mut authors, &exclude, &mut ops, &mut normalizations)?;
+    }
+    match_events(&authors, cli.probable_threshold);
+    let online = if cli.verify_threshold.is_some() || cli.group_by.is_some() {
+        Some(wikidata_client(&cli)?)
+    } else {
+        None
+    };
+    let groups = match (cli.group_by, &online) {
+        (Some(group_by), Some(online)) => Some(shelf::resolve_groups(
+            &cli.db,
+            &authors,
+            group_by,
+            cli.probable_threshold,
+            online,
+        )?),
+        _ => None,
+    };
+    write_authors_csv(&cli.csv_path, &authors, cli.inventory, groups.as_ref())?;
+    if let Some(review_threshold) = cli.review_threshold {
+        let count = write_review_csv(
+            &cli.review_csv,
+            &authors,
+            review_threshold,
+            cli.probable_threshold,
+        )?;
+        say!(
+            "{} suggestion(s) need review, listed in {:?}.",
+            count,
+            cli.review_csv
+        );
+    }
+    let merges = merge_by_author_id(&cli, &authors, &exclude, online.as_ref(), &mut ops)?;
+    if cli.roots.len() > 1 {
+        fold_into_main_root(
+            &cli,
+            &mut authors,
+            &merges,
+            &exclude,
+            &mut ops,
+            &mut normalizations,
+        )?;
+    }
+    let mut file_collisions = 0;
+    let mut pending = HashSet::new();
+    for root in &cli.roots {
+        file_collisions += collisions::resolve_file_collisions(
+            root,
+            &exclude,
+            cli.fix_collisions,
+            &unchanged,
+            &mut pending,
+            &mut ops,
+        )?;
+    }
+    if file_collisions > 0 && !cli.fix_collisions {
+        say!(
+            "{} file name collision(s) found; rerun with --fix-collisions to resolve them.",
+            file_collisions
+        );
+    }
+    if cli.library_authors {
+        if cli.dry_run {
+            say!("--library-authors is not written in dry-run; skipped.");
+        } else {
+            let written = library::write_library_authors(
+                &cli.db,
+                cli.root(),
+                &authors,
+                cli.probable_threshold,
+            )?;
+            say!(
+                "Recorded {} folder(s) in {}.",
+                written,
+                library::LIBRARY_TABLE
+            );
+        }
+    }
+    if let (Some(dir), Some(groups)) = (&cli.shelve_dir, &groups) {
+        if cli.dry_run {
+            say!("--shelve-dir is not written in dry-run; skipped.");
+        } else {
+            let linked = shelf::write_shelves(dir, &authors, groups)?;
+            say!("Linked {} author folder(s) under {:?}.", linked, dir);
+        }
+    }
+    if let Some(path) = &cli.split_plan {
+        let mixed = split::write_split_plan(&cli.db, path, &authors, cli.probable_threshold)?;
+        say!(
+            "{} mixed-author folder(s); split plan written to {:?}.",
+            mixed,
+            path
+        );
+    }
+    if let Some(scan) = &mut scan
+        && !cli.dry_run
+    {
+        for root in &cli.roots {
+            // Folders with collisions left in place stay unrecorded, so the next run reports them again
+            for folder in collisions::author_folders(root, &exclude)? {
+                if !pending.contains(&folder) {
+                    scan.record_folder(&folder)?;
+                }
+            }
+        }
+        scan.save().map_err(|e| anyhow!("Scan index: {e}"))?;
+        say!("Scan index: {} file(s) recorded.", scan.len());
+    }
+    if let Some(snapshot) = &ops.snapshot
+        && snapshot.kept() > 0
+    {
+        say!("Snapshot: {} deleted file(s) kept.", snapshot.kept());
+    }
+    say!("Done. CSV written to {:?}.", cli.csv_path);
+    if cli.dry_run {
+        stats::print_summary(
+            &ops.stats,
+            &stats::score_histogram(&authors),
+            cli.probable_threshold,
+        );
+    }
+    let report_input = ReportInput {
+        root: cli.root(),
+        dry_run: cli.dry_run,
+        probable_threshold: cli.probable_threshold,
+        normalizations: &normalizations,
+        authors: &authors,
+        merges: &merges,
+        simulation: cli.dry_run.then_some(&ops.stats),
+    };
+    if let Some(path) = &cli.report {
+        report::write_json_report(path, &report_input)?;
+        say!("JSON report written to {:?}.", path);
+    }
+    if let Some(path) = &cli.html_plan {
+        if cli.dry_run {
+            report::write_html_plan(path, &report_input)?;
+            say!("HTML merge plan written to {:?}.", path);
+        } else {
+            say!("--html-plan is only written in dry-run; skipped.");
+        }
+    }
+    if let Some(path) = ops.journal.written_path() {
+        say!(
+            "Undo journal: {:?} (revert with `cleanup revert --journal <file>`).",
+            path
+        );
+    }
+    ops.journal.finish()?;
+    Ok(())
+}
+
+fn normalize_directories(
+    root: &Path,
+    exclude: &ExcludeList,
+    overrides: &Overrides,
+    ops: &mut FsOps,
+) -> Result<Vec<Normalization>> {
+    let mut normalizations = Vec::new();
+    let mut entries: Vec<PathBuf> = fs::read_dir(root)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false))
+        .map(|entry| entry.path())
+        .collect();
+    entries.sort();
+
+    let pb = progress::phase_bar("normalize", entries.len());
+    for original in entries.into_iter().progress_with(pb.clone()) {
+        if !original.exists() || is_trash_dir(&original) || exclude.is_protected(&original) {
+            continue;
+        }
+        let name = original
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let normalized_display = match overrides.get(&name).and_then(|o| o.canonical_name.clone()) {
+            Some(canonical) => canonical,
+            None => normalize_author_display(&name),
+        };
+        let sanitized = sanitize_component(&normalized_display);
+        if sanitized.is_empty() {
+            continue;
+        }
+        let target = root.join(&sanitized);
+        if same_path(&original, &target) {
+            continue;
+        }
+        if exclude.is_protected(&target) {
+            say!(
+                "Skipping {}: target {} is protected",
+                original.display(),
+                target.display()
+            );
+            continue;
+        }
+        if target.exists() {
+            say!("Merging {} into {}", original.display(), target.display());
+            info!("normalize: merge {:?} into {:?}", original, target);
+            merge_directories(&original, &target, ops)?;
+            normalizations.push(Normalization {
+                from: original,
+                to: target,
+                action: NormalizeAction::Merge,
+            });
+            continue;
+        }
+        if ops.dry_run {
+            say!(
+                "[DRY-RUN] rename {} -> {}",
+                original.display(),
+                target.display()
+            );
+        } else {
+            rename_with_case_handling(&original, &target)?;
+            ops.journal
+                .record(JournalOp::RenameDir, &original, Some(&target))?;
+        }
+        info!("normalize: rename {:?} -> {:?}", original, target);
+        op_event("rename", &original, Some(&target), ops.dry_run);
+        normalizations.push(Normalization {
+            from: original,
+            to: target,
+            action: NormalizeAction::Rename,
+        });
+    }
+    pb.finish_and_clear();
+    Ok(normalizations)
+}
+
+fn collect_author_dirs(root: &Path) -> Result<Vec<AuthorEntry>> {
+    let mut authors = Vec::new();
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().trim().to_string();
+        if name.is_empty() || name == TRASH_DIR_NAME {
+            continue;
+        }
+        authors.push(AuthorEntry {
+            name,
+            path: entry.path(),
+            author_id: None,
+            author_name_db: None,
+            probable: None,
+            alternate_ids: Vec::new(),
+        });
+    }
+    authors.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(authors)
+}
+
+fn match_and_fill(
+    db_path: &Path,
+    authors: &mut [AuthorEntry],
+    overrides: &Overrides,
+    match_cache: &mut MatchCache,
+    expand_initials: bool,
+    use_identity: bool,
+) -> Result<()> {
+    let mut cache: HashMap<String, Option<(String, String)>> = HashMap::new();
+    let mut candidate_cache: HashMap<String, Vec<CandidateRow>> = HashMap::new();
+    let connection = Connection::open(db_path)
+        .with_context(|| format!("Impossible d'ouvrir la base {:?}", db_path))?;
+    let trigram = has_trigram_index(&connection);
+    let use_identity = use_identity && identity::table_exists(&connection)?;
+
+    let pb = progress::phase_bar("match", authors.len());
+    for entry in authors.iter_mut().progress_with(pb.clone()) {
+        if let Some(author_id) = overrides.get(&entry.name).and_then(|o| o.author_id.clone()) {
+            let db_name: Option<String> = connection
+                .query_row(
+                    "SELECT name FROM authors WHERE author_id = ?1 LIMIT 1",
+                    [&author_id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            entry.author_name_db = db_name.or_else(|| {
+                overrides
+                    .get(&entry.name)
+                    .and_then(|o| o.canonical_name.clone())
+            });
+            entry.author_id = Some(author_id);
+            continue;
+        }
+        if use_identity && let Some(author_id) = identity_author_id(&connection, &entry.name)? {
+            let db_name: Option<String> = connection
+                .query_row(
+                    "SELECT name FROM authors WHERE author_id = ?1 LIMIT 1",
+                    [&author_id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            debug!("match {:?}: identity {}", entry.name, author_id);
+            entry.author_name_db = db_name;
+            entry.author_id = Some(author_id);
+            continue;
+        }
+        // Initials expansion changes the suggestions, so it gets its own cache entries.
+        let name_key = if expand_initials {
+            format!("{} +initials", normalize_name(&entry.name))
+        } else {
+            normalize_name(&entry.name)
+        };
+        if let Some(cached) = match_cache.get(&name_key)? {
+            entry.author_id = cached.author_id;
+            entry.author_name_db = cached.author_name_db;
+            entry.probable = cached.probable;
+            continue;
+        }
+        let variants = normalized_variants(&entry.name);
+        let mut matched = None;
+        for variant in &variants {
+            if let Some(cached) = cache.get(variant) {
+                if let Some(hit) = cached {
+                    matched = Some(hit.clone());
+                    break;
+                }
+                continue;
+            }
+            let result: Option<(String, String)> = connection
+                .query_row(
+                    "SELECT author_id, name FROM authors WHERE name_normalized = ?1 LIMIT 1",
+                    [variant],
+                    |row| {
+                        let id: String = row.get(0)?;
+                        let name: String = row.get(1)?;
+                        Ok((id, name))
+                    },
+                )
+                .optional()?;
+            cache.insert(variant.clone(), result.clone());
+            if let Some(hit) = result {
+                matched = Some(hit);
+                break;
+            }
+        }
+
+        if let Some((id, db_name)) = matched {
+            debug!("match {:?}: exact {} ({})", entry.name, id, db_name);
+            entry.author_id = Some(id);
+            entry.author_name_db = Some(db_name);
+        } else if let Some(suggestion) = suggest_author(
+            &connection,
+            &variants,
+            trigram,
+            expand_initials,
+            &mut candidate_cache,
+        )? {
+            debug!(
+                "match {:?}: probable {}",
+                entry.name,
+                format_probable_value(&suggestion)
+            );
+            entry.probable = Some(suggestion);
+        } else {
+            debug!("match {:?}: no candidate", entry.name);
+        }
+        match_cache.put(
+            &name_key,
+            &CachedMatch {
+                author_id: entry.author_id.clone(),
+                author_name_db: entry.author_name_db.clone(),
+                probable: entry.probable.clone(),
+            },
+        )?;
+    }
+    pb.finish_and_clear();
+
+    for entry in authors.iter_mut() {
+        let id = entry
+            .author_id
+            .as_deref()
+            .or(entry.probable.as_ref().map(|p| p.author_id.as_str()));
+        if let Some(id) = id {
+            entry.alternate_ids = fetch_alternate_ids(&connection, id)?;
+        }
+    }
+    Ok(())
+}
+
+/// OpenLibrary author_id of the identity a folder name was recorded under in `author_identity`.
+fn identity_author_id(connection: &Connection, name: &str) -> Result<Option<String>> {
+    let Some(found) = identity::identity_of_name(connection, name)? else {
+        return Ok(None);
+    };
+    Ok(identity::values(connection, &found, "openlibrary")?
+        .into_iter()
+        .next())
+}
+
+// One `match` event per author folder: exact, probable (accepted for merging) or below threshold.
+fn match_events(authors: &[AuthorEntry], probable_threshold: f64) {
+    for entry in authors {
+        let event = Event::new("cleanup", "match", &entry.path, "unmatched");
+        let event = if let Some(id) = &entry.author_id {
+            Event {
+                result: "matched",
+                ..event
+            }
+            .score(1.0)
+            .detail(format!(
+                "{id} {}",
+                entry.author_name_db.as_deref().unwrap_or_default()
+            ))
+        } else if let Some(probable) = &entry.probable {
+            let result = if probable.score() >= probable_threshold {
+                "probable"
+            } else {
+                "suggested"
+            };
+            Event { result, ..event }
+                .score(probable.score())
+                .detail(format!("{} {}", probable.author_id, probable.display_name))
+        } else {
+            event
+        };
+        event.emit();
+    }
+}
+
+// `rename`/`merge`/`discard`/`remove` event: `planned` in dry-run, `ok` once done.
+fn op_event(action: &'static str, path: &Path, target: Option<&Path>, dry_run: bool) {
+    let mut event = Event::new(
+        "cleanup",
+        action,
+        path,
+        if dry_run { "planned" } else { "ok" },
+    );
+    if let Some(target) = target {
+        event = event.target(target);
+    }
+    event.emit();
+}
+
+fn fetch_alternate_ids(connection: &Connection, author_id: &str) -> Result<Vec<String>> {
+    let alternates: Option<String> = connection
+        .query_row(
+            "SELECT alternate_id FROM authors WHERE author_id = ?1 LIMIT 1",
+            [author_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    Ok(alternates
+        .unwrap_or_default()
+        .split(',')
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .collect())
+}
+
+fn write_authors_csv(
+    path: &Path,
+    authors: &[AuthorEntry],
+    inventory: bool,
+    groups: Option<&HashMap<String, String>>,
+) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(true)
+        .quote_style(csv::QuoteStyle::Necessary)
+        .from_path(path)?;
+    let mut header = vec![
+        "author",
+        "author_id",
+        "author_name_db",
+        "probable_author_multi",
+    ];
+    if inventory {
+        header.extend(["file_count", "total_bytes", "max_depth"]);
+    }
+    if groups.is_some() {
+        header.push("group");
+    }
+    writer.write_record(&header)?;
+    for entry in authors {
+        let probable_value = entry
+            .probable
+            .as_ref()
+            .map(format_probable_value)
+            .unwrap_or_default();
+        let mut record = vec![
+            entry.name.clone(),
+            entry.author_id.clone().unwrap_or_default(),
+            entry.author_name_db.clone().unwrap_or_default(),
+            probable_value,
+        ];
+        if inventory {
+            let (files, bytes, depth) = folder_inventory(&entry.path);
+            record.extend([files.to_string(), bytes.to_string(), depth.to_string()]);
+        }
+        if let Some(groups) = groups {
+            record.push(groups.get(&entry.name).cloned().unwrap_or_default());
+        }
+        writer.write_record(&record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Lists suggestions scoring in `[low, high)`: not merged automatically, but worth
+/// a look. `merge_into` names the folders already holding that author_id.
+fn write_review_csv(path: &Path, authors: &[AuthorEntry], low: f64, high: f64) -> Result<usize> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(true)
+        .quote_style(csv::QuoteStyle::Necessary)
+        .from_path(path)?;
+    writer.write_record([
+        "author",
+        "suggested_author_id",
+        "suggested_name",
+        "score",
+        "merge_into",
+    ])?;
+    let mut count = 0usize;
+    for entry in authors {
+        if entry.author_id.is_some() {
+            continue;
+        }
+        let Some(probable) = &entry.probable else {
+            continue;
+        };
+        let score = probable.score();
+        if score < low || score >= high {
+            continue;
+        }
+        let merge_into = authors
+            .iter()
+            .filter(|other| other.name != entry.name)
+            .filter(|other| effective_author_id(other, high) == Some(probable.author_id.as_str()))
+            .map(|other| other.name.as_str())
+            .collect::<Vec<_>>()
+            .join("|");
+        writer.write_record([
+            entry.name.as_str(),
+            probable.author_id.as_str(),
+            probable.display_name.as_str(),
+            format!("{score:.2}").as_str(),
+            merge_into.as_str(),
+        ])?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+fn merge_by_author_id(
+    cli: &Cli,
+    authors: &[AuthorEntry],
+    exclude: &ExcludeList,
+    online: Option<&BlockingWikidata>,
+    ops: &mut FsOps,
+) -> Result<Vec<MergeGroup>> {
+    let mut merges = Vec::new();
+    let mut links = IdLinks::default();
+    if cli.merge_alternates {
+        for entry in authors {
+            if let Some(id) = effective_author_id(entry, cli.probable_threshold) {
+                for alternate in &entry.alternate_ids {
+                    links.union(id, alternate);
+                }
+            }
+        }
+    }
+    let mut grouped: HashMap<String, Vec<&AuthorEntry>> = HashMap::new();
+    for entry in authors {
+        if exclude.is_protected(&entry.path) {
+            continue;
+        }
+        if let Some(id) = effective_author_id(entry, cli.probable_threshold) {
+            grouped.entry(links.find(id)).or_default().push(entry);
+        }
+    }
+
+    // Most confident groups first, so --max-merges/--max-files-moved keep the safest ones.
+    let mut grouped: Vec<(String, Vec<&AuthorEntry>)> = grouped.into_iter().collect();
+    grouped.sort_by(|(a_id, a_dirs), (b_id, b_dirs)| {
+        group_confidence(b_dirs)
+            .partial_cmp(&group_confidence(a_dirs))
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| a_id.cmp(b_id))
+    });
+    let mut merged_groups = 0usize;
+    let mut files_moved = 0usize;
+    let mut deferred = 0usize;
+
+    let pb = progress::phase_bar("merge", grouped.len());
+    for (author_id, dirs) in grouped.into_iter().progress_with(pb.clone()) {
+        if dirs.len() < 2 {
+            continue;
+        }
+        let mut candidates = Vec::new();
+        for entry in dirs {
+            if !entry.path.exists() {
+                continue;
+            }
+            let file_count = count_files(&entry.path)?;
+            if file_count < cli.min_files {
+                continue;
+            }
+            candidates.push((entry, file_count));
+        }
+        if candidates.len() < 2 {
+            continue;
+        }
+        if deferred > 0 || cli.max_merges.is_some_and(|max| merged_groups >= max) {
+            deferred += 1;
+            continue;
+        }
+        if let Some(threshold) = cli.verify_threshold
+            && let Some(online) = online
+            && needs_verification(&candidates, threshold)
+            && !wikidata_confirms(&candidates, online)
+        {
+            say!(
+                "Skipping low-confidence merge for author_id {} (not confirmed by Wikidata)",
+                author_id
+            );
+            info!("merge {}: skipped, not confirmed by Wikidata", author_id);
+            continue;
+        }
+        let db_name = candidates
+            .iter()
+            .find(|(entry, _)| entry.author_id.as_deref() == Some(author_id.as_str()))
+            .and_then(|(entry, _)| entry.author_name_db.clone())
+            .or_else(|| {
+                candidates
+                    .iter()
+                    .find_map(|(entry, _)| entry.author_name_db.clone())
+            })
+            .or_else(|| entry_best_probable_display(&candidates));
+
+        let db_name_ref = db_name.as_deref();
+        // Across several roots, the group always lands in the main library.
+        let in_main = |entry: &AuthorEntry| entry.path.parent() == Some(cli.root());
+        candidates.sort_by(|(a_entry, a_count), (b_entry, b_count)| {
+            let score_a = alignment_score(&a_entry.path, db_name_ref);
+            let score_b = alignment_score(&b_entry.path, db_name_ref);
+            in_main(b_entry)
+                .cmp(&in_main(a_entry))
+                .then_with(|| score_b.partial_cmp(&score_a).unwrap_or(Ordering::Equal))
+                .then_with(|| b_count.cmp(a_count))
+                .then_with(|| a_entry.name.cmp(&b_entry.name))
+        });
+        let incoming: usize = candidates.iter().skip(1).map(|(_, files)| files).sum();
+        if cli
+            .max_files_moved
+            .is_some_and(|max| files_moved + incoming > max)
+        {
+            deferred += 1;
+            continue;
+        }
+        merged_groups += 1;
+        files_moved += incoming;
+        let destination = candidates.first().unwrap().0.path.clone();
+        say!(
+            "Merging author_id {} into {}",
+            author_id,
+            destination.display()
+        );
+        info!(
+            "merge {}: into {:?} (db name {:?})",
+            author_id, destination, db_name
+        );
+        let members: Vec<MergeMember> = candidates
+            .iter()
+            .map(|(entry, file_count)| MergeMember {
+                path: entry.path.clone(),
+                file_count: *file_count,
+                alignment_score: alignment_score(&entry.path, db_name_ref),
+                probable_score: match (&entry.author_id, &entry.probable) {
+                    (None, Some(probable)) => Some(probable.score()),
+                    _ => None,
+                },
+            })
+            .collect();
+        for member in &members {
+            info!(
+                "merge {}: {:?} files={} alignment={:.3} probable={:?}",
+                author_id,
+                member.path,
+                member.file_count,
+                member.alignment_score,
+                member.probable_score
+            );
+        }
+        for (entry, _) in candidates.iter().skip(1) {
+            say!("  - {} -> {}", entry.path.display(), destination.display());
+            merge_directories(&entry.path, &destination, ops)?;
+        }
+        merges.push(MergeGroup {
+            author_id,
+            db_name,
+            destination,
+            members,
+        });
+    }
+    pb.finish_and_clear();
+    if deferred > 0 {
+        say!(
+            "{} merge group(s) deferred by --max-merges/--max-files-moved ({} done, {} file(s)).",
+            deferred,
+            merged_groups,
+            files_moved
+        );
+    }
+    Ok(merges)
+}
+
+/// Moves matched folders left in the secondary roots (not merged away by
+/// `merge_by_author_id`) into the main root under the same name, merging with a
+/// namesake already there. Unmatched folders stay where they are.
+fn fold_into_main_root(
+    cli: &Cli,
+    authors: &mut [AuthorEntry],
+    merges: &[MergeGroup],
+    exclude: &ExcludeList,
+    ops: &mut FsOps,
+    normalizations: &mut Vec<Normalization>,
+) -> Result<()> {
+    let merged_away: HashSet<&Path> = merges
+        .iter()
+        .flat_map(|group| {
+            group
+                .members
+                .iter()
+                .filter(|member| member.path != group.destination)
+        })
+        .map(|member| member.path.as_path())
+        .collect();
+    for entry in authors.iter_mut() {
+        if entry.path.parent() == Some(cli.root())
+            || merged_away.contains(entry.path.as_path())
+            || !entry.path.is_dir()
+            || exclude.is_protected(&entry.path)
+            || effective_author_id(entry, cli.probable_threshold).is_none()
+        {
+            continue;
+        }
+        let target = cli.root().join(&entry.name);
+        if exclude.is_protected(&target) {
+            continue;
+        }
+        say!("Folding {} into {}", entry.path.display(), target.display());
+        merge_directories(&entry.path, &target, ops)?;
+        normalizations.push(Normalization {
+            from: entry.path.clone(),
+            to: target.clone(),
+            action: NormalizeAction::Merge,
+        });
+        if !ops.dry_run {
+            entry.path = target;
+        }
+    }
+    Ok(())
+}
+
+/// Lowest member confidence: 1.0 for exact matches, the suggestion score otherwise.
+fn group_confidence(dirs: &[&AuthorEntry]) -> f64 {
+    dirs.iter()
+        .map(|entry| match (&entry.author_id, &entry.probable) {
+            (None, Some(probable)) => probable.score(),
+            _ => 1.0,
+        })
+        .fold(1.0, f64::min)
+}
+
+/// Confirmed author_id, or the probable one when its score reaches the threshold.
+fn effective_author_id(entry: &AuthorEntry, threshold: f64) -> Option<&str> {
+    if let Some(id) = &entry.author_id {
+        return Some(id);
+    }
+    let probable = entry.probable.as_ref()?;
+    let score = probable.score();
+    (score >= threshold).then_some(probable.author_id.as_str())
+}
+
+/// True when every folder in the group only matched through a probable suggestion
+/// and at least one of those suggestions scores below `threshold`.
+fn needs_verification(candidates: &[(&AuthorEntry, usize)], threshold: f64) -> bool {
+    let mut lowest = f64::MAX;
+    for (entry, _) in candidates {
+        if entry.author_id.is_some() {
+            return false;
+        }
+        let Some(probable) = &entry.probable else {
+            return false;
+        };
+        lowest = lowest.min(probable.score());
+    }
+    lowest < threshold
+}
+
+/// Wikidata client of --verify-threshold and --group-by, with the author-alias-online
+/// identity, cache, pacing and retries.
+fn wikidata_client(cli: &Cli) -> Result<BlockingWikidata> {
+    let options = ClientOptions {
+        contact: cli.user_agent_contact.clone(),
+        token: cli.api_token.clone(),
+        proxy: cli.proxy.clone(),
+    };
+    let mut http = CachedHttp::with_options(cli.verify_timeout, &options)
+        .context("Cannot build the Wikidata HTTP client")?
+        .with_policy(RequestPolicy {
+            min_interval: if cli.wikidata_rate > 0.0 {
+                Duration::from_secs_f64(1.0 / cli.wikidata_rate)
+            } else {
+                Duration::ZERO
+            },
+            retries: cli.wikidata_retries,
+            ..RequestPolicy::default()
+        });
+    if let Some(path) = &cli.wikidata_cache {
+        http = http.with_cache(Arc::new(ResponseCache::open(
+            path,
+            WIKIDATA_CACHE_TTL_DAYS,
+        )?));
+    }
+    BlockingWikidata::new(WikidataClient::new(http))
+}
+
+/// Looks every folder name up on Wikidata and accepts the group only when all of
+/// them resolve to the same entity. Network failures count as "not confirmed".
+fn wikidata_confirms(candidates: &[(&AuthorEntry, usize)], online: &BlockingWikidata) -> bool {
+    let mut qid: Option<String> = None;
+    for (entry, _) in candidates {
+        let query = wikidata::normalize_query(&entry.name);
+        let (id, label) = match online.search(&query) {
+            Ok(Some((id, label, _, _))) => (id, label),
+            Ok(None) => {
+                say!("  Wikidata: no match for {:?}", entry.name);
+                return false;
+            }
+            Err(e) => {
+                say!("  Wikidata: lookup of {:?} failed: {:#}", entry.name, e);
+                error!("Wikidata lookup of {:?} failed: {:#}", entry.name, e);
+                return false;
+            }
+        };
+        say!("  Wikidata: {:?} -> {} ({})", entry.name, id, label);
+        match &qid {
+            Some(existing) if *existing != id => return false,
+            Some(_) => {}
+            None => qid = Some(id),
+        }
+    }
+    qid.is_some()
+}
+
+/// Union-find over author_ids linked by `alternate_id`. `union(primary, alternate)`
+/// keeps the primary's root, so a group is keyed by the record listing the alternates.
+#[derive(Default)]
+struct IdLinks {
+    parent: HashMap<String, String>,
+}
+
+impl IdLinks {
+    fn find(&mut self, id: &str) -> String {
+        let mut current = id.to_string();
+        while let Some(parent) = self.parent.get(&current) {
+            if *parent == current {
+                break;
+            }
+            current = parent.clone();
+        }
+        if current != id {
+            self.parent.insert(id.to_string(), current.clone());
+        }
+        current
+    }
+
+    fn union(&mut self, a: &str, b: &str) {
+        let root_a = self.find(a);
+        let root_b = self.find(b);
+        if root_a == root_b {
+            return;
+        }
+        self.parent.insert(root_b, root_a.clone());
+        self.parent.insert(root_a.clone(), root_a);
+    }
+}
+
+fn entry_best_probable_display(candidates: &[(&AuthorEntry, usize)]) -> Option<String> {
+    for (entry, _) in candidates {
+        if let Some(probable) = &entry.probable {
+            return Some(probable.display_name.clone());
+        }
+    }
+    None
+}
+
+fn alignment_score(path: &Path, db_name: Option<&str>) -> f64 {
+    let db_name = match db_name {
+        Some(value) if !value.trim().is_empty() => value,
+        _ => return 0.0,
+    };
+    let dir_name = path
+        .file_name()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+    let dir_norm = normalize_for_compare(&dir_name);
+    let db_norm = normalize_for_compare(db_name);
+    if dir_norm.is_empty() || db_norm.is_empty() {
+        return 0.0;
+    }
+    let mut variants = vec![db_norm.clone()];
+    let parts: Vec<_> = db_norm.split_whitespace().collect();
+    if parts.len() >= 2 {
+        let mut rotated = parts.clone();
+        rotated.rotate_right(1);
+        variants.push(rotated.join(" "));
+    }
+    variants
+        .into_iter()
+        .map(|variant| sequence_ratio(&dir_norm, &variant))
+        .fold(0.0, f64::max)
+}
+
+fn normalize_for_compare(value: &str) -> String {
+    let stripped = strip_accents(value);
+    stripped
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() {
+                ch.to_ascii_lowercase()
+            } else {
+                ' '
+            }
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn rename_with_case_handling(src: &Path, dst: &Path) -> Result<()> {
+    if same_path(src, dst) {
+        return Ok(());
+    }
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let src_lower = src.to_string_lossy().to_lowercase();
+    let dst_lower = dst.to_string_lossy().to_lowercase();
+    if src_lower == dst_lower {
+        let mut temp = dst.with_extension("__tmp_case__");
+        let mut index = 1;
+        while temp.exists() {
+            temp = dst.with_extension(format!("__tmp_case__{}", index));
+            index += 1;
+        }
+        fs::rename(src, &temp)?;
+        fs::rename(temp, dst)?;
+    } else {
+        fs::rename(src, dst)?;
+    }
+    Ok(())
+}
+
+fn merge_directories(src: &Path, dst: &Path, ops: &mut FsOps) -> Result<()> {
+    if same_path(src, dst) {
+        return Ok(());
+    }
+    op_event("merge", src, Some(dst), ops.dry_run);
+    if ops.dry_run {
+        say!("[DRY-RUN] merge {} -> {}", src.display(), dst.display());
+        return simulate_merge(src, dst, ops);
+    }
+    fs::create_dir_all(dst)?;
+    for entry in WalkDir::new(src).min_depth(1) {
+        let entry = entry?;
+        let rel = entry.path().strip_prefix(src)?;
+        let sanitized_rel = sanitize_relative_path(rel);
+        let target = dst.join(&sanitized_rel);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+            continue;
+        }
+        if entry.file_type().is_file() {
+            fs::create_dir_all(
+                target
+                    .parent()
+                    .ok_or_else(|| anyhow!("Invalid path {:?}", target))?,
+            )?;
+            move_or_keep_larger(entry.path(), &target, ops)?;
+        }
+    }
+    fs::remove_dir_all(src)?;
+    Ok(())
+}
+
+/// Dry-run counterpart of `merge_directories`: applies the same conflict rules
+/// to the files that would land in `dst` and accumulates `ops.stats`.
+fn simulate_merge(src: &Path, dst: &Path, ops: &mut FsOps) -> Result<()> {
+    ops.stats.folders_merged += 1;
+    for entry in WalkDir::new(src).min_depth(1) {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let rel = entry.path().strip_prefix(src)?;
+        let target = dst.join(sanitize_relative_path(rel));
+        let src_size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        if ops.keep == KeepPolicy::Format {
+            let mut siblings: BTreeSet<PathBuf> = sibling_files(&target).into_iter().collect();
+            siblings.extend(
+                ops.stats
+                    .planned
+                    .keys()
+                    .filter(|path| path.parent() == target.parent())
+                    .cloned(),
+            );
+            siblings.retain(|path| ops.stats.size(path).is_some());
+            let mut dropped = false;
+            for loser in format_losers(entry.path(), &target, siblings, &ops.format_priority) {
+                ops.stats.duplicates_removed += 1;
+                if loser == entry.path() {
+                    ops.stats.bytes_reclaimed += src_size;
+                    dropped = true;
+                    break;
+                }
+                ops.stats.bytes_reclaimed += ops.stats.size(&loser).unwrap_or(0);
+                ops.stats.planned.remove(&loser);
+                ops.stats.removed.insert(loser);
+            }
+            if dropped {
+                continue;
+            }
+        }
+        let Some(dst_size) = ops.stats.size(&target) else {
+            ops.stats.files_moved += 1;
+            ops.stats.planned.insert(target, src_size);
+            continue;
+        };
+        let duplicate = match ops.compare {
+            CompareMode::Size => true,
+            CompareMode::Hash => {
+                src_size == dst_size
+                    && target.is_file()
+                    && file_digest(entry.path())? == file_digest(&target)?
+            }
+        };
+        if duplicate {
+            ops.stats.duplicates_removed += 1;
+            if incoming_wins(entry.path(), src_size, &target, dst_size, ops.keep) {
+                ops.stats.bytes_reclaimed += dst_size;
+                ops.stats.files_moved += 1;
+                ops.stats.planned.insert(target, src_size);
+            } else {
+                ops.stats.bytes_reclaimed += src_size;
+            }
+        } else {
+            ops.stats.files_moved += 1;
+        }
+    }
+    Ok(())
+}
+
+fn sanitize_relative_path(rel: &Path) -> PathBuf {
+    let mut cleaned = PathBuf::new();
+    for component in rel.components() {
+        let os: OsString = component.as_os_str().into();
+        let part = os.to_string_lossy();
+        cleaned.push(sanitize_component(&part));
+    }
+    cleaned
+}
+
+fn move_or_keep_larger(src: &Path, dst: &Path, ops: &mut FsOps) -> Result<()> {
+    if ops.keep == KeepPolicy::Format {
+        for loser in format_losers(src, dst, sibling_files(dst), &ops.format_priority) {
+            debug!("format conflict {:?}: discard {:?}", src, loser);
+            discard_file(&loser, ops)?;
+            if loser == src {
+                return Ok(());
+            }
+        }
+    }
+    if !dst.exists() {
+        move_file(src, dst)?;
+        ops.journal.record(JournalOp::MoveFile, src, Some(dst))?;
+        return Ok(());
+    }
+    let src_size = src.metadata().map(|m| m.len()).unwrap_or(0);
+    let dst_size = dst.metadata().map(|m| m.len()).unwrap_or(0);
+    debug!(
+        "conflict {:?} ({} B) vs {:?} ({} B)",
+        src, src_size, dst, dst_size
+    );
+    if ops.compare == CompareMode::Hash {
+        if src_size == dst_size && file_digest(src)? == file_digest(dst)? {
+            discard_file(src, ops)?;
+        } else {
+            let copy = suffixed_copy_path(dst);
+            move_file(src, &copy)?;
+            ops.journal.record(JournalOp::MoveFile, src, Some(&copy))?;
+        }
+        return Ok(());
+    }
+    if incoming_wins(src, src_size, dst, dst_size, ops.keep) {
+        if ops.trash.is_some() {
+            discard_file(dst, ops)?;
+            move_file(src, dst)?;
+            ops.journal.record(JournalOp::MoveFile, src, Some(dst))?;
+            return Ok(());
+        }
+        let tmp = dst.with_extension("old_to_delete");
+        if tmp.exists() {
+            fs::remove_file(&tmp).ok();
+        }
+        if let Some(snapshot) = &ops.snapshot {
+            snapshot.keep(dst)?;
+        }
+        fs::rename(dst, &tmp).ok();
+        ops.journal.record(JournalOp::DeleteFile, dst, None)?;
+        move_file(src, dst)?;
+        ops.journal.record(JournalOp::MoveFile, src, Some(dst))?;
+        fs::remove_file(tmp).ok();
+    } else {
+        discard_file(src, ops)?;
+    }
+    Ok(())
+}
+
+/// Whether the incoming file replaces the one already in the target (`--keep`).
+/// `newer` falls back to size when a modification time is missing or equal;
+/// `format` only matters across extensions (`format_losers`), so it compares sizes here.
+fn incoming_wins(src: &Path, src_size: u64, dst: &Path, dst_size: u64, keep: KeepPolicy) -> bool {
+    let modified = |path: &Path| path.metadata().and_then(|m| m.modified()).ok();
+    match (keep, modified(src), modified(dst)) {
+        (KeepPolicy::Newer, Some(src_time), Some(dst_time)) if src_time != dst_time => {
+            src_time > dst_time
+        }
+        _ => src_size > dst_size,
+    }
+}
+
+/// What `--keep format` discards when `src` arrives at `dst`. Rivals are the `siblings`
+/// with the stem of `dst` in another ranked format (`Title.pdf` when `Title.epub` arrives):
+/// `src` alone when the best rival ranks higher, else every rival, best-ranked first.
+/// Shared by `move_or_keep_larger` and the dry-run `simulate_merge`.
+fn format_losers(
+    src: &Path,
+    dst: &Path,
+    siblings: impl IntoIterator<Item = PathBuf>,
+    priority: &[String],
+) -> Vec<PathBuf> {
+    let (Some(src_rank), Some(stem)) = (format_rank(src, priority), dst.file_stem()) else {
+        return Vec::new();
+    };
+    let stem = stem.to_string_lossy().to_lowercase();
+    let mut rivals: Vec<(usize, PathBuf)> = siblings
+        .into_iter()
+        .filter(|path| path.as_path() != dst)
+        .filter(|path| {
+            path.file_stem()
+                .is_some_and(|s| s.to_string_lossy().to_lowercase() == stem)
+        })
+        .filter_map(|path| Some((format_rank(&path, priority)?, path)))
+        .filter(|(rank, _)| *rank != src_rank)
+        .collect();
+    rivals.sort();
+    match rivals.first() {
+        Some((best, _)) if *best < src_rank => vec![src.to_path_buf()],
+        _ => rivals.into_iter().map(|(_, path)| path).collect(),
+    }
+}
+
+/// Files in the folder of `path`, as they are on disk.
+fn sibling_files(path: &Path) -> Vec<PathBuf> {
+    path.parent()
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+fn format_rank(path: &Path, priority: &[String]) -> Option<usize> {
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    priority.iter().position(|candidate| *candidate == ext)
+}
+
+fn file_digest(path: &Path) -> Result<[u8; 32]> {
+    let mut file = fs::File::open(path).with_context(|| format!("Hash {:?}", path))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+/// `Title.epub` -> first free `Title (2).epub`, `Title (3).epub`, ...
+fn suffixed_copy_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().map(|ext| ext.to_string_lossy());
+    let mut index = 2;
+    loop {
+        let name = match &ext {
+            Some(ext) => format!("{stem} ({index}).{ext}"),
+            None => format!("{stem} ({index})"),
+        };
+        let candidate = path.with_file_name(name);
+        if !candidate.exists() {
+            return candidate;
+        }
+        index += 1;
+    }
+}
+
+/// Deletes a losing duplicate, or moves it under the run's trash folder with `--trash`.
+fn discard_file(path: &Path, ops: &mut FsOps) -> Result<()> {
+    if let Some(snapshot) = &ops.snapshot {
+        snapshot.keep(path)?;
+    }
+    let Some(trash) = &ops.trash else {
+        fs::remove_file(path).ok();
+        ops.journal.record(JournalOp::DeleteFile, path, None)?;
+        op_event("discard", path, None, false);
+        return Ok(());
+    };
+    let rel = trash
+        .roots
+        .iter()
+        .find_map(|root| path.strip_prefix(root).ok())
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from(path.file_name().unwrap_or_default()));
+    let mut target = trash.dir.join(rel);
+    let mut index = 1;
+    while target.exists() {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match path.extension() {
+            Some(ext) => format!("{stem}.{index}.{}", ext.to_string_lossy()),
+            None => format!("{stem}.{index}"),
+        };
+        target.set_file_name(name);
+        index += 1;
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    move_file(path, &target)?;
+    ops.journal
+        .record(JournalOp::TrashFile, path, Some(&target))?;
+    op_event("discard", path, Some(&target), false);
+    Ok(())
+}
+
+fn is_trash_dir(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name == TRASH_DIR_NAME)
+        .unwrap_or(false)
+}
+
+fn move_file(src: &Path, dst: &Path) -> Result<()> {
+    match fs::rename(src, dst) {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            if err.kind() == std::io::ErrorKind::NotFound {
+                return Ok(());
+            }
+            fs::copy(src, dst)?;
+            fs::remove_file(src).ok();
+            Ok(())
+        }
+    }
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(left), Ok(right)) => left == right,
+        _ => a == b,
+    }
+}
+
+fn strip_accents(value: &str) -> String {
+    value
+        .nfkd()
+        .filter(|ch| !is_combining_mark(*ch))
+        .collect::<String>()
+}
+
+fn normalize_author_display(name: &str) -> String {
+    if name.trim().is_empty() {
+        return "_".into();
+    }
+    let mut stripped = strip_accents(name);
+    stripped = stripped
+        .replace(['’', '`', '´'], "'")
+        .replace(['_', '-'], " ");
+    stripped = stripped.split_whitespace().collect::<Vec<_>>().join(" ");
+    let letters: String = stripped
+        .chars()
+        .filter(|ch| ch.is_ascii_alphabetic())
+        .collect();
+    let mut base = stripped.clone();
+    if !letters.is_empty() && letters.chars().all(|ch| ch.is_ascii_uppercase()) {
+        base = stripped.to_lowercase();
+    }
+
+    let (first, last) = if let Some(idx) = base.find(',') {
+        let last = base[..idx].trim().to_string();
+        let first = base[idx + 1..].trim().to_string();
+        split_leading_particles(&last, &first)
+    } else {
+        let tokens: Vec<_> = base.split_whitespace().collect();
+        let particles = tokens.iter().take_while(|t| is_particle(t)).count();
+        if particles > 0 && particles < tokens.len() {
+            // "De Maupassant Guy": particles + surname first, given names after.
+            let last = tokens[particles].to_string();
+            let first = tokens[particles + 1..]
+                .iter()
+                .chain(&tokens[..particles])
+                .copied()
+                .collect::<Vec<_>>()
+                .join(" ");
+            (first, last)
+        } else if tokens.len() >= 2 {
+            let last = tokens.last().unwrap().to_string();
+            let first = tokens[..tokens.len() - 1].join(" ");
+            (first, last)
+        } else {
+            (
+                tokens.first().map(|s| s.to_string()).unwrap_or_default(),
+                String::new(),
+            )
+        }
+    };
+
+    let first_cap = capitalize_words(&first);
+    let last_cap = capitalize_words(&last);
+    if !last_cap.is_empty() {
+        let mut value = format!("{}, {}", last_cap, first_cap)
+            .trim()
+            .trim_matches(',')
+            .to_string();
+        if value.is_empty() {
+            value = last_cap;
+        }
+        value
+    } else {
+        first_cap
+    }
+}
+
+fn is_particle(token: &str) -> bool {
+    NAME_PARTICLES.contains(&token.to_lowercase().as_str())
+}
+
+/// `De Maupassant` / `Guy` -> (`Guy De`, `Maupassant`): leading particles of the
+/// surname move after the given names.
+fn split_leading_particles(last: &str, first: &str) -> (String, String) {
+    let tokens: Vec<_> = last.split_whitespace().collect();
+    let particles = tokens.iter().take_while(|t| is_particle(t)).count();
+    if particles == 0 || particles == tokens.len() {
+        return (first.to_string(), last.to_string());
+    }
+    let first = first
+        .split_whitespace()
+        .chain(tokens[..particles].iter().copied())
+        .collect::<Vec<_>>()
+        .join(" ");
+    (first, tokens[particles..].join(" "))
+}
+
+/// "De Maupassant Guy" -> "Guy De Maupassant", so surname-first names with a
+/// leading particle also produce the given-name-first variant stored in the DB.
+fn reorder_particles(value: &str) -> Option<String> {
+    if value.contains(',') {
+        return None;
+    }
+    let tokens: Vec<_> = value.split_whitespace().collect();
+    let particles = tokens.iter().take_while(|t| is_particle(t)).count();
+    if particles == 0 || tokens.len() < particles + 2 {
+        return None;
+    }
+    let (surname, given) = tokens.split_at(particles + 1);
+    Some(
+        given
+            .iter()
+            .chain(surname)
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" "),
+    )
+}
+
+fn capitalize_words(value: &str) -> String {
+    value
+        .split_whitespace()
+        .map(|token| {
+            let mut chars = token.chars();
+            match chars.next() {
+                Some(first) => {
+                    first.to_uppercase().collect::<String>() + &chars.as_str().to_lowercase()
+                }
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn normalized_variants(name: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut variants = Vec::new();
+    for candidate in generate_candidates(name) {
+        let normalized = normalize_name(&candidate);
+        if normalized.is_empty() || !seen.insert(normalized.clone()) {
+            continue;
+        }
+        variants.push(normalized);
+    }
+    variants
+}
+
+fn normalize_name(value: &str) -> String {
+    let stripped = strip_accents(value);
+    let lowered = stripped.to_lowercase();
+    let filtered = lowered
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || ch == '-' {
+                ch
+            } else {
+                ' '
+            }
+        })
+        .collect::<String>();
+    filtered.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn generate_candidates(name: &str) -> Vec<String> {
+    let mut results = Vec::new();
+    let trimmed = name.trim();
+    if trimmed.is_empty() {
+        return results;
+    }
+    let mut base = vec![trimmed.to_string()];
+    let stripped = strip_enclosures(trimmed);
+    if stripped != trimmed {
+        base.push(stripped.clone());
+    }
+    let digits_removed = remove_numeric_tokens(&stripped);
+    if !digits_removed.is_empty() && !base.contains(&digits_removed) {
+        base.push(digits_removed.clone());
+    }
+    let reordered = reorder_initials(&digits_removed)
+        .or_else(|| reorder_initials(&stripped))
+        .unwrap_or_default();
+    if !reordered.is_empty() && !base.contains(&reordered) {
+        base.push(reordered);
+    }
+    if let Some(particles) = reorder_particles(&digits_removed)
+        && !base.contains(&particles)
+    {
+        base.push(particles);
+    }
+
+    let mut seen = HashSet::new();
+    for candidate in base {
+        if seen.insert(candidate.clone()) {
+            results.push(candidate.clone());
+        }
+        if candidate.contains(',')
+            && let Some((left, right)) = candidate.split_once(',')
+        {
+            let swapped = format!("{} {}", right.trim(), left.trim())
+                .trim()
+                .to_string();
+            if !swapped.is_empty() && seen.insert(swapped.clone()) {
+                results.push(swapped);
+            }
+        }
+    }
+    results
+}
+
+fn strip_enclosures(value: &str) -> String {
+    let step = BRACKET_RE.replace_all(value, " ");
+    PAREN_RE.replace_all(&step, " ").to_string()
+}
+
+fn remove_numeric_tokens(value: &str) -> String {
+    value
+        .split_whitespace()
+        .filter(|token| !token.chars().all(|ch| ch.is_ascii_digit()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn reorder_initials(value: &str) -> Option<String> {
+    let tokens: Vec<_> = value.split_whitespace().collect();
+    if tokens.is_empty() {
+        return None;
+    }
+    let initials: Vec<_> = tokens.iter().filter(|t| t.len() == 1).cloned().collect();
+    let others: Vec<_> = tokens.iter().filter(|t| t.len() > 1).cloned().collect();
+    if initials.is_empty() || others.is_empty() {
+        return None;
+    }
+    let mut combined: Vec<_> = others;
+    combined.extend(initials);
+    Some(combined.join(" "))
+}
+
+/// Replaces the single-letter tokens of `variant` with the given names of `candidate`
+/// when every full word of `variant` appears in `candidate` and the leftover candidate
+/// words start with those initials, in order ("j r r tolkien" vs "john ronald reuel
+/// tolkien" -> "john ronald reuel tolkien").
+fn expand_initials_against(variant: &str, candidate: &str) -> Option<String> {
+    let tokens: Vec<&str> = variant.split_whitespace().collect();
+    if !tokens.iter().any(|token| token.len() == 1) {
+        return None;
+    }
+    let mut remaining: Vec<&str> = candidate.split_whitespace().collect();
+    for token in tokens.iter().filter(|token| token.len() > 1) {
+        let index = remaining.iter().position(|word| word == token)?;
+        remaining.remove(index);
+    }
+    let initials: Vec<&str> = tokens
+        .iter()
+        .copied()
+        .filter(|token| token.len() == 1)
+        .collect();
+    if remaining.len() != initials.len()
+        || !remaining
+            .iter()
+            .zip(&initials)
+            .all(|(word, initial)| word.starts_with(initial))
+    {
+        return None;
+    }
+    let mut given = remaining.into_iter();
+    Some(
+        tokens
+            .iter()
+            .map(|token| {
+                if token.len() == 1 {
+                    given.next().unwrap_or(token)
+                } else {
+                    token
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" "),
+    )
+}
+
+/// Renames folders written with initials to the DB name they were expanded to
+/// (e.g. `Tolkien, J.R.R.` -> `Tolkien, John Ronald Reuel`) when the suggestion
+/// reaches the probable threshold. An existing target is left to the author_id merge.
+fn rename_expanded_initials(
+    cli: &Cli,
+    authors: &mut [AuthorEntry],
+    exclude: &ExcludeList,
+    ops: &mut FsOps,
+    normalizations: &mut Vec<Normalization>,
+) -> Result<()> {
+    for entry in authors.iter_mut() {
+        if entry.author_id.is_some() || exclude.is_protected(&entry.path) {
+            continue;
+        }
+        let Some(probable) = &entry.probable else {
+            continue;
+        };
+        if probable.score() < cli.probable_threshold {
+            continue;
+        }
+        let db_normalized = normalize_name(&probable.display_name);
+        let expanded = normalized_variants(&entry.name)
+            .iter()
+            .any(|variant| expand_initials_against(variant, &db_normalized).is_some());
+        if !expanded {
+            continue;
+        }
+        let display_name = probable.display_name.clone();
+        rename_entry_to_db_name(
+            entry,
+            &display_name,
+            "initials expanded",
+            exclude,
+            ops,
+            normalizations,
+        )?;
+    }
+    Ok(())
+}
+
+/// `--rename-to-db-name`: renames every exactly matched folder to the DB name in
+/// "Last, First" form so the library converges on one naming convention.
+fn rename_exact_to_db_names(
+    authors: &mut [AuthorEntry],
+    exclude: &ExcludeList,
+    ops: &mut FsOps,
+    normalizations: &mut Vec<Normalization>,
+) -> Result<()> {
+    for entry in authors.iter_mut() {
+        if entry.author_id.is_none() || exclude.is_protected(&entry.path) {
+            continue;
+        }
+        let Some(db_name) = entry.author_name_db.clone() else {
+            continue;
+        };
+        rename_entry_to_db_name(entry, &db_name, "DB name", exclude, ops, normalizations)?;
+    }
+    Ok(())
+}
+
+/// Renames an author folder to the sanitized `normalize_author_display` form of
+/// `db_name`. Skips when the folder already has that name, or when the target exists
+/// (merging same-author folders is left to `merge_by_author_id`) or is protected.
+fn rename_entry_to_db_name(
+    entry: &mut AuthorEntry,
+    db_name: &str,
+    reason: &str,
+    exclude: &ExcludeList,
+    ops: &mut FsOps,
+    normalizations: &mut Vec<Normalization>,
+) -> Result<()> {
+    let root = entry.path.parent().unwrap_or(Path::new("."));
+    let target = root.join(sanitize_component(&normalize_author_display(db_name)));
+    if same_path(&entry.path, &target) || target.exists() || exclude.is_protected(&target) {
+        return Ok(());
+    }
+    if ops.dry_run {
+        say!(
+            "[DRY-RUN] rename {} -> {} ({reason})",
+            entry.path.display(),
+            target.display()
+        );
+    } else {
+        say!(
+            "Renaming {} -> {} ({reason})",
+            entry.path.display(),
+            target.display()
+        );
+        rename_with_case_handling(&entry.path, &target)?;
+        ops.journal
+            .record(JournalOp::RenameDir, &entry.path, Some(&target))?;
+    }
+    op_event("rename", &entry.path, Some(&target), ops.dry_run);
+    normalizations.push(Normalization {
+        from: entry.path.clone(),
+        to: target.clone(),
+        action: NormalizeAction::Rename,
+    });
+    if !ops.dry_run {
+        entry.name = target
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        entry.path = target;
+    }
+    Ok(())
+}
+
+fn suggest_author(
+    connection: &Connection,
+    variants: &[String],
+    trigram: bool,
+    expand_initials: bool,
+    cache: &mut HashMap<String, Vec<CandidateRow>>,
+) -> Result<Option<Suggestion>> {
+    let mut best: Option<Suggestion> = None;
+    let mut best_avg = 0.0;
+    for suggestion in score_candidates(connection, variants, trigram, expand_initials, cache)? {
+        if suggestion.avg_score > best_avg {
+            best_avg = suggestion.avg_score;
+            best = Some(suggestion);
+        }
+    }
+    if let Some(suggestion) = best
+        && best_avg >= SUGGESTION_FLOOR
+    {
+        return Ok(Some(suggestion));
+    }
+    Ok(None)
+}
+
+/// Every candidate scored for the variants, in retrieval order. Later variants are
+/// skipped once a candidate reaches `GOOD_ENOUGH_SCORE`.
+fn score_candidates(
+    connection: &Connection,
+    variants: &[String],
+    trigram: bool,
+    expand_initials: bool,
+    cache: &mut HashMap<String, Vec<CandidateRow>>,
+) -> Result<Vec<Suggestion>> {
+    let mut scored = Vec::new();
+    let mut best_avg = 0.0;
+    for variant in variants {
+        let candidates = fetch_candidates(connection, variant, trigram, cache)?;
+        // Initials only expand when they point to a single DB author ("Smith, J." would not).
+        let expandable = expand_initials
+            && candidates
+                .iter()
+                .filter(|c| expand_initials_against(variant, &c.name_normalized).is_some())
+                .map(|c| c.author_id.as_str())
+                .collect::<HashSet<_>>()
+                .len()
+                == 1;
+        for candidate in &candidates {
+            let expanded = if expandable {
+                expand_initials_against(variant, &candidate.name_normalized)
+            } else {
+                None
+            };
+            let normalized = expanded.as_deref().unwrap_or(variant);
+            let mut per_metric = BTreeMap::new();
+            per_metric.insert(
+                "seq".into(),
+                clamp(sequence_ratio(normalized, &candidate.name_normalized)),
+            );
+            per_metric.insert(
+                "token".into(),
+                clamp(token_overlap_score(normalized, &candidate.name_normalized)),
+            );
+            per_metric.insert(
+                "prefix".into(),
+                clamp(prefix_score(normalized, &candidate.name_normalized)),
+            );
+            per_metric.insert(
+                "suffix".into(),
+                clamp(suffix_score(normalized, &candidate.name_normalized)),
+            );
+            per_metric.insert(
+                "ngram".into(),
+                clamp(bigram_dice_score(normalized, &candidate.name_normalized)),
+            );
+            per_metric.insert(
+                "lenratio".into(),
+                clamp(length_ratio_score(normalized, &candidate.name_normalized)),
+            );
+            let avg = per_metric.values().sum::<f64>() / per_metric.len() as f64;
+            if avg > best_avg {
+                best_avg = avg;
+            }
+            scored.push(Suggestion {
+                author_id: candidate.author_id.clone(),
+                display_name: candidate.name.clone(),
+                avg_score: avg,
+                seq_score: per_metric.get("seq").copied(),
+                per_metric,
+            });
+        }
+        if best_avg >= GOOD_ENOUGH_SCORE {
+            break;
+        }
+    }
+    Ok(scored)
+}
+
+/// Whether the FTS5 trigram index over `authors.name_normalized` exists (built by the
+/// import script or `--build-trigram-index`); without it candidates come from the neighbor window.
+fn has_trigram_index(connection: &Connection) -> bool {
+    let exists = connection
+        .query_row(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [TRIGRAM_TABLE],
+            |row| row.get::<_, String>(0),
+        )
+        .optional();
+    match exists {
+        Ok(found) => found.is_some(),
+        Err(err) => {
+            say!("Trigram index unavailable ({err}); using neighbor window.");
+            false
+        }
+    }
+}
+
+/// `--build-trigram-index`: creates and fills the FTS5 trigram index in the database when it is
+/// missing. This is the only schema write of cleanup into the OpenLibrary database.
+fn build_trigram_index(db_path: &Path) -> Result<()> {
+    let connection = Connection::open(db_path)
+        .with_context(|| format!("Impossible d'ouvrir la base {:?}", db_path))?;
+    if has_trigram_index(&connection) {
+        say!("Trigram index {TRIGRAM_TABLE} already present.");
+        return Ok(());
+    }
+    say!("Building trigram index {TRIGRAM_TABLE} (may take a while)...");
+    let result = connection.execute_batch(&format!(
+        "BEGIN;
+         CREATE VIRTUAL TABLE {TRIGRAM_TABLE} USING fts5(
+             name_normalized, content='authors', content_rowid='rowid', tokenize='trigram'
+         );
+         INSERT INTO {TRIGRAM_TABLE}({TRIGRAM_TABLE}) VALUES('rebuild');
+         COMMIT;"
+    ));
+    if let Err(err) = result {
+        connection.execute_batch("ROLLBACK;").ok();
+        return Err(anyhow!("Cannot build trigram index {TRIGRAM_TABLE}: {err}"));
+    }
+    Ok(())
+}
+
+fn fetch_candidates(
+    connection: &Connection,
+    normalized: &str,
+    trigram: bool,
+    cache: &mut HashMap<String, Vec<CandidateRow>>,
+) -> Result<Vec<CandidateRow>> {
+    if let Some(cached) = cache.get(normalized) {
+        return Ok(cached.clone());
+    }
+    let mut rows = if trigram {
+        fetch_trigram_candidates(connection, normalized)?
+    } else {
+        Vec::new()
+    };
+    if rows.is_empty() {
+        rows = fetch_neighbor_candidates(connection, normalized)?;
+    }
+    cache.insert(normalized.to_string(), rows.clone());
+    Ok(rows)
+}
+
+/// Token-order independent retrieval: every token of at least three characters
+/// must appear first (AND), then any of them (OR) to fill the remaining slots.
+fn fetch_trigram_candidates(
+    connection: &Connection,
+    normalized: &str,
+) -> Result<Vec<CandidateRow>> {
+    let tokens: Vec<String> = normalized
+        .split_whitespace()
+        .filter(|token| token.chars().count() >= 3)
+        // FTS5 string: an embedded `"` is doubled
+        .map(|token| format!("\"{}\"", token.replace('"', "\"\"")))
+        .collect();
+    if tokens.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut rows = query_trigram(connection, &tokens.join(" AND "))?;
+    if tokens.len() > 1 && rows.len() < TRIGRAM_LIMIT as usize {
+        let mut seen: HashSet<String> = rows.iter().map(|row| row.author_id.clone()).collect();
+        for row in query_trigram(connection, &tokens.join(" OR "))? {
+            if rows.len() >= TRIGRAM_LIMIT as usize {
+                break;
+            }
+            if seen.insert(row.author_id.clone()) {
+                rows.push(row);
+            }
+        }
+    }
+    Ok(rows)
+}
+
+fn query_trigram(connection: &Connection, query: &str) -> Result<Vec<CandidateRow>> {
+    let mut stmt = connection.prepare(&format!(
+        "SELECT a.author_id, a.name, a.name_normalized
+         FROM {TRIGRAM_TABLE} t
+         JOIN authors a ON a.rowid = t.rowid
+         WHERE {TRIGRAM_TABLE} MATCH ?1
+         ORDER BY t.rank
+         LIMIT ?2"
+    ))?;
+    let results = stmt.query_map(params![query, TRIGRAM_LIMIT], |row| {
+        Ok(CandidateRow {
+            author_id: row.get(0)?,
+            name: row.get(1)?,
+            name_normalized: row.get(2)?,
+        })
+    })?;
+    let mut rows = Vec::new();
+    for result in results {
+        rows.push(result?);
+    }
+    Ok(rows)
+}
+
+fn fetch_neighbor_candidates(
+    connection: &Connection,
+    normalized: &str,
+) -> Result<Vec<CandidateRow>> {
+    let mut rows = Vec::new();
+    {
+        let mut stmt = connection.prepare(
+            "SELECT author_id, name, name_normalized
+             FROM authors
+             WHERE name_normalized >= ?1
+             ORDER BY name_normalized
+             LIMIT ?2",
+        )?;
+        let results = stmt.query_map(params![normalized, NEIGHBOR_LIMIT], |row| {
+            Ok(CandidateRow {
+                author_id: row.get(0)?,
+                name: row.get(1)?,
+                name_normalized: row.get(2)?,
+            })
+        })?;
+        for result in results {
+            rows.push(result?);
+        }
+    }
+    {
+        let mut stmt = connection.prepare(
+            "SELECT author_id, name, name_normalized
+             FROM authors
+             WHERE name_normalized < ?1
+             ORDER BY name_normalized DESC
+             LIMIT ?2",
+        )?;
+        let results = stmt.query_map(params![normalized, NEIGHBOR_LIMIT], |row| {
+            Ok(CandidateRow {
+                author_id: row.get(0)?,
+                name: row.get(1)?,
+                name_normalized: row.get(2)?,
+            })
+        })?;
+        for result in results {
+            rows.push(result?);
+        }
+    }
+    Ok(rows)
+}
+
+fn count_files(path: &Path) -> Result<usize> {
+    Ok(WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .count())
+}
+
+/// File count, total size in bytes, and deepest file nesting (1 = directly in the folder).
+fn folder_inventory(path: &Path) -> (usize, u64, usize) {
+    let mut files = 0usize;
+    let mut bytes = 0u64;
+    let mut depth = 0usize;
+    for entry in WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+    {
+        files += 1;
+        bytes += entry.metadata().map(|m| m.len()).unwrap_or(0);
+        depth = depth.max(entry.depth());
+    }
+    (files, bytes, depth)
+}
+
+fn format_probable_value(suggestion: &Suggestion) -> String {
+    let mut parts = vec![
+        suggestion.author_id.clone(),
+        suggestion.display_name.clone(),
+        format!("avg:{:.2}", suggestion.avg_score),
+    ];
+    for key in SCORER_KEYS {
+        if let Some(score) = suggestion.per_metric.get(key) {
+            parts.push(format!("{key}:{score:.2}"));
+        }
+    }
+    parts.join("|")
+}
+
+fn clamp(value: f64) -> f64 {
+    value.clamp(0.0, 1.0)
+}
+
+fn sequence_ratio(a: &str, b: &str) -> f64 {
+    let lcs = lcs_length(a.as_bytes(), b.as_bytes());
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    (2.0 * lcs as f64) / (a.len() as f64 + b.len() as f64)
+}
+
+fn lcs_length(a: &[u8], b: &[u8]) -> usize {
+    let mut prev = vec![0usize; b.len() + 1];
+    let mut curr = vec![0usize; b.len() + 1];
+    for &byte_a in a {
+        for (j, &byte_b) in b.iter().enumerate() {
+            curr[j + 1] = if byte_a == byte_b {
+                prev[j] + 1
+            } else {
+                prev[j + 1].max(curr[j])
+            };
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+fn token_overlap_score(a: &str, b: &str) -> f64 {
+    let a_tokens: HashSet<_> = a.split_whitespace().collect();
+    let b_tokens: HashSet<_> = b.split_whitespace().collect();
+    if a_tokens.is_empty() || b_tokens.is_empty() {
+        return 0.0;
+    }
+    let inter = a_tokens.intersection(&b_tokens).count() as f64;
+    let union = a_tokens.union(&b_tokens).count() as f64;
+    if union == 0.0 { 0.0 } else { inter / union }
+}
+
+fn prefix_score(a: &str, b: &str) -> f64 {
+    let max_len = a.len().max(b.len());
+    if max_len == 0 {
+        return 0.0;
+    }
+    let mut count = 0;
+    for (ch_a, ch_b) in a.chars().zip(b.chars()) {
+        if ch_a == ch_b {
+            count += 1;
+        } else {
+            break;
+        }
+    }
+    count as f64 / max_len as f64
+}
+
+fn suffix_score(a: &str, b: &str) -> f64 {
+    let a_rev: String = a.chars().rev().collect();
+    let b_rev: String = b.chars().rev().collect();
+    prefix_score(&a_rev, &b_rev)
+}
+
+fn bigram_dice_score(a: &str, b: &str) -> f64 {
+    fn grams(value: &str) -> HashSet<String> {
+        let chars: Vec<char> = value.chars().collect();
+        if chars.len() < 2 {
+            return chars.into_iter().map(|c| c.to_string()).collect();
+        }
+        (0..chars.len() - 1)
+            .map(|i| format!("{}{}", chars[i], chars[i + 1]))
+            .collect()
+    }
+    let set_a = grams(a);
+    let set_b = grams(b);
+    if set_a.is_empty() || set_b.is_empty() {
+        return 0.0;
+    }
+    let intersection = set_a.intersection(&set_b).count() as f64;
+    (2.0 * intersection) / (set_a.len() as f64 + set_b.len() as f64)
+}
+
+fn length_ratio_score(a: &str, b: &str) -> f64 {
+    let max_len = a.len().max(b.len());
+    if max_len == 0 {
+        return 0.0;
+    }
+    1.0 - (a.len().abs_diff(b.len()) as f64 / max_len as f64)
+}
@@ -0,0 +1,93 @@
+use anyhow::{Context, Result};
+use rusqlite::{Connection, params};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::{AuthorEntry, effective_author_id};
+
+pub const LIBRARY_TABLE: &str = "library_authors";
+
+/// Records which author folder under `root` holds each author_id, so the sorter
+/// can route new books into the existing folder instead of inventing a name.
+/// Rows for folders that no longer exist are dropped; other rows are upserted.
+pub fn write_library_authors(
+    db_path: &Path,
+    root: &Path,
+    authors: &[AuthorEntry],
+    probable_threshold: f64,
+) -> Result<usize> {
+    let mut connection = Connection::open(db_path)
+        .with_context(|| format!("Impossible d'ouvrir la base {:?}", db_path))?;
+    connection.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {LIBRARY_TABLE} (
+             folder TEXT PRIMARY KEY,
+             author_id TEXT NOT NULL,
+             match_kind TEXT NOT NULL,
+             verified_at TEXT NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS idx_{LIBRARY_TABLE}_author ON {LIBRARY_TABLE}(author_id);"
+    ))?;
+    let tx = connection.transaction()?;
+    let stale: Vec<String> = {
+        let mut stmt = tx.prepare(&format!("SELECT folder FROM {LIBRARY_TABLE}"))?;
+        let folders = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        folders
+            .into_iter()
+            .filter(|folder| !root.join(folder).is_dir())
+            .collect()
+    };
+    for folder in &stale {
+        tx.execute(
+            &format!("DELETE FROM {LIBRARY_TABLE} WHERE folder = ?1"),
+            [folder],
+        )?;
+    }
+
+    let verified_at = chrono::Utc::now().to_rfc3339();
+    let mut written = 0usize;
+    for entry in authors {
+        // Folders left in secondary `--root`s are not part of the library.
+        if !entry.path.is_dir() || entry.path.parent() != Some(root) {
+            continue;
+        }
+        let Some(author_id) = effective_author_id(entry, probable_threshold) else {
+            continue;
+        };
+        let match_kind = if entry.author_id.is_some() {
+            "exact"
+        } else {
+            "probable"
+        };
+        tx.execute(
+            &format!(
+                "INSERT OR REPLACE INTO {LIBRARY_TABLE} (folder, author_id, match_kind, verified_at)
+                 VALUES (?1, ?2, ?3, ?4)"
+            ),
+            params![entry.name, author_id, match_kind, verified_at],
+        )?;
+        written += 1;
+    }
+    tx.commit()?;
+    Ok(written)
+}
+
+/// `folder -> author_id` rows recorded by a previous run; empty when the table is absent.
+pub fn read_library_authors(db_path: &Path) -> Result<HashMap<String, String>> {
+    let connection = Connection::open(db_path)
+        .with_context(|| format!("Impossible d'ouvrir la base {:?}", db_path))?;
+    let exists: bool = connection.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        [LIBRARY_TABLE],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Ok(HashMap::new());
+    }
+    let mut stmt = connection.prepare(&format!("SELECT folder, author_id FROM {LIBRARY_TABLE}"))?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+    Ok(rows)
+}
@@ -0,0 +1,76 @@
+use anyhow::{Context, Result, bail};
+use sortbook_common::say;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Same name as in the sorter, so `cleanup` and `sortbook` exclude each other.
+pub const LOCK_FILE_NAME: &str = ".sortbook.lock";
+/// A lock whose owner cannot be checked is considered abandoned after this long.
+const STALE_AFTER: Duration = Duration::from_secs(24 * 3600);
+
+/// Lock file held in a library directory for the duration of a run; removed on drop.
+pub struct RunLock {
+    path: PathBuf,
+}
+
+impl RunLock {
+    /// Creates `<dir>/.sortbook.lock`. An existing lock is replaced only when stale
+    /// (owner process gone, or older than `STALE_AFTER` where `/proc` is unavailable).
+    pub fn acquire(dir: &Path, tool: &str) -> Result<Self> {
+        let path = dir.join(LOCK_FILE_NAME);
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    writeln!(
+                        file,
+                        "pid={}\ntool={}\nstarted={}",
+                        std::process::id(),
+                        tool,
+                        chrono::Local::now().to_rfc3339()
+                    )?;
+                    return Ok(Self { path });
+                }
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                    let contents = fs::read_to_string(&path).unwrap_or_default();
+                    if !is_stale(&path, &contents) {
+                        bail!(
+                            "{:?} is held by another run ({}); remove it if no run is active.",
+                            path,
+                            contents.trim().replace('\n', ", ")
+                        );
+                    }
+                    say!("Removing stale lock {:?}.", path);
+                    fs::remove_file(&path).with_context(|| format!("Remove {:?}", path))?;
+                }
+                Err(err) => return Err(err).with_context(|| format!("Create {:?}", path)),
+            }
+        }
+        bail!("Could not acquire {:?}.", path)
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+fn is_stale(path: &Path, contents: &str) -> bool {
+    let pid = contents
+        .lines()
+        .find_map(|line| line.strip_prefix("pid="))
+        .and_then(|pid| pid.trim().parse::<u32>().ok());
+    let proc_dir = Path::new("/proc");
+    if let Some(pid) = pid
+        && proc_dir.is_dir()
+    {
+        return !proc_dir.join(pid.to_string()).exists();
+    }
+    fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_none_or(|age| age > STALE_AFTER)
+}
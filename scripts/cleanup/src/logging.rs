@@ -0,0 +1,39 @@
+use simplelog::{ConfigBuilder, LevelFilter, WriteLogger};
+use sortbook_common::say;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::journal::DEFAULT_JOURNAL_DIR;
+
+/// Same file logger as the sorter: `--log-file` or `--debug` (defaulting to
+/// `logs/cleanup.log`) enables it; otherwise nothing is logged beyond stdout.
+pub fn init(debug: bool, log_file: Option<&Path>) {
+    if !debug && log_file.is_none() {
+        return;
+    }
+    let log_path = log_file
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_JOURNAL_DIR).join("cleanup.log"));
+    if let Some(parent) = log_path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    match fs::File::create(&log_path) {
+        Ok(file) => {
+            let cfg = ConfigBuilder::new()
+                .set_time_format_rfc3339()
+                .set_target_level(LevelFilter::Off)
+                .build();
+            let level = if debug {
+                LevelFilter::Debug
+            } else {
+                LevelFilter::Info
+            };
+            if let Err(e) = WriteLogger::init(level, cfg, file) {
+                eprintln!("[warn] file logger init failed: {e}");
+            } else {
+                say!("[log] logs → {:?}", log_path);
+            }
+        }
+        Err(e) => eprintln!("[warn] cannot create log file {:?}: {e}", log_path),
+    }
+}
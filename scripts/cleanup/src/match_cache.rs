@@ -0,0 +1,142 @@
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OptionalExtension, params};
+use sortbook_common::say;
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::Suggestion;
+
+/// Result of matching one folder name: exact hit, probable suggestion, or neither.
+#[derive(Debug, Clone)]
+pub struct CachedMatch {
+    pub author_id: Option<String>,
+    pub author_name_db: Option<String>,
+    pub probable: Option<Suggestion>,
+}
+
+/// SQLite cache of match results keyed by normalized folder name.
+/// Entries are dropped whenever the OpenLibrary database file changes.
+pub struct MatchCache {
+    connection: Option<Connection>,
+    hits: usize,
+}
+
+impl MatchCache {
+    pub fn disabled() -> Self {
+        Self {
+            connection: None,
+            hits: 0,
+        }
+    }
+
+    pub fn open(path: &Path, db_path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let connection =
+            Connection::open(path).with_context(|| format!("Open match cache {:?}", path))?;
+        connection.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             PRAGMA synchronous = NORMAL;
+             CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+             CREATE TABLE IF NOT EXISTS matches (
+                 name_key TEXT PRIMARY KEY,
+                 author_id TEXT,
+                 author_name_db TEXT,
+                 probable TEXT,
+                 updated_at TEXT NOT NULL
+             );",
+        )?;
+        let stamp = db_stamp(db_path);
+        let stored: Option<String> = connection
+            .query_row("SELECT value FROM meta WHERE key = 'db_stamp'", [], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        if stored.as_deref() != Some(stamp.as_str()) {
+            if stored.is_some() {
+                say!("Database changed since the match cache was built; clearing it.");
+            }
+            connection.execute("DELETE FROM matches", [])?;
+            connection.execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES ('db_stamp', ?1)",
+                [&stamp],
+            )?;
+        }
+        Ok(Self {
+            connection: Some(connection),
+            hits: 0,
+        })
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    pub fn get(&mut self, name_key: &str) -> Result<Option<CachedMatch>> {
+        let Some(connection) = &self.connection else {
+            return Ok(None);
+        };
+        let row: Option<(Option<String>, Option<String>, Option<String>)> = connection
+            .query_row(
+                "SELECT author_id, author_name_db, probable FROM matches WHERE name_key = ?1",
+                [name_key],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+        let Some((author_id, author_name_db, probable)) = row else {
+            return Ok(None);
+        };
+        let probable = match probable {
+            Some(json) => match serde_json::from_str(&json) {
+                Ok(suggestion) => Some(suggestion),
+                Err(_) => return Ok(None),
+            },
+            None => None,
+        };
+        self.hits += 1;
+        Ok(Some(CachedMatch {
+            author_id,
+            author_name_db,
+            probable,
+        }))
+    }
+
+    pub fn put(&self, name_key: &str, entry: &CachedMatch) -> Result<()> {
+        let Some(connection) = &self.connection else {
+            return Ok(());
+        };
+        let probable = entry
+            .probable
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        connection.execute(
+            "INSERT OR REPLACE INTO matches (name_key, author_id, author_name_db, probable, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                name_key,
+                entry.author_id,
+                entry.author_name_db,
+                probable,
+                chrono::Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+/// Size and modification time of the database file, used to detect a reimport.
+fn db_stamp(db_path: &Path) -> String {
+    let Ok(metadata) = fs::metadata(db_path) else {
+        return String::new();
+    };
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    format!("{}:{}", metadata.len(), modified)
+}
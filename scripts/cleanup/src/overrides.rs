@@ -0,0 +1,82 @@
+use anyhow::{Context, Result, anyhow};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::{normalize_author_display, sanitize_component};
+
+/// Operator corrections loaded from a CSV with header `folder,author_id,canonical_name`.
+/// Either value may be blank; a row needs at least one of them.
+#[derive(Debug, Clone)]
+pub struct Override {
+    pub author_id: Option<String>,
+    pub canonical_name: Option<String>,
+}
+
+#[derive(Default)]
+pub struct Overrides {
+    by_folder: HashMap<String, Override>,
+    rows: usize,
+}
+
+impl Overrides {
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_path(path)
+            .with_context(|| format!("Open overrides {:?}", path))?;
+        let headers = reader.headers()?.clone();
+        let column = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+        let folder_col = column("folder")
+            .ok_or_else(|| anyhow!("Overrides {:?}: missing `folder` column", path))?;
+        let id_col = column("author_id");
+        let name_col = column("canonical_name");
+
+        let mut overrides = Overrides::default();
+        for record in reader.records() {
+            let record = record?;
+            let value = |col: Option<usize>| {
+                col.and_then(|c| record.get(c))
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+                    .map(str::to_string)
+            };
+            let Some(folder) = value(Some(folder_col)) else {
+                continue;
+            };
+            let entry = Override {
+                author_id: value(id_col),
+                canonical_name: value(name_col),
+            };
+            if entry.author_id.is_none() && entry.canonical_name.is_none() {
+                continue;
+            }
+            overrides.rows += 1;
+            // Also reachable under the names the folder takes after normalization
+            // or after being renamed to its canonical name.
+            let mut keys = vec![
+                folder.clone(),
+                sanitize_component(&normalize_author_display(&folder)),
+            ];
+            if let Some(canonical) = &entry.canonical_name {
+                keys.push(sanitize_component(canonical));
+            }
+            for key in keys {
+                overrides
+                    .by_folder
+                    .entry(key.to_lowercase())
+                    .or_insert_with(|| entry.clone());
+            }
+        }
+        Ok(overrides)
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn get(&self, folder: &str) -> Option<&Override> {
+        self.by_folder.get(&folder.trim().to_lowercase())
+    }
+}
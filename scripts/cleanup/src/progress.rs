@@ -0,0 +1,15 @@
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Progress bar for one pipeline phase (drawn on stderr, hidden when it is not a
+/// terminal), with the phase name as prefix and an ETA.
+pub fn phase_bar(phase: &'static str, len: usize) -> ProgressBar {
+    let pb = ProgressBar::new(len as u64);
+    pb.set_style(
+        ProgressStyle::with_template(
+            "{prefix:>10} [{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} ETA {eta} {msg}",
+        )
+        .unwrap(),
+    );
+    pb.set_prefix(phase);
+    pb
+}
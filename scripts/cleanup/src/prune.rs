@@ -0,0 +1,76 @@
+use anyhow::{Context, Result};
+use sortbook_common::say;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::exclude::ExcludeList;
+use crate::journal::{Journal, JournalOp};
+use crate::lock::LOCK_FILE_NAME;
+use crate::{is_trash_dir, op_event};
+
+/// Removes author folders that hold no file at any depth (left behind by merges)
+/// and lists orphan files sitting directly in the root.
+pub fn prune(
+    root: &Path,
+    exclude: &ExcludeList,
+    journal: &mut Journal,
+    dry_run: bool,
+) -> Result<()> {
+    say!(
+        "Pruning {}{:?}",
+        if dry_run { "(dry-run) " } else { "" },
+        root
+    );
+    let mut entries: Vec<PathBuf> = fs::read_dir(root)
+        .with_context(|| format!("Read {:?}", root))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect();
+    entries.sort();
+
+    let mut removed = 0usize;
+    let mut orphans = Vec::new();
+    for path in entries {
+        if path.is_file() {
+            if !path.ends_with(LOCK_FILE_NAME) {
+                orphans.push(path);
+            }
+            continue;
+        }
+        if !path.is_dir() || is_trash_dir(&path) || exclude.is_protected(&path) {
+            continue;
+        }
+        if !is_empty_tree(&path) {
+            continue;
+        }
+        op_event("remove", &path, None, dry_run);
+        if dry_run {
+            say!("[DRY-RUN] remove empty {}", path.display());
+        } else {
+            fs::remove_dir_all(&path).with_context(|| format!("Remove {:?}", path))?;
+            journal.record(JournalOp::RemoveDir, &path, None)?;
+            say!("Removed empty {}", path.display());
+        }
+        removed += 1;
+    }
+
+    for orphan in &orphans {
+        say!("  orphan file: {}", orphan.display());
+    }
+    say!(
+        "Prune done: {} empty folder(s) {}, {} orphan file(s) in the root.",
+        removed,
+        if dry_run { "to remove" } else { "removed" },
+        orphans.len()
+    );
+    Ok(())
+}
+
+/// True when the directory contains only (possibly nested) empty directories.
+fn is_empty_tree(path: &Path) -> bool {
+    WalkDir::new(path)
+        .min_depth(1)
+        .into_iter()
+        .all(|entry| entry.map(|e| e.file_type().is_dir()).unwrap_or(false))
+}
@@ -0,0 +1,222 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::stats::{self, MergeStats, ScoreBucket};
+use crate::{AuthorEntry, Suggestion};
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalizeAction {
+    Rename,
+    Merge,
+}
+
+/// One folder touched by `normalize_directories`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Normalization {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub action: NormalizeAction,
+}
+
+/// One folder participating in an author_id merge.
+#[derive(Debug, Clone, Serialize)]
+pub struct MergeMember {
+    pub path: PathBuf,
+    pub file_count: usize,
+    pub alignment_score: f64,
+    /// `None` for an exact author_id match, the suggestion score otherwise.
+    pub probable_score: Option<f64>,
+}
+
+/// A merge group: every member folder is merged into `destination`
+/// (the first member, kept in place).
+#[derive(Debug, Clone, Serialize)]
+pub struct MergeGroup {
+    pub author_id: String,
+    pub db_name: Option<String>,
+    pub destination: PathBuf,
+    pub members: Vec<MergeMember>,
+}
+
+#[derive(Serialize)]
+struct SuggestionReport<'a> {
+    author_id: &'a str,
+    display_name: &'a str,
+    avg_score: f64,
+    seq_score: Option<f64>,
+    scores: &'a BTreeMap<String, f64>,
+}
+
+#[derive(Serialize)]
+struct AuthorReport<'a> {
+    folder: &'a str,
+    path: &'a Path,
+    author_id: Option<&'a str>,
+    author_name_db: Option<&'a str>,
+    suggestion: Option<SuggestionReport<'a>>,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    generated_at: String,
+    root: &'a Path,
+    dry_run: bool,
+    probable_threshold: f64,
+    normalizations: &'a [Normalization],
+    authors: Vec<AuthorReport<'a>>,
+    merges: &'a [MergeGroup],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    simulation: Option<&'a MergeStats>,
+    score_histogram: Vec<ScoreBucket>,
+}
+
+pub struct ReportInput<'a> {
+    pub root: &'a Path,
+    pub dry_run: bool,
+    pub probable_threshold: f64,
+    pub normalizations: &'a [Normalization],
+    pub authors: &'a [AuthorEntry],
+    pub merges: &'a [MergeGroup],
+    /// Dry-run merge totals (`None` outside dry-run).
+    pub simulation: Option<&'a MergeStats>,
+}
+
+pub fn write_json_report(path: &Path, input: &ReportInput) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let report = Report {
+        generated_at: chrono::Utc::now().to_rfc3339(),
+        root: input.root,
+        dry_run: input.dry_run,
+        probable_threshold: input.probable_threshold,
+        normalizations: input.normalizations,
+        authors: input.authors.iter().map(author_report).collect(),
+        merges: input.merges,
+        simulation: input.simulation,
+        score_histogram: stats::score_histogram(input.authors),
+    };
+    let file = fs::File::create(path).with_context(|| format!("Create report {:?}", path))?;
+    serde_json::to_writer_pretty(file, &report)?;
+    Ok(())
+}
+
+fn author_report(entry: &AuthorEntry) -> AuthorReport<'_> {
+    AuthorReport {
+        folder: &entry.name,
+        path: &entry.path,
+        author_id: entry.author_id.as_deref(),
+        author_name_db: entry.author_name_db.as_deref(),
+        suggestion: entry.probable.as_ref().map(suggestion_report),
+    }
+}
+
+fn suggestion_report(suggestion: &Suggestion) -> SuggestionReport<'_> {
+    SuggestionReport {
+        author_id: &suggestion.author_id,
+        display_name: &suggestion.display_name,
+        avg_score: suggestion.avg_score,
+        seq_score: suggestion.seq_score,
+        scores: &suggestion.per_metric,
+    }
+}
+
+/// Static HTML preview of a dry-run: one table per author_id merge group,
+/// followed by the planned folder renames.
+pub fn write_html_plan(path: &Path, input: &ReportInput) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut html = String::new();
+    html.push_str(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>cleanup merge plan</title>\n\
+         <style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse;margin-bottom:1.5em}\
+         td,th{border:1px solid #ccc;padding:4px 8px;text-align:left}th{background:#eee}\
+         .dest{font-weight:bold}.low{color:#b00}</style></head><body>\n",
+    );
+    html.push_str(&format!(
+        "<h1>Merge plan for {}</h1>\n<p>{} merge group(s), {} folder rename(s). Probable threshold {:.2}.</p>\n",
+        escape_html(&input.root.display().to_string()),
+        input.merges.len(),
+        input.normalizations.len(),
+        input.probable_threshold
+    ));
+
+    html.push_str("<h2>Merges by author_id</h2>\n");
+    if input.merges.is_empty() {
+        html.push_str("<p>No merges planned.</p>\n");
+    }
+    for group in input.merges {
+        let destination = folder_name(&group.destination);
+        html.push_str(&format!(
+            "<h3>{} — {}</h3>\n<table><tr><th>Before</th><th>After</th><th>Files</th>\
+             <th>Alignment</th><th>Match</th></tr>\n",
+            escape_html(&group.author_id),
+            escape_html(group.db_name.as_deref().unwrap_or("(no DB name)"))
+        ));
+        for member in &group.members {
+            let is_destination = member.path == group.destination;
+            let match_cell = match member.probable_score {
+                Some(score) => format!("<span class=\"low\">probable {score:.2}</span>"),
+                None => "exact".to_string(),
+            };
+            html.push_str(&format!(
+                "<tr{}><td>{}</td><td>{}</td><td>{}</td><td>{:.2}</td><td>{}</td></tr>\n",
+                if is_destination {
+                    " class=\"dest\""
+                } else {
+                    ""
+                },
+                escape_html(&folder_name(&member.path)),
+                if is_destination {
+                    "(kept)".to_string()
+                } else {
+                    escape_html(&destination)
+                },
+                member.file_count,
+                member.alignment_score,
+                match_cell
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    if !input.normalizations.is_empty() {
+        html.push_str(
+            "<h2>Folder normalization</h2>\n<table><tr><th>Before</th><th>After</th><th>Action</th></tr>\n",
+        );
+        for item in input.normalizations {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape_html(&folder_name(&item.from)),
+                escape_html(&folder_name(&item.to)),
+                match item.action {
+                    NormalizeAction::Rename => "rename",
+                    NormalizeAction::Merge => "merge",
+                }
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str("</body></html>\n");
+    fs::write(path, html).with_context(|| format!("Write HTML plan {:?}", path))?;
+    Ok(())
+}
+
+fn folder_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
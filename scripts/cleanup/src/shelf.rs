@@ -0,0 +1,178 @@
+use anyhow::{Context, Result};
+use author_alias_online::wikidata::{self, BlockingWikidata};
+use clap::ValueEnum;
+use rusqlite::{Connection, OptionalExtension, params};
+use sortbook_common::say;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::{AuthorEntry, effective_author_id, sanitize_component};
+
+/// Wikidata values looked up per author_id and property, reused on later runs.
+pub const GROUPS_TABLE: &str = "author_groups";
+/// Shelf of folders without an author_id or without the Wikidata claim.
+const UNKNOWN_GROUP: &str = "Unknown";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    /// Languages spoken or written (P1412), first claim.
+    Language,
+    /// Country of citizenship (P27), first claim.
+    Country,
+}
+
+impl GroupBy {
+    fn property(self) -> &'static str {
+        match self {
+            GroupBy::Language => "P1412",
+            GroupBy::Country => "P27",
+        }
+    }
+}
+
+/// Group label of every author folder (keyed by folder name). Values come from
+/// `author_groups` when present, otherwise from Wikidata (search on the DB name,
+/// then the entity's claim), and are stored back so later runs stay offline.
+pub fn resolve_groups(
+    db_path: &Path,
+    authors: &[AuthorEntry],
+    group_by: GroupBy,
+    probable_threshold: f64,
+    online: &BlockingWikidata,
+) -> Result<HashMap<String, String>> {
+    let connection = Connection::open(db_path)
+        .with_context(|| format!("Impossible d'ouvrir la base {:?}", db_path))?;
+    connection.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {GROUPS_TABLE} (
+             author_id TEXT NOT NULL,
+             property TEXT NOT NULL,
+             qid TEXT NOT NULL,
+             value TEXT NOT NULL,
+             fetched_at TEXT NOT NULL,
+             PRIMARY KEY (author_id, property)
+         );"
+    ))?;
+    let property = group_by.property();
+    let mut groups = HashMap::new();
+    let mut fetched = 0usize;
+    for entry in authors {
+        let Some(author_id) = effective_author_id(entry, probable_threshold) else {
+            groups.insert(entry.name.clone(), UNKNOWN_GROUP.to_string());
+            continue;
+        };
+        let stored: Option<String> = connection
+            .query_row(
+                &format!("SELECT value FROM {GROUPS_TABLE} WHERE author_id = ?1 AND property = ?2"),
+                params![author_id, property],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let value = match stored {
+            Some(value) => value,
+            None => {
+                let name = entry
+                    .author_name_db
+                    .clone()
+                    .or_else(|| entry.probable.as_ref().map(|p| p.display_name.clone()))
+                    .unwrap_or_else(|| entry.name.clone());
+                // Misses and network errors are not stored, so the next run retries them.
+                let (qid, value) = match lookup(online, &name, property) {
+                    Ok(Some(found)) => found,
+                    Ok(None) => {
+                        groups.insert(entry.name.clone(), UNKNOWN_GROUP.to_string());
+                        continue;
+                    }
+                    Err(e) => {
+                        say!("Wikidata lookup of {:?} failed: {:#}", name, e);
+                        groups.insert(entry.name.clone(), UNKNOWN_GROUP.to_string());
+                        continue;
+                    }
+                };
+                fetched += 1;
+                connection.execute(
+                    &format!(
+                        "INSERT OR REPLACE INTO {GROUPS_TABLE} (author_id, property, qid, value, fetched_at)
+                         VALUES (?1, ?2, ?3, ?4, ?5)"
+                    ),
+                    params![
+                        author_id,
+                        property,
+                        qid,
+                        value,
+                        chrono::Utc::now().to_rfc3339()
+                    ],
+                )?;
+                value
+            }
+        };
+        let label = if value.is_empty() {
+            UNKNOWN_GROUP.to_string()
+        } else {
+            value
+        };
+        groups.insert(entry.name.clone(), label);
+    }
+    if fetched > 0 {
+        say!(
+            "Fetched {} author group(s) from Wikidata into {}.",
+            fetched,
+            GROUPS_TABLE
+        );
+    }
+    Ok(groups)
+}
+
+/// (qid, first claim label); an empty label when the author has no such claim, `None`
+/// when Wikidata has no match for the name.
+fn lookup(
+    online: &BlockingWikidata,
+    name: &str,
+    property: &str,
+) -> Result<Option<(String, String)>> {
+    let query = wikidata::normalize_query(name);
+    let Some((qid, _, _, _)) = online.search(&query)? else {
+        return Ok(None);
+    };
+    let labels = online.claim_labels(&qid, property)?;
+    Ok(Some((qid, labels.into_iter().next().unwrap_or_default())))
+}
+
+/// Alternate layout: `<dir>/<group>/<author folder>` symlinks to the real folders,
+/// leaving the library itself untouched. Existing links are kept.
+pub fn write_shelves(
+    dir: &Path,
+    authors: &[AuthorEntry],
+    groups: &HashMap<String, String>,
+) -> Result<usize> {
+    let mut linked = 0usize;
+    for entry in authors {
+        if !entry.path.is_dir() {
+            continue;
+        }
+        let group = groups
+            .get(&entry.name)
+            .map(String::as_str)
+            .unwrap_or(UNKNOWN_GROUP);
+        let shelf = dir.join(sanitize_component(group));
+        let link = shelf.join(&entry.name);
+        if link.symlink_metadata().is_ok() {
+            continue;
+        }
+        fs::create_dir_all(&shelf)?;
+        let target = fs::canonicalize(&entry.path)?;
+        symlink_dir(&target, &link).with_context(|| format!("Link {:?}", link))?;
+        linked += 1;
+    }
+    Ok(linked)
+}
+
+#[cfg(unix)]
+fn symlink_dir(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink_dir(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_dir(target, link)
+}
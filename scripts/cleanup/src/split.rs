@@ -0,0 +1,221 @@
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OptionalExtension};
+use sortbook_common::say;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::{
+    AuthorEntry, effective_author_id, is_trash_dir, normalize_author_display, strip_accents,
+};
+
+/// Books attributed to other authors a folder needs before it is reported as mixed.
+const MIN_FOREIGN_FILES: usize = 2;
+
+/// One file that should leave its folder.
+struct SplitMove {
+    folder: String,
+    file: String,
+    title: String,
+    author_id: String,
+    author_name: String,
+    proposed_folder: String,
+}
+
+/// Matches every file title against `works` and proposes moving the books of other
+/// authors out of folders holding several authors. Only writes a CSV plan.
+pub fn write_split_plan(
+    db_path: &Path,
+    path: &Path,
+    authors: &[AuthorEntry],
+    probable_threshold: f64,
+) -> Result<usize> {
+    let connection = Connection::open(db_path)
+        .with_context(|| format!("Impossible d'ouvrir la base {:?}", db_path))?;
+    let has_works: bool = connection.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'works')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_works {
+        say!(
+            "No `works` table in {:?}; split detection skipped.",
+            db_path
+        );
+        return Ok(0);
+    }
+    let mut work_cache: HashMap<String, Option<(String, String)>> = HashMap::new();
+    let mut name_cache: HashMap<String, Option<String>> = HashMap::new();
+    let mut moves = Vec::new();
+    let mut mixed_folders = 0usize;
+
+    for entry in authors {
+        if !entry.path.is_dir() {
+            continue;
+        }
+        let mut matched: Vec<(String, String, String)> = Vec::new();
+        for file in WalkDir::new(&entry.path)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|e| !is_trash_dir(e.path()))
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+        {
+            let rel = file
+                .path()
+                .strip_prefix(&entry.path)
+                .unwrap_or(file.path())
+                .to_string_lossy()
+                .to_string();
+            let key = normalize_title(&title_from_file(file.path()));
+            if key.is_empty() {
+                continue;
+            }
+            let hit = match work_cache.get(&key) {
+                Some(cached) => cached.clone(),
+                None => {
+                    let hit = lookup_work(&connection, &key)?;
+                    work_cache.insert(key, hit.clone());
+                    hit
+                }
+            };
+            if let Some((title, author_id)) = hit {
+                matched.push((rel, title, author_id));
+            }
+        }
+
+        let own_ids: Vec<&str> = match effective_author_id(entry, probable_threshold) {
+            Some(id) => std::iter::once(id)
+                .chain(entry.alternate_ids.iter().map(String::as_str))
+                .collect(),
+            None => dominant_author(&matched).into_iter().collect(),
+        };
+        let foreign: Vec<&(String, String, String)> = matched
+            .iter()
+            .filter(|(_, _, author_id)| !own_ids.contains(&author_id.as_str()))
+            .collect();
+        if foreign.len() < MIN_FOREIGN_FILES {
+            continue;
+        }
+        mixed_folders += 1;
+        say!(
+            "Mixed folder {:?}: {} of {} matched book(s) belong to other authors.",
+            entry.name,
+            foreign.len(),
+            matched.len()
+        );
+        for (file, title, author_id) in foreign {
+            let author_name = match name_cache.get(author_id) {
+                Some(cached) => cached.clone(),
+                None => {
+                    let name = lookup_author_name(&connection, author_id)?;
+                    name_cache.insert(author_id.clone(), name.clone());
+                    name
+                }
+            }
+            .unwrap_or_default();
+            moves.push(SplitMove {
+                folder: entry.name.clone(),
+                file: file.clone(),
+                title: title.clone(),
+                author_id: author_id.clone(),
+                proposed_folder: normalize_author_display(&author_name),
+                author_name,
+            });
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(true)
+        .quote_style(csv::QuoteStyle::Necessary)
+        .from_path(path)?;
+    writer.write_record([
+        "folder",
+        "file",
+        "work_title",
+        "author_id",
+        "author_name_db",
+        "proposed_folder",
+    ])?;
+    for item in &moves {
+        writer.write_record([
+            &item.folder,
+            &item.file,
+            &item.title,
+            &item.author_id,
+            &item.author_name,
+            &item.proposed_folder,
+        ])?;
+    }
+    writer.flush()?;
+    Ok(mixed_folders)
+}
+
+/// Sorted files are named `<title> - <first> <last>.<ext>`; keep the title part.
+fn title_from_file(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    match stem.rsplit_once(" - ") {
+        Some((title, _)) => title.to_string(),
+        None => stem,
+    }
+}
+
+/// Same rules as `title_normalized` in `import_works_sqlite.py`.
+fn normalize_title(value: &str) -> String {
+    strip_accents(&value.to_lowercase())
+        .chars()
+        .filter(|ch| {
+            ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch.is_whitespace() || *ch == '-'
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// (title, author_id) of the work, skipping titles shared by several works
+/// (non-empty `alternate_id`), which would attribute books at random.
+fn lookup_work(connection: &Connection, title_norm: &str) -> Result<Option<(String, String)>> {
+    let row: Option<(String, Option<String>, Option<String>)> = connection
+        .query_row(
+            "SELECT title, author_id, alternate_id FROM works WHERE title_normalized = ?1",
+            [title_norm],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    Ok(row.and_then(|(title, author_id, alternates)| {
+        let author_id = author_id.filter(|id| !id.is_empty())?;
+        alternates
+            .is_none_or(|alt| alt.is_empty())
+            .then_some((title, author_id))
+    }))
+}
+
+fn lookup_author_name(connection: &Connection, author_id: &str) -> Result<Option<String>> {
+    Ok(connection
+        .query_row(
+            "SELECT name FROM authors WHERE author_id = ?1 LIMIT 1",
+            [author_id],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+/// Most frequent author among the matched books, used when the folder itself has no author_id.
+fn dominant_author(matched: &[(String, String, String)]) -> Option<&str> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for (_, _, author_id) in matched {
+        *counts.entry(author_id.as_str()).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+        .map(|(id, _)| id)
+}
@@ -0,0 +1,129 @@
+use serde::Serialize;
+use sortbook_common::say;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::AuthorEntry;
+
+const HISTOGRAM_FLOOR: f64 = 0.5;
+const HISTOGRAM_STEP: f64 = 0.05;
+const HISTOGRAM_BAR: usize = 40;
+
+/// Totals collected while simulating merges in dry-run.
+#[derive(Debug, Default, Serialize)]
+pub struct MergeStats {
+    pub folders_merged: usize,
+    pub files_moved: usize,
+    pub duplicates_removed: usize,
+    pub bytes_reclaimed: u64,
+    /// Files that would already sit in a target once earlier simulated merges ran.
+    #[serde(skip)]
+    pub planned: HashMap<PathBuf, u64>,
+    /// Files that earlier simulated merges would discard (`--keep format` rivals).
+    #[serde(skip)]
+    pub removed: HashSet<PathBuf>,
+}
+
+impl MergeStats {
+    /// Size of `path` once the simulated merges so far ran; `None` when it would not be there.
+    pub fn size(&self, path: &Path) -> Option<u64> {
+        match self.planned.get(path) {
+            Some(size) => Some(*size),
+            None if self.removed.contains(path) => None,
+            None => path.metadata().ok().map(|m| m.len()),
+        }
+    }
+}
+
+/// Count of probable suggestions whose score falls in `[lower, upper)`.
+#[derive(Debug, Serialize)]
+pub struct ScoreBucket {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: usize,
+}
+
+/// Suggestion scores of folders without an exact author_id, bucketed by 0.05 from 0.50
+/// (everything lower lands in the first bucket).
+pub fn score_histogram(authors: &[AuthorEntry]) -> Vec<ScoreBucket> {
+    let bucket_count = ((1.0 - HISTOGRAM_FLOOR) / HISTOGRAM_STEP).round() as usize;
+    let mut buckets: Vec<ScoreBucket> = (0..=bucket_count)
+        .map(|index| {
+            let lower = if index == 0 {
+                0.0
+            } else {
+                round_cents(HISTOGRAM_FLOOR + (index - 1) as f64 * HISTOGRAM_STEP)
+            };
+            ScoreBucket {
+                lower,
+                upper: round_cents(HISTOGRAM_FLOOR + index as f64 * HISTOGRAM_STEP).min(1.0),
+                count: 0,
+            }
+        })
+        .collect();
+    for entry in authors {
+        if entry.author_id.is_some() {
+            continue;
+        }
+        let Some(probable) = &entry.probable else {
+            continue;
+        };
+        let score = probable.score();
+        let index = if score < HISTOGRAM_FLOOR {
+            0
+        } else {
+            (((score - HISTOGRAM_FLOOR) / HISTOGRAM_STEP).floor() as usize + 1).min(bucket_count)
+        };
+        buckets[index].count += 1;
+    }
+    buckets
+}
+
+pub fn print_summary(stats: &MergeStats, histogram: &[ScoreBucket], threshold: f64) {
+    say!("Dry-run summary:");
+    say!("  folders merged:     {}", stats.folders_merged);
+    say!("  files moved:        {}", stats.files_moved);
+    say!("  duplicates removed: {}", stats.duplicates_removed);
+    say!(
+        "  bytes reclaimed:    {} ({})",
+        stats.bytes_reclaimed,
+        human_bytes(stats.bytes_reclaimed)
+    );
+    let max = histogram.iter().map(|b| b.count).max().unwrap_or(0);
+    if max == 0 {
+        say!("  no probable suggestions to chart.");
+        return;
+    }
+    say!("Probable suggestion scores (threshold {threshold:.2}):");
+    for bucket in histogram.iter().rev() {
+        let width = (bucket.count * HISTOGRAM_BAR).div_ceil(max);
+        let line = format!(
+            "  {:.2}-{:.2} {:>6} {}{}",
+            bucket.lower,
+            bucket.upper,
+            bucket.count,
+            "#".repeat(width),
+            if bucket.lower >= threshold {
+                "  (>= threshold)"
+            } else {
+                ""
+            }
+        );
+        say!("{}", line.trim_end());
+    }
+}
+
+fn round_cents(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
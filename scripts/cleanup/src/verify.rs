@@ -0,0 +1,153 @@
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OptionalExtension};
+use sortbook_common::say;
+
+use crate::exclude::ExcludeList;
+use crate::match_cache::MatchCache;
+use crate::overrides::Overrides;
+use crate::{
+    Cli, collect_author_dirs, effective_author_id, library, match_and_fill,
+    normalize_author_display, sanitize_component,
+};
+
+/// Read-only pass: matches every folder again and reports those whose name drifted
+/// from their recorded (`library_authors`) or matched DB author, with the folder name
+/// the DB suggests. Recorded folders that disappeared are listed too.
+pub fn verify(cli: &Cli) -> Result<usize> {
+    say!(
+        "Verifying {:?} against {:?} (no changes)",
+        cli.roots,
+        cli.db
+    );
+    let exclude = match &cli.exclude_file {
+        Some(path) => ExcludeList::load(path)?,
+        None => ExcludeList::empty(),
+    };
+    let overrides = match &cli.overrides {
+        Some(path) => Overrides::load(path)?,
+        None => Overrides::default(),
+    };
+    let mut authors = Vec::new();
+    for root in &cli.roots {
+        authors.extend(collect_author_dirs(root)?);
+    }
+    let mut match_cache = match &cli.match_cache {
+        Some(path) => MatchCache::open(path, &cli.db)?,
+        None => MatchCache::disabled(),
+    };
+    match_and_fill(
+        &cli.db,
+        &mut authors,
+        &overrides,
+        &mut match_cache,
+        cli.expand_initials,
+        cli.identity,
+    )?;
+    let recorded = library::read_library_authors(&cli.db)?;
+    let connection = Connection::open(&cli.db)
+        .with_context(|| format!("Impossible d'ouvrir la base {:?}", cli.db))?;
+
+    let mut drifted = 0usize;
+    for entry in &authors {
+        if exclude.is_protected(&entry.path) {
+            continue;
+        }
+        let recorded_id = recorded.get(&entry.name).map(String::as_str);
+        let matched_id = effective_author_id(entry, cli.probable_threshold);
+        let Some(author_id) = recorded_id.or(matched_id) else {
+            continue;
+        };
+        let mut reasons = Vec::new();
+        if let Some(recorded_id) = recorded_id
+            && matched_id != Some(recorded_id)
+        {
+            reasons.push(format!(
+                "recorded as {}, name now matches {}",
+                recorded_id,
+                matched_id.unwrap_or("no author")
+            ));
+        }
+        let Some(db_name) = author_name(&connection, author_id)? else {
+            say!(
+                "{:?}: author_id {} not found in the DB",
+                entry.name,
+                author_id
+            );
+            drifted += 1;
+            continue;
+        };
+        let expected = sanitize_component(&normalize_author_display(&db_name));
+        if !expected.is_empty() && expected != entry.name {
+            reasons.push(format!("DB name is {:?}", db_name));
+        }
+        if reasons.is_empty() {
+            continue;
+        }
+        drifted += 1;
+        say!(
+            "{:?} ({}): {}; suggested folder {:?}",
+            entry.name,
+            author_id,
+            reasons.join("; "),
+            expected
+        );
+    }
+
+    let mut missing: Vec<(&String, &String)> = recorded
+        .iter()
+        .filter(|(folder, _)| !cli.root().join(folder).is_dir())
+        .collect();
+    missing.sort();
+    let missing_count = missing.len();
+    for (folder, author_id) in missing {
+        // Below-threshold suggestions still point at the likely renamed folder.
+        let now = authors
+            .iter()
+            .find(|entry| effective_author_id(entry, cli.probable_threshold) == Some(author_id))
+            .or_else(|| {
+                authors.iter().find(|entry| {
+                    entry.author_id.is_none()
+                        && entry.probable.as_ref().map(|p| p.author_id.as_str()) == Some(author_id)
+                })
+            });
+        match now {
+            Some(entry) => say!(
+                "{:?} ({}): recorded folder is gone; author now matches {:?}{}",
+                folder,
+                author_id,
+                entry.name,
+                match (&entry.author_id, &entry.probable) {
+                    (None, Some(probable)) => format!(" (probable, score {:.2})", probable.score()),
+                    _ => String::new(),
+                }
+            ),
+            None => say!(
+                "{:?} ({}): recorded folder is gone; no folder matches this author",
+                folder,
+                author_id
+            ),
+        }
+    }
+    say!(
+        "Verify done: {} of {} folder(s) drifted, {} recorded folder(s) missing{}.",
+        drifted,
+        authors.len(),
+        missing_count,
+        if recorded.is_empty() {
+            " (no library_authors records; matched authors only)"
+        } else {
+            ""
+        }
+    );
+    Ok(drifted + missing_count)
+}
+
+fn author_name(connection: &Connection, author_id: &str) -> Result<Option<String>> {
+    Ok(connection
+        .query_row(
+            "SELECT name FROM authors WHERE author_id = ?1 LIMIT 1",
+            [author_id],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
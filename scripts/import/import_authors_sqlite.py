@@ -25,6 +25,7 @@ from typing import DefaultDict, List, Tuple
 DB_PATH: str = "../../data/database/openlibrary.sqlite3"
 DUMP_FILE: str = "../../data/dumps/ol_dump_authors.txt"
 TABLE_NAME: str = "authors"
+TRIGRAM_TABLE: str = "authors_trigram"
 
 # Ensure target directory for the database exists (non-destructive)
 os.makedirs(os.path.dirname(DB_PATH), exist_ok=True)
@@ -57,6 +58,8 @@ def normalize_name(name: str) -> str:
 def rebuild_table(conn: sqlite3.Connection) -> None:
     """Drop-and-create authors table. Preserves original behavior."""
     c = conn.cursor()
+    # The trigram index uses authors as external content; drop it with the table
+    c.execute(f"DROP TABLE IF EXISTS {TRIGRAM_TABLE}")
     c.execute(f"DROP TABLE IF EXISTS {TABLE_NAME}")
     c.execute(
         f"""
@@ -71,6 +74,28 @@ def rebuild_table(conn: sqlite3.Connection) -> None:
     conn.commit()
 
 
+def build_trigram_index(conn: sqlite3.Connection, verbose: bool = False) -> None:
+    """Create the FTS5 trigram index used by cleanup for candidate retrieval.
+
+    Skipped (cleanup builds it lazily) when the SQLite build lacks FTS5/trigram.
+    """
+    c = conn.cursor()
+    try:
+        c.execute(
+            f"""
+            CREATE VIRTUAL TABLE {TRIGRAM_TABLE} USING fts5(
+                name_normalized, content='{TABLE_NAME}', content_rowid='rowid', tokenize='trigram'
+            )
+            """
+        )
+        c.execute(f"INSERT INTO {TRIGRAM_TABLE}({TRIGRAM_TABLE}) VALUES('rebuild')")
+        conn.commit()
+    except sqlite3.OperationalError as e:
+        conn.rollback()
+        if verbose:
+            print(f"⚠️ Trigram index skipped ({e})")
+
+
 # === Main import ===
 def import_authors(db_path: str, dump_file: str, verbose: bool = False) -> None:
     """Rebuild authors table and import from the dump file.
@@ -79,6 +104,7 @@ def import_authors(db_path: str, dump_file: str, verbose: bool = False) -> None:
     - Groups authors by normalized name
     - First author id becomes the primary id, others go to alternate_id
     - Recreates index for fast lookups on name_normalized
+    - Builds the authors_trigram FTS5 index when supported
     """
     conn = sqlite3.connect(db_path)
     rebuild_table(conn)
@@ -134,6 +160,7 @@ def import_authors(db_path: str, dump_file: str, verbose: bool = False) -> None:
     # Index for fast lookups (kept as-is; no IF NOT EXISTS to preserve behavior)
     c.execute(f"CREATE INDEX idx_name_norm ON {TABLE_NAME}(name_normalized)")
     conn.commit()
+    build_trigram_index(conn, verbose)
     conn.close()
 
     print(f"✅ Import complete: inserted {len(batch)} authors into {db_path}")
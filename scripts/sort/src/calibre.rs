@@ -0,0 +1,275 @@
+use crate::catalog::{self, display_author, Sources};
+use crate::{author_out_dir, SORTED_DIR};
+use anyhow::{bail, Context, Result};
+use clap::{Args, Subcommand};
+use rusqlite::functions::FunctionFlags;
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use sortbook_common::ledger::{self, BookMeta, Ledger, Op};
+use sortbook_common::sanitize::sanitize_component;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Calibre's database, at the top of a Calibre library folder.
+const METADATA_DB: &str = "metadata.db";
+/// Ledger detail of the copies made by `calibre import`.
+const IMPORT_DETAIL: &str = "calibre";
+
+/// Options of `sortbook calibre`; the project, database and ledger come from the global options.
+#[derive(Args, Debug)]
+pub struct CalibreArgs {
+    #[command(subcommand)]
+    pub action: CalibreAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CalibreAction {
+    /// Copy the books of a Calibre library into the sorted library, under Calibre's authors and titles
+    Import {
+        /// Calibre library folder (holding metadata.db)
+        library: PathBuf,
+        /// Formats to copy, comma separated (default: all)
+        #[arg(long, default_value = "")]
+        exts: String,
+        /// File books into the folder `cleanup --library-authors` recorded for their author, like the sorter's option
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        library_authors: bool,
+        /// Simulate (true) or apply (false); defaults to the global --dry-run, else true
+        #[arg(long, action = clap::ArgAction::Set)]
+        dry_run: Option<bool>,
+    },
+    /// Write the titles and authors the library now has for imported books back into metadata.db
+    Export {
+        /// Calibre library folder the books were imported from
+        library: PathBuf,
+        /// Simulate (true) or apply (false); defaults to the global --dry-run, else true
+        #[arg(long, action = clap::ArgAction::Set)]
+        dry_run: Option<bool>,
+    },
+}
+
+/// One format file of a Calibre book.
+struct CalibreFile {
+    book: i64,
+    title: String,
+    /// `authors.sort` of the first author ("Last, First").
+    author_sort: String,
+    isbn: Option<String>,
+    format: String,
+    path: PathBuf,
+}
+
+pub fn run(args: &CalibreArgs, root: &Path, db: Option<&Path>, ledger: Option<&Path>, global_dry_run: Option<bool>) -> Result<()> {
+    match &args.action {
+        CalibreAction::Import { library, exts, library_authors, dry_run } => {
+            let ledger = ledger.map(Path::to_path_buf).unwrap_or_else(|| root.join(ledger::DEFAULT_LEDGER));
+            import(library, root, db, &ledger, exts, *library_authors, dry_run.or(global_dry_run).unwrap_or(true))
+        }
+        CalibreAction::Export { library, dry_run } => {
+            let Some(ledger) = crate::dashboard::ledger_or_default(root, ledger).filter(|p| p.is_file()) else {
+                bail!("No ledger (--ledger, or {} under --root): import the library first", ledger::DEFAULT_LEDGER);
+            };
+            export(library, root, db, &ledger, dry_run.or(global_dry_run).unwrap_or(true))
+        }
+    }
+}
+
+fn import(library: &Path, root: &Path, db: Option<&Path>, ledger_path: &Path, exts: &str, library_authors: bool, dry_run: bool) -> Result<()> {
+    let calibre = open_calibre(library, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let wanted: Vec<String> = exts.split(',').map(|e| e.trim().trim_start_matches('.').to_ascii_lowercase()).filter(|e| !e.is_empty()).collect();
+    let files: Vec<CalibreFile> = calibre_files(&calibre, library)?.into_iter().filter(|f| wanted.is_empty() || wanted.contains(&f.format)).collect();
+    // The OpenLibrary database is only read for --library-authors
+    let conn = if library_authors {
+        let db = db.map(Path::to_path_buf).unwrap_or_else(|| root.join("data/database/openlibrary.sqlite3"));
+        Connection::open_with_flags(&db, OpenFlags::SQLITE_OPEN_READ_ONLY).with_context(|| format!("Opening {}", db.display()))?
+    } else {
+        Connection::open_in_memory()?
+    };
+    let mut ledger = if dry_run {
+        Ledger::disabled()
+    } else {
+        Ledger::open(ledger_path, "calibre", &std::env::args().collect::<Vec<_>>())?
+    };
+    println!("Importing {} file(s) from {}{}", files.len(), library.display(), if dry_run { " (dry-run)" } else { "" });
+    let (mut copied, mut skipped) = (0usize, 0usize);
+    for file in &files {
+        if !file.path.is_file() {
+            println!("  skip #{} {} (file missing)", file.book, file.path.display());
+            skipped += 1;
+            continue;
+        }
+        let (last, first) = file.author_sort.split_once(", ").unwrap_or((&file.author_sort, ""));
+        let (first, last) = (sanitize_component(first.trim()), sanitize_component(last.trim()));
+        let dir = author_out_dir(&conn, &root.join(SORTED_DIR), library_authors, None, &first, &last)?;
+        let author = format!("{first} {last}").trim().to_string();
+        let target = dir.join(sanitize_component(&format!("{} - {author}.{}", file.title, file.format)));
+        if target.exists() {
+            let same = fs::metadata(&target)?.len() == fs::metadata(&file.path)?.len();
+            println!("  skip #{} {} ({})", file.book, target.display(), if same { "already imported" } else { "another file there" });
+            skipped += 1;
+            continue;
+        }
+        if dry_run {
+            println!("[DRY-RUN] copy {} -> {}", file.path.display(), target.display());
+        } else {
+            fs::create_dir_all(&dir)?;
+            fs::copy(&file.path, &target).with_context(|| format!("Copying {}", file.path.display()))?;
+            ledger.record(Op::Copy, &file.path, Some(&target), IMPORT_DETAIL)?;
+            let meta = BookMeta { isbn: file.isbn.clone(), title: Some(file.title.clone()), author: Some(author), ..BookMeta::default() };
+            ledger.describe(&target, &meta)?;
+        }
+        copied += 1;
+    }
+    ledger.finish("ok")?;
+    println!("Import done: {copied} copied, {skipped} skipped.");
+    Ok(())
+}
+
+fn export(library: &Path, root: &Path, db: Option<&Path>, ledger_path: &Path, dry_run: bool) -> Result<()> {
+    let flags = if dry_run { OpenFlags::SQLITE_OPEN_READ_ONLY } else { OpenFlags::SQLITE_OPEN_READ_WRITE };
+    let mut calibre = open_calibre(library, flags)?;
+    let books_by_file: HashMap<PathBuf, i64> = calibre_files(&calibre, library)?.into_iter().map(|f| (f.path, f.book)).collect();
+    // Current path of every book the ledger knows -> the Calibre file it was imported from
+    // (rows described again later, e.g. by the dashboard, have no source and are left out)
+    let imported: HashMap<PathBuf, i64> = ledger::books(ledger_path)?
+        .into_iter()
+        .filter_map(|book| Some((PathBuf::from(&book.path), *books_by_file.get(Path::new(book.source.as_deref()?))?)))
+        .collect();
+    let sorted = std::path::absolute(root.join(SORTED_DIR))?;
+    let sources = Sources { ledger: Some(ledger_path.to_path_buf()), db: Sources::resolve(root, db, None)?.db };
+    let entries = catalog::scan(&sorted, &sources, "", false)?;
+    println!("Exporting to {}{}", library.join(METADATA_DB).display(), if dry_run { " (dry-run)" } else { "" });
+    if !dry_run {
+        let stamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+        let backup = library.join(format!("{METADATA_DB}.sortbook-{stamp}.bak"));
+        fs::copy(library.join(METADATA_DB), &backup)?;
+        println!("Backup: {}", backup.display());
+        register_functions(&calibre)?;
+    }
+    let tx = calibre.transaction()?;
+    let (mut updated, mut unchanged) = (0usize, 0usize);
+    let mut done: HashSet<i64> = HashSet::new();
+    for entry in &entries {
+        let Some(&book) = imported.get(&sorted.join(&entry.path)) else { continue };
+        // One book, several formats: the first one decides
+        if !done.insert(book) {
+            continue;
+        }
+        let title: String = tx.query_row("SELECT title FROM books WHERE id = ?1", [book], |row| row.get(0))?;
+        let authors = book_authors(&tx, book)?;
+        let name = display_author(&entry.author);
+        let new_title = (entry.title != title).then_some(entry.title.as_str());
+        let new_author = (!authors.iter().any(|(_, author)| author == &name)).then_some(name.as_str());
+        if new_title.is_none() && new_author.is_none() {
+            unchanged += 1;
+            continue;
+        }
+        let was = authors.first().map(|(_, a)| a.as_str()).unwrap_or("");
+        println!(
+            "{}#{book}: \"{title}\" by {was} -> \"{}\" by {}",
+            if dry_run { "[DRY-RUN] " } else { "" },
+            new_title.unwrap_or(&title),
+            new_author.unwrap_or(was)
+        );
+        updated += 1;
+        if dry_run {
+            continue;
+        }
+        if let Some(new_title) = new_title {
+            tx.execute("UPDATE books SET title = ?1, last_modified = ?2 WHERE id = ?3", params![new_title, now(), book])?;
+        }
+        if new_author.is_some() {
+            // The folder is already "Last, First"
+            let sort = entry.author.clone();
+            let author_id = match tx.query_row("SELECT id FROM authors WHERE name = ?1", [&name], |row| row.get::<_, i64>(0)).optional()? {
+                Some(id) => id,
+                None => {
+                    tx.execute("INSERT INTO authors (name, sort, link) VALUES (?1, ?2, '')", params![name, sort])?;
+                    tx.last_insert_rowid()
+                }
+            };
+            // The first author becomes the library's one; co-authors stay
+            match authors.first() {
+                Some((link, _)) => tx.execute("UPDATE books_authors_link SET author = ?1 WHERE id = ?2", params![author_id, link])?,
+                None => tx.execute("INSERT INTO books_authors_link (book, author) VALUES (?1, ?2)", params![book, author_id])?,
+            };
+            let author_sort: String = tx.query_row(
+                "SELECT group_concat(sort, ' & ') FROM (SELECT a.sort FROM books_authors_link l JOIN authors a ON a.id = l.author WHERE l.book = ?1 ORDER BY l.id)",
+                [book],
+                |row| row.get(0),
+            )?;
+            tx.execute("UPDATE books SET author_sort = ?1, last_modified = ?2 WHERE id = ?3", params![author_sort, now(), book])?;
+        }
+        if let Some(isbn) = &entry.isbn {
+            tx.execute("INSERT OR IGNORE INTO identifiers (book, type, val) VALUES (?1, 'isbn', ?2)", params![book, isbn])?;
+        }
+    }
+    if dry_run {
+        tx.rollback()?;
+    } else {
+        tx.commit()?;
+    }
+    println!("Export done: {updated} book(s) updated, {unchanged} unchanged (close Calibre before exporting; it rewrites its OPF backups itself).");
+    Ok(())
+}
+
+fn open_calibre(library: &Path, flags: OpenFlags) -> Result<Connection> {
+    let path = library.join(METADATA_DB);
+    if !path.is_file() {
+        bail!("{} not found (not a Calibre library?)", path.display());
+    }
+    Connection::open_with_flags(&path, flags | OpenFlags::SQLITE_OPEN_NO_MUTEX).with_context(|| format!("Opening {}", path.display()))
+}
+
+/// Every format file of every book, with its title, first author and ISBN.
+fn calibre_files(conn: &Connection, library: &Path) -> Result<Vec<CalibreFile>> {
+    let library = std::path::absolute(library)?;
+    let mut stmt = conn.prepare(
+        "SELECT b.id, b.title, b.path, d.format, d.name,
+                (SELECT a.sort FROM books_authors_link l JOIN authors a ON a.id = l.author WHERE l.book = b.id ORDER BY l.id LIMIT 1),
+                (SELECT val FROM identifiers i WHERE i.book = b.id AND i.type = 'isbn')
+         FROM books b JOIN data d ON d.book = b.id ORDER BY b.id, d.format",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let format: String = row.get::<_, String>(3)?.to_ascii_lowercase();
+        let folder: String = row.get(2)?;
+        let name: String = row.get(4)?;
+        Ok(CalibreFile {
+            book: row.get(0)?,
+            title: row.get(1)?,
+            author_sort: row.get::<_, Option<String>>(5)?.unwrap_or_else(|| "Unknown".to_string()),
+            isbn: row.get(6)?,
+            path: library.join(folder).join(format!("{name}.{format}")),
+            format,
+        })
+    })?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// `(link id, author name)` of a book, in Calibre's order.
+fn book_authors(conn: &Connection, book: i64) -> Result<Vec<(i64, String)>> {
+    let mut stmt = conn.prepare("SELECT l.id, a.name FROM books_authors_link l JOIN authors a ON a.id = l.author WHERE l.book = ?1 ORDER BY l.id")?;
+    let rows = stmt.query_map([book], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// SQL functions Calibre registers and its triggers call (`books_update_trg` sorts titles with `title_sort`).
+fn register_functions(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function("title_sort", 1, FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC, |ctx| {
+        Ok(title_sort(&ctx.get::<String>(0)?))
+    })
+}
+
+/// Calibre's default title sort: a leading English article goes to the end ("The Stand" -> "Stand, The").
+fn title_sort(title: &str) -> String {
+    for article in ["The ", "An ", "A "] {
+        if let Some(rest) = title.strip_prefix(article).filter(|rest| !rest.trim().is_empty()) {
+            return format!("{}, {}", rest.trim(), article.trim());
+        }
+    }
+    title.to_string()
+}
+
+fn now() -> String {
+    chrono::Utc::now().format("%Y-%m-%d %H:%M:%S%.6f+00:00").to_string()
+}
@@ -0,0 +1,310 @@
+use anyhow::{bail, Context, Result};
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+use sortbook_common::ledger::{self, BookRow};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Covers, metadata and other companions of the books, left out when `--exts` is empty.
+const SIDECAR_EXTS: [&str; 9] = ["opf", "jpg", "jpeg", "png", "gif", "webp", "json", "nfo", "db"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Csv,
+    Json,
+    /// OPDS 1.2 acquisition feed (Atom), for e-reader apps
+    Opds,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Json => "json",
+            Format::Opds => "xml",
+        }
+    }
+}
+
+/// Options of `sortbook catalog`; the library, ledger and database come from the global options.
+#[derive(Args, Debug)]
+pub struct CatalogArgs {
+    /// Library folder (default: <root>/output/sorted_books)
+    #[arg(long)]
+    pub library: Option<PathBuf>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = Format::Csv)]
+    pub format: Format,
+
+    /// Output file (default: <root>/data/catalog.csv, .json or .xml); `-` writes to stdout
+    #[arg(long)]
+    pub out: Option<PathBuf>,
+
+    /// Extensions to list, comma separated (default: every file but hidden ones, covers and metadata)
+    #[arg(long, default_value = "")]
+    pub exts: String,
+
+    /// OPDS: base URL the book links are relative to (default: file:// URL of the library)
+    #[arg(long)]
+    pub base_url: Option<String>,
+
+    /// Do not open EPUB files to read the ISBN of books the ledger has none for
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub no_epub: bool,
+}
+
+/// One book of the library.
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+    /// Path relative to the library, with `/` separators.
+    pub path: String,
+    /// Author folder (first component of `path`).
+    pub author: String,
+    pub title: String,
+    pub format: String,
+    pub size: u64,
+    pub modified: String,
+    pub work_id: Option<String>,
+    pub author_id: Option<String>,
+    pub isbn: Option<String>,
+    /// When the sorter copied it, from the ledger.
+    pub sorted_at: Option<String>,
+    /// Input file it was sorted from, from the ledger.
+    pub source: Option<String>,
+}
+
+impl Entry {
+    /// `path` without its extension (nor `.kepub` of `.kepub.epub`), lowercased: the formats of one book share it.
+    pub fn book_key(&self) -> String {
+        let key = self.path.strip_suffix(&format!(".{}", self.format)).unwrap_or(&self.path);
+        key.strip_suffix(".kepub").unwrap_or(key).to_lowercase()
+    }
+}
+
+/// Writes the catalog of the library. `root`, `db` and `ledger` are the global options of `sortbook`;
+/// the database and the ledger are only read when given or present at their default place under `root`.
+pub fn run(args: &CatalogArgs, root: &Path, db: Option<&Path>, ledger: Option<&Path>) -> Result<()> {
+    let library = args.library.clone().unwrap_or_else(|| root.join(crate::SORTED_DIR));
+    let entries = scan(&library, &Sources::resolve(root, db, ledger)?, &args.exts, !args.no_epub)?;
+    let out = args.out.clone().unwrap_or_else(|| root.join(format!("data/catalog.{}", args.format.extension())));
+    let body = match args.format {
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            for entry in &entries {
+                writer.serialize(entry)?;
+            }
+            writer.into_inner().map_err(|e| e.into_error())?
+        }
+        Format::Json => (serde_json::to_string_pretty(&entries)? + "\n").into_bytes(),
+        Format::Opds => {
+            let base = match &args.base_url {
+                Some(url) => url.clone(),
+                None => file_url(&library)?,
+            };
+            opds_feed("urn:sortbook:catalog", "sortbook library", &base, &[], &entries).into_bytes()
+        }
+    };
+    if out.as_os_str() == "-" {
+        std::io::stdout().write_all(&body)?;
+        return Ok(());
+    }
+    if let Some(parent) = out.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&out, body).with_context(|| format!("Writing {}", out.display()))?;
+    let described = entries.iter().filter(|e| e.work_id.is_some()).count();
+    println!("Catalog: {} book(s), {} with a work_id -> {}", entries.len(), described, out.display());
+    Ok(())
+}
+
+/// Ledger and OpenLibrary database of a catalog, either of which may be missing.
+pub struct Sources {
+    pub ledger: Option<PathBuf>,
+    pub db: Option<PathBuf>,
+}
+
+impl Sources {
+    /// Explicit paths must exist; the defaults under `root` are used when present.
+    pub fn resolve(root: &Path, db: Option<&Path>, ledger: Option<&Path>) -> Result<Self> {
+        let pick = |given: Option<&Path>, default: &str| -> Result<Option<PathBuf>> {
+            match given {
+                Some(path) if !path.is_file() => bail!("{} not found", path.display()),
+                Some(path) => Ok(Some(path.to_path_buf())),
+                None => Ok(Some(root.join(default)).filter(|p| p.is_file())),
+            }
+        };
+        Ok(Self {
+            ledger: pick(ledger, ledger::DEFAULT_LEDGER)?,
+            db: pick(db, "data/database/openlibrary.sqlite3")?,
+        })
+    }
+}
+
+/// Books under the author folders of `library` (files directly in it are not listed), sorted by path.
+/// `exts` as in `--exts`; `epub` reads missing ISBNs from the EPUB files.
+pub fn scan(library: &Path, sources: &Sources, exts: &str, epub: bool) -> Result<Vec<Entry>> {
+    if !library.is_dir() {
+        bail!("Library {} not found", library.display());
+    }
+    let library = std::path::absolute(library)?;
+    let books: HashMap<String, BookRow> = match &sources.ledger {
+        Some(path) => ledger::books(path)?.into_iter().map(|book| (book.path.clone(), book)).collect(),
+        None => HashMap::new(),
+    };
+    let folders = match &sources.db {
+        Some(db) => cleanup::read_library_authors(db)?,
+        None => HashMap::new(),
+    };
+    let wanted: Vec<String> = exts
+        .split(',')
+        .map(|e| e.trim().trim_start_matches('.').to_ascii_lowercase())
+        .filter(|e| !e.is_empty())
+        .collect();
+
+    let mut entries = Vec::new();
+    let walker = WalkDir::new(&library).min_depth(2).into_iter().filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'));
+    for item in walker {
+        let item = item?;
+        if !item.file_type().is_file() {
+            continue;
+        }
+        let path = item.path();
+        let format = path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+        let listed = if wanted.is_empty() { !SIDECAR_EXTS.contains(&format.as_str()) } else { wanted.contains(&format) };
+        if !listed {
+            continue;
+        }
+        let relative = path.strip_prefix(&library).unwrap_or(path);
+        let author = relative.components().next().map(|c| c.as_os_str().to_string_lossy().into_owned()).unwrap_or_default();
+        let meta = item.metadata()?;
+        let book = books.get(path.to_string_lossy().as_ref());
+        let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        let isbn = book.and_then(|b| b.meta.isbn.clone()).or_else(|| {
+            (epub && format == "epub").then(|| cleanup_filenames::epub::epub_isbn(path)).flatten()
+        });
+        entries.push(Entry {
+            path: relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/"),
+            title: book.and_then(|b| b.meta.title.clone()).unwrap_or_else(|| title_from_stem(&stem, &author)),
+            format,
+            size: meta.len(),
+            modified: meta.modified().map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339()).unwrap_or_default(),
+            work_id: book.and_then(|b| b.meta.work_id.clone()),
+            author_id: book.and_then(|b| b.meta.author_id.clone()).or_else(|| folders.get(&author).cloned()),
+            isbn,
+            sorted_at: book.map(|b| b.ts.clone()),
+            source: book.and_then(|b| b.source.clone()),
+            author,
+        });
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// "Last, First" folder as "First Last".
+pub fn display_author(folder: &str) -> String {
+    match folder.split_once(", ") {
+        Some((last, first)) if !first.trim().is_empty() => format!("{} {}", first.trim(), last.trim()),
+        _ => folder.to_string(),
+    }
+}
+
+/// File name without the " - First Last" suffix the sorter adds.
+fn title_from_stem(stem: &str, folder: &str) -> String {
+    let suffix = format!(" - {}", display_author(folder)).to_lowercase();
+    let lower = stem.to_lowercase();
+    match lower.strip_suffix(&suffix) {
+        Some(title) if lower.len() == stem.len() && !title.trim().is_empty() && stem.is_char_boundary(title.len()) => {
+            stem[..title.len()].trim().to_string()
+        }
+        _ => stem.to_string(),
+    }
+}
+
+/// MIME types of OPDS feeds.
+pub const NAVIGATION_TYPE: &str = "application/atom+xml;profile=opds-catalog;kind=navigation";
+pub const ACQUISITION_TYPE: &str = "application/atom+xml;profile=opds-catalog;kind=acquisition";
+
+/// Header link of a feed: (rel, href, type).
+pub type FeedLink<'a> = (&'a str, String, &'a str);
+
+/// Opening of an OPDS feed, up to its header links; `base` (`xml:base`) is what relative links resolve against.
+pub fn feed_header(id: &str, title: &str, base: &str, links: &[FeedLink]) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<feed xmlns=\"http://www.w3.org/2005/Atom\" xmlns:dc=\"http://purl.org/dc/terms/\" xmlns:opds=\"http://opds-spec.org/2010/catalog\" xml:base=\"{}\">\n",
+        escape(base)
+    ));
+    xml.push_str(&format!("  <id>{}</id>\n", escape(id)));
+    xml.push_str(&format!("  <title>{}</title>\n  <updated>{}</updated>\n", escape(title), chrono::Utc::now().to_rfc3339()));
+    xml.push_str("  <author><name>sortbook</name></author>\n");
+    for (rel, href, kind) in links {
+        xml.push_str(&format!("  <link rel=\"{}\" href=\"{}\" type=\"{}\"/>\n", rel, escape(href), kind));
+    }
+    xml
+}
+
+/// OPDS 1.2 acquisition feed of `entries`; book links are their paths, relative to `base`.
+pub fn opds_feed(id: &str, title: &str, base: &str, links: &[FeedLink], entries: &[Entry]) -> String {
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut xml = feed_header(id, title, base, links);
+    for entry in entries {
+        let href = url_path(&entry.path);
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!("    <title>{}</title>\n", escape(&entry.title)));
+        xml.push_str(&format!("    <id>urn:sortbook:book:{}</id>\n", escape(&href)));
+        xml.push_str(&format!("    <updated>{}</updated>\n", if entry.modified.is_empty() { &now } else { &entry.modified }));
+        xml.push_str(&format!("    <author><name>{}</name></author>\n", escape(&display_author(&entry.author))));
+        if let Some(isbn) = &entry.isbn {
+            xml.push_str(&format!("    <dc:identifier>urn:isbn:{}</dc:identifier>\n", escape(isbn)));
+        }
+        if let Some(work_id) = &entry.work_id {
+            xml.push_str(&format!("    <dc:identifier>https://openlibrary.org/works/{}</dc:identifier>\n", escape(work_id)));
+        }
+        xml.push_str(&format!(
+            "    <link rel=\"http://opds-spec.org/acquisition\" href=\"{}\" type=\"{}\" length=\"{}\"/>\n",
+            escape(&href),
+            mime_type(&entry.format),
+            entry.size
+        ));
+        xml.push_str("  </entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+pub fn mime_type(format: &str) -> &'static str {
+    match format {
+        "epub" => "application/epub+zip",
+        "pdf" => "application/pdf",
+        "mobi" => "application/x-mobipocket-ebook",
+        "azw" | "azw3" => "application/vnd.amazon.ebook",
+        "cbz" => "application/vnd.comicbook+zip",
+        "cbr" => "application/vnd.comicbook-rar",
+        "fb2" => "application/x-fictionbook+xml",
+        "djvu" => "image/vnd.djvu",
+        "txt" => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Percent-encodes each `/`-separated part of a relative path.
+pub fn url_path(path: &str) -> String {
+    path.split('/').map(|part| urlencoding::encode(part).into_owned()).collect::<Vec<_>>().join("/")
+}
+
+/// `file://` URL of a folder, with a trailing slash so relative links resolve inside it.
+fn file_url(dir: &Path) -> Result<String> {
+    let absolute = std::path::absolute(dir)?;
+    let path = absolute.to_string_lossy().replace('\\', "/");
+    let encoded = path.split('/').map(|part| urlencoding::encode(part).into_owned()).collect::<Vec<_>>().join("/");
+    Ok(format!("file://{}{}/", if encoded.starts_with('/') { "" } else { "/" }, encoded.trim_end_matches('/')))
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
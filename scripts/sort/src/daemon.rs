@@ -0,0 +1,438 @@
+use crate::dashboard::{self, blocking, internal, ApiError, Item};
+use crate::RAW_DIR;
+use anyhow::{anyhow, Result};
+use axum::extract::{Path as UrlPath, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{mpsc, Arc, Mutex};
+use walkdir::WalkDir;
+
+/// Jobs listed by `/api/status`, most recent first.
+const STATUS_JOBS: usize = 20;
+/// Log lines returned with a job.
+const LOG_TAIL: usize = 50;
+/// Tools `/api/cleanup` may run.
+const CLEANUP_TOOLS: [&str; 3] = ["cleanup", "filenames", "aliases"];
+
+/// Options of `sortbook daemon`; the project, database, config, ledger and dry-run come from the
+/// global options and are passed to every job.
+#[derive(Args, Debug)]
+pub struct DaemonArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8082")]
+    pub listen: String,
+
+    /// Bearer token clients must send (`Authorization: Bearer <token>`); no check when unset
+    #[arg(long, env = "SORTBOOK_DAEMON_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+
+    /// Quarantine folder of cleanup-filenames (`--quarantine`), listed with the failures
+    #[arg(long)]
+    pub quarantine: Option<PathBuf>,
+}
+
+/// A tool run queued by the API, executed as `sortbook <global options> <tool> <args>`.
+#[derive(Debug, Clone, Serialize)]
+struct Job {
+    id: u64,
+    tool: &'static str,
+    args: Vec<String>,
+    /// `queued`, `running`, `ok` or `failed`.
+    status: &'static str,
+    exit_code: Option<i32>,
+    error: Option<String>,
+    submitted: String,
+    started: Option<String>,
+    finished: Option<String>,
+    log: String,
+}
+
+struct Daemon {
+    root: PathBuf,
+    ledger: Option<PathBuf>,
+    quarantine: Option<PathBuf>,
+    token: Option<String>,
+    /// The global `--dry-run true`: jobs only preview, and `/api/sort` is refused (the sorter has no dry-run).
+    dry_run: bool,
+    exe: PathBuf,
+    globals: Vec<OsString>,
+    jobs: Mutex<Vec<Job>>,
+    queue: Mutex<mpsc::Sender<u64>>,
+}
+
+/// Files or folders to sort: copied into `input/<ext>/`, then one sort job per extension.
+#[derive(Debug, Deserialize)]
+struct SortRequest {
+    #[serde(default)]
+    paths: Vec<PathBuf>,
+    /// Also sort what is already in `input/<ext>/`.
+    ext: Option<String>,
+    mode: Option<String>,
+    /// Other sorter options (`--library-authors`, `--limit 10`...).
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct SortResponse {
+    jobs: Vec<Job>,
+    copied: Vec<String>,
+    /// Already in `input/<ext>/` with the same size.
+    present: Vec<String>,
+    /// Files without an extension.
+    skipped: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CleanupRequest {
+    tool: Option<String>,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Status {
+    root: String,
+    queued: usize,
+    running: Option<Job>,
+    failures: usize,
+    jobs: Vec<Job>,
+}
+
+#[derive(Debug, Serialize)]
+struct JobDetail {
+    #[serde(flatten)]
+    job: Job,
+    log_tail: Vec<String>,
+}
+
+/// Serves the API until the process is stopped; jobs run one at a time, in submission order.
+pub fn run(args: &DaemonArgs, root: &Path, ledger: Option<&Path>, globals: Vec<OsString>, dry_run: bool) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let state = Arc::new(Daemon {
+        root: root.to_path_buf(),
+        ledger: dashboard::ledger_or_default(root, ledger),
+        quarantine: args.quarantine.clone(),
+        token: args.token.clone().filter(|t| !t.is_empty()),
+        dry_run,
+        exe: std::env::current_exe()?,
+        globals,
+        jobs: Mutex::new(Vec::new()),
+        queue: Mutex::new(tx),
+    });
+    let worker = state.clone();
+    std::thread::spawn(move || {
+        for id in rx {
+            worker.execute(id);
+        }
+    });
+    let app = Router::new()
+        .route("/api/status", get(status))
+        .route("/api/jobs", get(jobs))
+        .route("/api/jobs/{id}", get(job))
+        .route("/api/failures", get(failures))
+        .route("/api/sort", post(sort))
+        .route("/api/cleanup", post(cleanup))
+        .layer(middleware::from_fn_with_state(state.clone(), auth))
+        .with_state(state.clone());
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let listener = tokio::net::TcpListener::bind(&args.listen).await.map_err(|e| anyhow!("Listening on {}: {e}", args.listen))?;
+        println!("sortbook daemon on http://{}/api/status (jobs log to {})", args.listen, state.root.join("logs/daemon").display());
+        if state.token.is_none() {
+            println!("No --token: any client that reaches {} can queue runs", args.listen);
+        }
+        if state.dry_run {
+            println!("Dry-run: cleanup jobs only preview, /api/sort is refused (the sorter has no dry-run mode)");
+        }
+        axum::serve(listener, app).await?;
+        Ok(())
+    })
+}
+
+impl Daemon {
+    fn submit(&self, tool: &'static str, args: Vec<String>) -> Job {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let id = jobs.len() as u64 + 1;
+        let job = Job {
+            id,
+            tool,
+            args,
+            status: "queued",
+            exit_code: None,
+            error: None,
+            submitted: now(),
+            started: None,
+            finished: None,
+            log: self.root.join("logs/daemon").join(format!("job-{id}.log")).display().to_string(),
+        };
+        jobs.push(job.clone());
+        let _ = self.queue.lock().unwrap_or_else(|e| e.into_inner()).send(id);
+        job
+    }
+
+    fn update(&self, id: u64, change: impl FnOnce(&mut Job)) -> Option<Job> {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let job = jobs.iter_mut().find(|j| j.id == id)?;
+        change(job);
+        Some(job.clone())
+    }
+
+    /// Runs a job in a child `sortbook` process, its output going to the job's log.
+    fn execute(&self, id: u64) {
+        let Some(job) = self.update(id, |j| {
+            j.status = "running";
+            j.started = Some(now());
+        }) else {
+            return;
+        };
+        let result = (|| -> Result<std::process::ExitStatus> {
+            let log = PathBuf::from(&job.log);
+            if let Some(parent) = log.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let out = fs::File::create(&log)?;
+            Ok(Command::new(&self.exe)
+                .args(&self.globals)
+                .arg(job.tool)
+                .args(&job.args)
+                .stdin(Stdio::null())
+                .stdout(out.try_clone()?)
+                .stderr(out)
+                .status()?)
+        })();
+        self.update(id, |j| {
+            j.finished = Some(now());
+            match result {
+                Ok(status) => {
+                    j.status = if status.success() { "ok" } else { "failed" };
+                    j.exit_code = status.code();
+                }
+                Err(e) => {
+                    j.status = "failed";
+                    j.error = Some(e.to_string());
+                }
+            }
+        });
+    }
+
+    /// Queues one of `CLEANUP_TOOLS`. A dry-run daemon refuses a `--dry-run` of the client: given after the
+    /// tool, it would win over the global one and let the job apply its changes.
+    fn cleanup(&self, request: CleanupRequest) -> Result<Job, ApiError> {
+        let name = request.tool.as_deref().unwrap_or("cleanup");
+        let Some(tool) = CLEANUP_TOOLS.into_iter().find(|t| *t == name) else {
+            return Err((StatusCode::BAD_REQUEST, format!("Unknown tool {name} (cleanup, filenames or aliases)")));
+        };
+        if self.dry_run && request.args.iter().any(|a| a == "--dry-run" || a.starts_with("--dry-run=")) {
+            return Err((StatusCode::CONFLICT, "The daemon runs with --dry-run true: drop --dry-run from the job arguments".into()));
+        }
+        Ok(self.submit(tool, request.args))
+    }
+
+    /// Copies the requested files into `input/<ext>/` and queues a sort job per extension.
+    fn sort(&self, request: SortRequest) -> Result<SortResponse, ApiError> {
+        // Checked before any copy: the job would only fail with "sort has no dry-run mode"
+        if self.dry_run {
+            return Err((StatusCode::CONFLICT, "The daemon runs with --dry-run true and the sorter has no dry-run mode".into()));
+        }
+        let mut exts = BTreeSet::new();
+        if let Some(ext) = request.ext.as_deref().map(|e| e.trim_start_matches('.').to_lowercase()).filter(|e| !e.is_empty()) {
+            exts.insert(ext);
+        }
+        let mut files = Vec::new();
+        for path in &request.paths {
+            if path.is_file() {
+                files.push(path.clone());
+            } else if path.is_dir() {
+                files.extend(
+                    WalkDir::new(path)
+                        .into_iter()
+                        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+                        .flatten()
+                        .filter(|e| e.file_type().is_file())
+                        .map(|e| e.into_path()),
+                );
+            } else {
+                return Err((StatusCode::BAD_REQUEST, format!("{} not found", path.display())));
+            }
+        }
+        let (mut copied, mut present, mut skipped) = (Vec::new(), Vec::new(), Vec::new());
+        for file in files {
+            let Some(ext) = file.extension().map(|e| e.to_string_lossy().to_lowercase()) else {
+                skipped.push(file.display().to_string());
+                continue;
+            };
+            let dir = self.root.join(RAW_DIR).join(&ext);
+            match copy_into(&file, &dir) {
+                Ok(Some(dest)) => copied.push(dest.display().to_string()),
+                Ok(None) => present.push(file.display().to_string()),
+                Err(e) => return Err(internal(anyhow!("Copying {}: {e}", file.display()))),
+            }
+            exts.insert(ext);
+        }
+        if exts.is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "Nothing to sort: give paths or an ext".into()));
+        }
+        let jobs = exts
+            .into_iter()
+            .map(|ext| {
+                let mut args = vec!["--ext".to_string(), ext];
+                if let Some(mode) = &request.mode {
+                    args.extend(["--mode".to_string(), mode.clone()]);
+                }
+                args.extend(request.args.iter().cloned());
+                self.submit("sort", args)
+            })
+            .collect();
+        Ok(SortResponse { jobs, copied, present, skipped })
+    }
+}
+
+async fn auth(State(state): State<Arc<Daemon>>, request: Request, next: Next) -> Response {
+    if let Some(token) = &state.token {
+        let given = request.headers().get(header::AUTHORIZATION).and_then(|v| v.to_str().ok());
+        if given != Some(format!("Bearer {token}").as_str()) {
+            return (StatusCode::UNAUTHORIZED, "Missing or wrong bearer token").into_response();
+        }
+    }
+    next.run(request).await
+}
+
+async fn status(State(state): State<Arc<Daemon>>) -> Result<Json<Status>, ApiError> {
+    let failures = {
+        let state = state.clone();
+        blocking(move || dashboard::pending(&state.root, state.ledger.as_deref(), state.quarantine.as_deref()).map_err(internal)).await?.len()
+    };
+    let jobs = state.jobs.lock().unwrap_or_else(|e| e.into_inner());
+    Ok(Json(Status {
+        root: state.root.display().to_string(),
+        queued: jobs.iter().filter(|j| j.status == "queued").count(),
+        running: jobs.iter().find(|j| j.status == "running").cloned(),
+        failures,
+        jobs: jobs.iter().rev().take(STATUS_JOBS).cloned().collect(),
+    }))
+}
+
+async fn jobs(State(state): State<Arc<Daemon>>) -> Json<Vec<Job>> {
+    Json(state.jobs.lock().unwrap_or_else(|e| e.into_inner()).clone())
+}
+
+async fn job(State(state): State<Arc<Daemon>>, UrlPath(id): UrlPath<u64>) -> Result<Json<JobDetail>, ApiError> {
+    let job = state.jobs.lock().unwrap_or_else(|e| e.into_inner()).iter().find(|j| j.id == id).cloned();
+    let job = job.ok_or((StatusCode::NOT_FOUND, format!("No job {id}")))?;
+    blocking(move || {
+        let log = fs::read_to_string(&job.log).unwrap_or_default();
+        let lines: Vec<&str> = log.lines().collect();
+        let log_tail = lines[lines.len().saturating_sub(LOG_TAIL)..].iter().map(|l| l.to_string()).collect();
+        Ok(Json(JobDetail { job, log_tail }))
+    })
+    .await
+}
+
+async fn failures(State(state): State<Arc<Daemon>>) -> Result<Json<Vec<Item>>, ApiError> {
+    blocking(move || dashboard::pending(&state.root, state.ledger.as_deref(), state.quarantine.as_deref()).map_err(internal)).await.map(Json)
+}
+
+async fn sort(State(state): State<Arc<Daemon>>, Json(request): Json<SortRequest>) -> Result<(StatusCode, Json<SortResponse>), ApiError> {
+    blocking(move || state.sort(request)).await.map(|r| (StatusCode::ACCEPTED, Json(r)))
+}
+
+async fn cleanup(State(state): State<Arc<Daemon>>, Json(request): Json<CleanupRequest>) -> Result<(StatusCode, Json<Job>), ApiError> {
+    state.cleanup(request).map(|job| (StatusCode::ACCEPTED, Json(job)))
+}
+
+/// Copies `file` into `dir`; None when a file of the same name and size is already there. Another
+/// file with the same name gets a ` (n)` suffix.
+fn copy_into(file: &Path, dir: &Path) -> std::io::Result<Option<PathBuf>> {
+    fs::create_dir_all(dir)?;
+    let name = file.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let size = fs::metadata(file)?.len();
+    let mut dest = dir.join(&name);
+    let mut n = 1;
+    while dest.exists() {
+        if fs::metadata(&dest)?.len() == size {
+            return Ok(None);
+        }
+        let stem = Path::new(&name).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        let ext = Path::new(&name).extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+        dest = dir.join(format!("{stem} ({n}){ext}"));
+        n += 1;
+    }
+    fs::copy(file, &dest)?;
+    Ok(Some(dest))
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn daemon(dry_run: bool) -> (Daemon, mpsc::Receiver<u64>) {
+        let (tx, rx) = mpsc::channel();
+        let daemon = Daemon {
+            root: std::env::temp_dir(),
+            ledger: None,
+            quarantine: None,
+            token: None,
+            dry_run,
+            exe: PathBuf::from("sortbook"),
+            globals: vec!["--dry-run".into(), dry_run.to_string().into()],
+            jobs: Mutex::new(Vec::new()),
+            queue: Mutex::new(tx),
+        };
+        (daemon, rx)
+    }
+
+    fn request(tool: &str, args: &[&str]) -> CleanupRequest {
+        CleanupRequest { tool: Some(tool.to_string()), args: args.iter().map(|a| a.to_string()).collect() }
+    }
+
+    #[test]
+    fn dry_run_daemon_refuses_a_job_dry_run() {
+        let (daemon, rx) = daemon(true);
+        for args in [&["--dry-run", "false"][..], &["--dry-run=false"], &["--verbose", "--dry-run"]] {
+            for tool in CLEANUP_TOOLS {
+                let (status, _) = daemon.cleanup(request(tool, args)).unwrap_err();
+                assert_eq!(status, StatusCode::CONFLICT, "{tool} {args:?}");
+            }
+        }
+        assert!(rx.try_recv().is_err(), "nothing queued");
+        assert!(daemon.jobs.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn dry_run_daemon_queues_jobs_without_dry_run() {
+        let (daemon, rx) = daemon(true);
+        let job = daemon.cleanup(request("filenames", &["--verbose"])).unwrap();
+        assert_eq!((job.tool, job.args.as_slice()), ("filenames", &["--verbose".to_string()][..]));
+        assert_eq!(rx.try_recv().ok(), Some(job.id));
+    }
+
+    #[test]
+    fn live_daemon_passes_the_job_dry_run() {
+        let (daemon, _rx) = daemon(false);
+        let job = daemon.cleanup(request("aliases", &["--dry-run", "false"])).unwrap();
+        assert_eq!(job.args, ["--dry-run", "false"]);
+        assert!(daemon.cleanup(request("rm", &[])).is_err());
+    }
+
+    #[test]
+    fn dry_run_daemon_refuses_sort() {
+        let (daemon, _rx) = daemon(true);
+        let sort = SortRequest { paths: Vec::new(), ext: Some("epub".into()), mode: None, args: Vec::new() };
+        assert_eq!(daemon.sort(sort).unwrap_err().0, StatusCode::CONFLICT);
+    }
+}
@@ -0,0 +1,395 @@
+use crate::{author_out_dir, normalize_text, FAIL_AUTHOR_DIR, FAIL_TITLE_DIR, SORTED_DIR};
+use anyhow::{anyhow, bail, Result};
+use author_alias_online::metadata::{ProviderKind, Registry, RegistryOptions};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use clap::Args;
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use sortbook_common::ledger::{self, BookMeta, Ledger, Op};
+use sortbook_common::sanitize::sanitize_component;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use walkdir::WalkDir;
+
+/// Candidates proposed per item and provider, for works and for authors.
+const CANDIDATES: usize = 8;
+/// HTTP timeout of the online providers, in seconds.
+const PROVIDER_TIMEOUT: u64 = 20;
+
+const PAGE: &str = include_str!("dashboard.html");
+
+/// Options of `sortbook dashboard`; the project, database and ledger come from the global options.
+#[derive(Args, Debug)]
+pub struct DashboardArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8081")]
+    pub listen: String,
+
+    /// Quarantine folder of cleanup-filenames (`--quarantine`), listed with the fail folders
+    /// (files the ledger recorded as quarantined are listed in any case)
+    #[arg(long)]
+    pub quarantine: Option<PathBuf>,
+
+    /// File books into the folder `cleanup --library-authors` recorded for their author_id, like the sorter's option
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub library_authors: bool,
+
+    /// Metadata providers the candidates come from, in order, comma-separated (like the sorter's option)
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "openlibrary-sqlite")]
+    pub providers: Vec<ProviderKind>,
+
+    /// Only report what a decision would do (true) or apply it (false); defaults to the global --dry-run, else false
+    #[arg(long, action = clap::ArgAction::Set)]
+    pub dry_run: Option<bool>,
+}
+
+struct Dashboard {
+    root: PathBuf,
+    db: PathBuf,
+    ledger: Option<PathBuf>,
+    quarantine: Option<PathBuf>,
+    library_authors: bool,
+    registry: Registry,
+    dry_run: bool,
+}
+
+/// A file waiting for a decision.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Item {
+    /// `<bucket>/<path in the bucket>`, as sent back by the page.
+    id: String,
+    /// `fail_author`, `fail_title` or `quarantine`.
+    bucket: &'static str,
+    name: String,
+    size: u64,
+    #[serde(skip)]
+    path: PathBuf,
+    /// LLM guess recorded by the sorter (ledger).
+    guess_title: Option<String>,
+    guess_author: Option<String>,
+    /// Where a quarantined file came from, when the ledger knows.
+    origin: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Work {
+    /// Provider the candidate comes from (`openlibrary-sqlite`...).
+    source: &'static str,
+    /// OpenLibrary keys; empty for the other providers, whose ids are not recorded in the ledger.
+    work_id: String,
+    title: String,
+    author_id: String,
+    author: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Author {
+    source: &'static str,
+    author_id: String,
+    name: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Candidates {
+    works: Vec<Work>,
+    authors: Vec<Author>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CandidatesQuery {
+    title: Option<String>,
+    author: Option<String>,
+}
+
+/// Decision on an item: `file` it under `<Last, First>/<Title - First Last>.<ext>`, or `restore` a
+/// quarantined file to its origin.
+#[derive(Debug, Deserialize)]
+struct Resolve {
+    id: String,
+    action: String,
+    #[serde(default)]
+    first: String,
+    #[serde(default)]
+    last: String,
+    #[serde(default)]
+    title: String,
+    work_id: Option<String>,
+    author_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Resolved {
+    target: String,
+    run_id: Option<i64>,
+    /// Nothing was moved: the dashboard runs with --dry-run.
+    dry_run: bool,
+}
+
+pub(crate) type ApiError = (StatusCode, String);
+
+/// Serves the dashboard until the process is stopped.
+pub fn run(args: &DashboardArgs, root: &Path, db: Option<&Path>, ledger: Option<&Path>, global_dry_run: Option<bool>) -> Result<()> {
+    let db = db.map(Path::to_path_buf).unwrap_or_else(|| root.join("data/database/openlibrary.sqlite3"));
+    if !db.is_file() {
+        bail!("OpenLibrary database {} not found (--db)", db.display());
+    }
+    let ledger = ledger_or_default(root, ledger);
+    let registry = Registry::new(
+        &args.providers,
+        &RegistryOptions { db: &db, normalize: normalize_text, timeout: PROVIDER_TIMEOUT, langs: vec!["fr".into(), "en".into()] },
+    )?;
+    let state = Arc::new(Dashboard {
+        root: root.to_path_buf(),
+        db,
+        ledger,
+        quarantine: args.quarantine.clone(),
+        library_authors: args.library_authors,
+        registry,
+        dry_run: args.dry_run.or(global_dry_run).unwrap_or(false),
+    });
+    let pending = state.items()?.len();
+    let app = Router::new()
+        .route("/", get(|| async { Html(PAGE) }))
+        .route("/api/items", get(items))
+        .route("/api/candidates", get(candidates))
+        .route("/api/resolve", post(resolve))
+        .with_state(state.clone());
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let listener = tokio::net::TcpListener::bind(&args.listen).await.map_err(|e| anyhow!("Listening on {}: {e}", args.listen))?;
+        println!(
+            "Triage dashboard ({pending} item(s)) on http://{}/{}",
+            args.listen,
+            if state.dry_run { " (dry-run: decisions are only reported)" } else { "" }
+        );
+        match &state.ledger {
+            Some(path) => println!("Decisions are recorded in {}", path.display()),
+            None => println!("No ledger (--ledger): decisions are not recorded and LLM guesses are not shown"),
+        }
+        axum::serve(listener, app).await?;
+        Ok(())
+    })
+}
+
+/// The ledger given with `--ledger`, else `<root>/data/sortbook_state.sqlite3` when it exists.
+pub(crate) fn ledger_or_default(root: &Path, ledger: Option<&Path>) -> Option<PathBuf> {
+    match ledger {
+        Some(path) => Some(path.to_path_buf()),
+        None => Some(root.join(ledger::DEFAULT_LEDGER)).filter(|p| p.is_file()),
+    }
+}
+
+/// Files of the fail folders and of the quarantine, with what the ledger knows about them
+/// (also listed by `sortbook daemon`).
+pub(crate) fn pending(root: &Path, ledger: Option<&Path>, quarantine: Option<&Path>) -> Result<Vec<Item>> {
+    let (books, quarantined) = match ledger {
+        Some(path) => {
+            let books: HashMap<String, BookMeta> = ledger::books(path)?.into_iter().map(|b| (b.path, b.meta)).collect();
+            (books, quarantine_origins(path)?)
+        }
+        None => (HashMap::new(), HashMap::new()),
+    };
+    let mut items = Vec::new();
+    for (bucket, dir) in [("fail_author", root.join(FAIL_AUTHOR_DIR)), ("fail_title", root.join(FAIL_TITLE_DIR))] {
+        for path in files(&dir, 1) {
+            items.push(item(bucket, &dir, path, &books, None));
+        }
+    }
+    let mut seen = BTreeSet::new();
+    if let Some(dir) = quarantine {
+        for path in files(dir, usize::MAX) {
+            let origin = quarantined.get(&absolute(&path)).cloned();
+            seen.insert(absolute(&path));
+            items.push(item("quarantine", dir, path, &books, origin));
+        }
+    }
+    for (dst, src) in &quarantined {
+        let path = PathBuf::from(dst);
+        if path.is_file() && seen.insert(dst.clone()) {
+            let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            items.push(item("quarantine", &dir, path, &books, Some(src.clone())));
+        }
+    }
+    Ok(items)
+}
+
+impl Dashboard {
+    fn items(&self) -> Result<Vec<Item>> {
+        pending(&self.root, self.ledger.as_deref(), self.quarantine.as_deref())
+    }
+
+    fn connect(&self) -> rusqlite::Result<Connection> {
+        Connection::open_with_flags(&self.db, OpenFlags::SQLITE_OPEN_READ_ONLY)
+    }
+
+    /// Works matching `title` and authors matching `author`, from each provider in order (in the OpenLibrary
+    /// dump: normalized title or name starting with them). A failed provider is skipped.
+    async fn candidates(&self, title: &str, author: &str) -> Result<Candidates> {
+        let (mut works, mut authors) = (Vec::new(), Vec::new());
+        for provider in self.registry.iter() {
+            let kind = provider.kind();
+            let ids = |id: String| if kind.is_openlibrary() { id } else { String::new() };
+            if !title.trim().is_empty() {
+                match provider.search_title(title, (!author.trim().is_empty()).then_some(author)).await {
+                    Ok(hits) => works.extend(hits.into_iter().take(CANDIDATES).map(|hit| Work {
+                        source: kind.name(),
+                        work_id: ids(hit.work_id),
+                        title: hit.title,
+                        author_id: ids(hit.author_id),
+                        author: hit.author,
+                    })),
+                    Err(e) => eprintln!("[warn] {}: {e:#}", kind.name()),
+                }
+            }
+            if !author.trim().is_empty() {
+                match provider.search_author(author).await {
+                    Ok(hits) => authors.extend(hits.into_iter().take(CANDIDATES).map(|hit| Author {
+                        source: kind.name(),
+                        author_id: ids(hit.author_id),
+                        name: hit.name,
+                    })),
+                    Err(e) => eprintln!("[warn] {}: {e:#}", kind.name()),
+                }
+            }
+        }
+        Ok(Candidates { works, authors })
+    }
+
+    /// Moves the item and records the move (and the new description of the book) as a `dashboard` run; in dry-run,
+    /// only reports the target.
+    fn resolve(&self, request: &Resolve) -> Result<Resolved> {
+        let items = self.items()?;
+        let Some(item) = items.iter().find(|i| i.id == request.id) else { bail!("{} is no longer pending", request.id) };
+        let (target, detail, meta) = match request.action.as_str() {
+            "restore" => {
+                let Some(origin) = &item.origin else { bail!("The ledger does not know where {} came from", item.name) };
+                (PathBuf::from(origin), "restore", None)
+            }
+            "file" => {
+                let (first, last, title) = (request.first.trim(), request.last.trim(), request.title.trim());
+                if last.is_empty() || title.is_empty() {
+                    bail!("A last name and a title are needed");
+                }
+                let conn = self.connect()?;
+                let (first, last) = (sanitize_component(first), sanitize_component(last));
+                let dir = author_out_dir(&conn, &self.root.join(SORTED_DIR), self.library_authors, request.author_id.as_deref(), &first, &last)?;
+                let ext = item.path.extension().map(|e| e.to_string_lossy().into_owned()).unwrap_or_else(|| "bin".into());
+                let author = format!("{first} {last}").trim().to_string();
+                let name = sanitize_component(&format!("{} - {author}.{ext}", title));
+                let meta = BookMeta {
+                    work_id: request.work_id.clone().filter(|w| !w.is_empty()),
+                    author_id: request.author_id.clone().filter(|a| !a.is_empty()),
+                    title: Some(title.to_string()),
+                    author: Some(author),
+                    ..BookMeta::default()
+                };
+                (dir.join(name), "triage", Some(meta))
+            }
+            other => bail!("Unknown action {other}"),
+        };
+        if target.exists() {
+            bail!("{} already exists", target.display());
+        }
+        if self.dry_run {
+            println!("[DRY-RUN] {} {} -> {}", detail, item.path.display(), target.display());
+            return Ok(Resolved { target: target.display().to_string(), run_id: None, dry_run: true });
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if fs::rename(&item.path, &target).is_err() {
+            fs::copy(&item.path, &target)?;
+            fs::remove_file(&item.path)?;
+        }
+        let mut run_id = None;
+        if let Some(path) = &self.ledger {
+            let mut ledger = Ledger::open(path, "dashboard", &[request.action.clone(), request.id.clone()])?;
+            ledger.record(Op::Move, &item.path, Some(&target), detail)?;
+            if let Some(meta) = &meta {
+                ledger.describe(&target, meta)?;
+            }
+            ledger.finish("ok")?;
+            run_id = ledger.run_id();
+        }
+        println!("{} {} -> {}", detail, item.path.display(), target.display());
+        Ok(Resolved { target: target.display().to_string(), run_id, dry_run: false })
+    }
+}
+
+async fn items(State(state): State<Arc<Dashboard>>) -> Result<Json<Vec<Item>>, ApiError> {
+    blocking(move || state.items().map_err(internal)).await.map(Json)
+}
+
+async fn candidates(State(state): State<Arc<Dashboard>>, Query(query): Query<CandidatesQuery>) -> Result<Json<Candidates>, ApiError> {
+    let title = query.title.unwrap_or_default();
+    let author = query.author.unwrap_or_default();
+    state.candidates(&title, &author).await.map(Json).map_err(internal)
+}
+
+async fn resolve(State(state): State<Arc<Dashboard>>, Json(request): Json<Resolve>) -> Response {
+    match blocking(move || state.resolve(&request).map_err(|e| (StatusCode::CONFLICT, e.to_string()))).await {
+        Ok(resolved) => Json(resolved).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Runs file-system and SQLite work (folder listings, moves, copies) on tokio's blocking threads,
+/// so a slow disk does not hold up the other requests.
+pub(crate) async fn blocking<T: Send + 'static>(work: impl FnOnce() -> Result<T, ApiError> + Send + 'static) -> Result<T, ApiError> {
+    tokio::task::spawn_blocking(work).await.map_err(|e| internal(e.into()))?
+}
+
+/// A 500 for an unexpected failure: the full error goes to the server log, the client only gets a
+/// generic message (errors carry absolute paths of the library).
+pub(crate) fn internal(e: anyhow::Error) -> ApiError {
+    eprintln!("[error] {e:#}");
+    (StatusCode::INTERNAL_SERVER_ERROR, "Internal error".into())
+}
+
+fn item(bucket: &'static str, dir: &Path, path: PathBuf, books: &HashMap<String, BookMeta>, origin: Option<String>) -> Item {
+    let relative = path.strip_prefix(dir).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+    let meta = books.get(&absolute(&path));
+    Item {
+        id: format!("{bucket}/{relative}"),
+        bucket,
+        name: path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+        size: fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+        guess_title: meta.and_then(|m| m.title.clone()),
+        guess_author: meta.and_then(|m| m.author.clone()),
+        origin,
+        path,
+    }
+}
+
+/// Files under `dir` down to `depth`, hidden ones excluded, in name order.
+fn files(dir: &Path, depth: usize) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = WalkDir::new(dir)
+        .min_depth(1)
+        .max_depth(depth)
+        .into_iter()
+        .filter_entry(|e| !e.file_name().to_string_lossy().starts_with('.'))
+        .flatten()
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .collect();
+    files.sort();
+    files
+}
+
+/// Quarantined path -> original path, from the `quarantine_file` moves of the ledger.
+fn quarantine_origins(path: &Path) -> Result<HashMap<String, String>> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut stmt = conn.prepare("SELECT dst, src FROM operations WHERE op = 'move' AND detail = 'quarantine_file' AND dst IS NOT NULL ORDER BY op_id")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+fn absolute(path: &Path) -> String {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()).to_string_lossy().into_owned()
+}
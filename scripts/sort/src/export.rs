@@ -0,0 +1,301 @@
+use crate::catalog::{self, Entry, Sources};
+use anyhow::{bail, Context, Result};
+use clap::{Args, ValueEnum};
+use serde::{Deserialize, Serialize};
+use sortbook_common::ledger::{Ledger, Op};
+use sortbook_common::sanitize::sanitize_component;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Sync manifest, in the books folder of the device: what was copied there, and from which library file.
+const MANIFEST_NAME: &str = ".sortbook_sync.json";
+/// Ledger detail of the copies made by `export`.
+const EXPORT_DETAIL: &str = "export";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+    /// Kobo: books at the top of the device, kepub/EPUB first
+    Kobo,
+    /// Kindle: books in documents/, AZW3/MOBI (no EPUB over USB)
+    Kindle,
+}
+
+impl Profile {
+    /// Formats the device reads, best first (`kepub` is a `.kepub.epub` file).
+    fn formats(self) -> &'static [&'static str] {
+        match self {
+            Profile::Kobo => &["kepub", "epub", "pdf", "cbz", "cbr", "mobi", "txt", "rtf", "html"],
+            Profile::Kindle => &["azw3", "azw", "mobi", "kfx", "pdf", "txt"],
+        }
+    }
+
+    /// Folder of the books, relative to the mount point.
+    fn books_dir(self) -> &'static str {
+        match self {
+            Profile::Kobo => "",
+            Profile::Kindle => "documents",
+        }
+    }
+
+    /// Longest file or folder name, in characters; FAT32 allows 255, but the readers' indexers and
+    /// 255-byte paths give up earlier.
+    fn max_name(self) -> usize {
+        match self {
+            Profile::Kobo => 120,
+            Profile::Kindle => 100,
+        }
+    }
+}
+
+/// Options of `sortbook export`; the library and ledger come from the global options.
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    /// Mount point of the e-reader (e.g. /media/$USER/KOBOeReader)
+    pub device: PathBuf,
+
+    /// Device profile: formats, books folder and name length
+    #[arg(long, value_enum)]
+    pub profile: Profile,
+
+    /// Library folder (default: <root>/output/sorted_books)
+    #[arg(long)]
+    pub library: Option<PathBuf>,
+
+    /// Only authors whose folder has these words (word prefixes, accents and case ignored); repeatable
+    #[arg(long)]
+    pub author: Vec<String>,
+
+    /// Only titles with these words (word prefixes, accents and case ignored)
+    #[arg(long)]
+    pub title: Option<String>,
+
+    /// Only books sorted (or, without ledger entry, modified) on or after this date (YYYY-MM-DD)
+    #[arg(long)]
+    pub since: Option<String>,
+
+    /// Formats to copy, best first, comma separated (default: the profile's)
+    #[arg(long, default_value = "")]
+    pub formats: String,
+
+    /// Longest file or folder name on the device, in characters (default: the profile's)
+    #[arg(long)]
+    pub max_name: Option<usize>,
+
+    /// Sync manifest (default: .sortbook_sync.json in the books folder of the device)
+    #[arg(long)]
+    pub manifest: Option<PathBuf>,
+
+    /// Copy again the books deleted from the device since they were exported
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub recopy_deleted: bool,
+
+    /// Simulate (true) or apply (false); defaults to the global --dry-run, else true
+    #[arg(long, action = clap::ArgAction::Set)]
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    profile: Option<Profile>,
+    updated_at: String,
+    /// Library path (relative, `/` separators) -> its copy on the device.
+    files: BTreeMap<String, Synced>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Synced {
+    /// Path relative to the mount point, `/` separators.
+    device: String,
+    size: u64,
+    modified: String,
+    copied_at: String,
+}
+
+pub fn run(args: &ExportArgs, root: &Path, db: Option<&Path>, ledger: Option<&Path>, global_dry_run: Option<bool>) -> Result<()> {
+    let dry_run = args.dry_run.or(global_dry_run).unwrap_or(true);
+    if !args.device.is_dir() {
+        bail!("Device {} not found (not mounted?)", args.device.display());
+    }
+    let profile = args.profile;
+    let formats: Vec<String> = match args.formats.trim() {
+        "" => profile.formats().iter().map(|f| f.to_string()).collect(),
+        list => list.split(',').map(|f| f.trim().trim_start_matches('.').to_ascii_lowercase()).filter(|f| !f.is_empty()).collect(),
+    };
+    let max_name = args.max_name.unwrap_or(profile.max_name()).max(16);
+    if let Some(since) = &args.since {
+        chrono::NaiveDate::parse_from_str(since, "%Y-%m-%d").with_context(|| format!("--since {since}: expected YYYY-MM-DD"))?;
+    }
+    let library = std::path::absolute(args.library.clone().unwrap_or_else(|| root.join(crate::SORTED_DIR)))?;
+    // Only the ledger's titles and dates are needed; ISBNs are not read from the EPUB files
+    let entries = catalog::scan(&library, &Sources::resolve(root, db, ledger)?, "", false)?;
+    let books_dir = args.device.join(profile.books_dir());
+    let manifest_path = args.manifest.clone().unwrap_or_else(|| books_dir.join(MANIFEST_NAME));
+    let mut manifest: Manifest = match fs::read_to_string(&manifest_path) {
+        Ok(text) => serde_json::from_str(&text).with_context(|| format!("Reading {}", manifest_path.display()))?,
+        Err(_) => Manifest::default(),
+    };
+    if manifest.profile.is_some_and(|p| p != profile) {
+        println!("Note: {} was written for another profile", manifest_path.display());
+    }
+
+    let selected = select(&entries, args);
+    let mut ledger = Ledger::for_run(ledger, "export", dry_run)?;
+    println!(
+        "Exporting {} book(s) to {} ({:?}){}",
+        selected.len(),
+        books_dir.display(),
+        profile,
+        if dry_run { " (dry-run)" } else { "" }
+    );
+    let (mut copied, mut current, mut skipped) = (0usize, 0usize, 0usize);
+    let mut unreadable = 0usize;
+    // A failed copy stops the loop, but the copies made so far still go into the manifest
+    let mut sync = || -> Result<()> {
+        for files in selected.values() {
+            let Some(entry) = best_format(files, &formats) else {
+                unreadable += 1;
+                continue;
+            };
+            let synced = manifest.files.get(&entry.path);
+            let target = match synced {
+                Some(synced) => args.device.join(&synced.device),
+                None => books_dir.join(device_name(&entry.author, max_name)).join(device_name(file_name(entry), max_name)),
+            };
+            if let Some(synced) = synced {
+                let unchanged = synced.size == entry.size && synced.modified == entry.modified;
+                if !target.is_file() && !args.recopy_deleted {
+                    println!("  skip {} (deleted from the device; --recopy-deleted copies it again)", entry.path);
+                    skipped += 1;
+                    continue;
+                }
+                if target.is_file() && unchanged {
+                    current += 1;
+                    continue;
+                }
+            } else if target.exists() {
+                println!("  skip {} ({} already on the device)", entry.path, target.display());
+                skipped += 1;
+                continue;
+            }
+            let source = library.join(&entry.path);
+            if dry_run {
+                println!("[DRY-RUN] copy {} -> {}", entry.path, target.display());
+            } else {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent).with_context(|| format!("Creating {}", parent.display()))?;
+                }
+                fs::copy(&source, &target).with_context(|| format!("Copying {} to {}", source.display(), target.display()))?;
+                let device = target.strip_prefix(&args.device).unwrap_or(&target);
+                manifest.files.insert(
+                    entry.path.clone(),
+                    Synced {
+                        device: device.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/"),
+                        size: entry.size,
+                        modified: entry.modified.clone(),
+                        copied_at: chrono::Utc::now().to_rfc3339(),
+                    },
+                );
+                ledger.record(Op::Copy, &source, Some(&target), EXPORT_DETAIL)?;
+            }
+            copied += 1;
+        }
+        Ok(())
+    };
+    let result = sync();
+    let saved = if dry_run { Ok(()) } else { save_manifest(&manifest_path, &mut manifest, profile) };
+    ledger.finish(if result.is_ok() && saved.is_ok() { "ok" } else { "failed" })?;
+    if let Err(e) = result {
+        if !dry_run && saved.is_ok() {
+            println!("Export stopped after {copied} copied book(s), recorded in {}", manifest_path.display());
+        }
+        return Err(e);
+    }
+    saved?;
+    println!(
+        "Export done: {copied} copied, {current} already up to date, {skipped} skipped, {unreadable} without a format the device reads ({}).",
+        formats.join(",")
+    );
+    Ok(())
+}
+
+/// Writes the manifest next to its path then renames it, so an unplugged device keeps the previous one.
+fn save_manifest(path: &Path, manifest: &mut Manifest, profile: Profile) -> Result<()> {
+    manifest.profile = Some(profile);
+    manifest.updated_at = chrono::Utc::now().to_rfc3339();
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let partial = path.with_extension("json.partial");
+    fs::write(&partial, serde_json::to_string_pretty(manifest)? + "\n")?;
+    fs::rename(&partial, path).with_context(|| format!("Writing {}", path.display()))
+}
+
+/// Selected books, each with all its formats: files of the same folder whose names only differ by the
+/// extension are one book.
+fn select<'a>(entries: &'a [Entry], args: &ExportArgs) -> BTreeMap<String, Vec<&'a Entry>> {
+    let words = |text: &str| -> Vec<String> { crate::normalize_text(text).split_whitespace().map(String::from).collect() };
+    let has_all = |text: &str, wanted: &[String]| {
+        let text = crate::normalize_text(text);
+        wanted.iter().all(|w| text.split_whitespace().any(|t| t.starts_with(w.as_str())))
+    };
+    let authors: Vec<Vec<String>> = args.author.iter().map(|a| words(a)).filter(|w| !w.is_empty()).collect();
+    let title = args.title.as_deref().map(words).unwrap_or_default();
+    let mut books: BTreeMap<String, Vec<&Entry>> = BTreeMap::new();
+    for entry in entries {
+        if !authors.is_empty() && !authors.iter().any(|wanted| has_all(&entry.author, wanted)) {
+            continue;
+        }
+        if !title.is_empty() && !has_all(&entry.title, &title) {
+            continue;
+        }
+        if let Some(since) = &args.since {
+            let date = entry.sorted_at.as_deref().unwrap_or(&entry.modified);
+            if date.get(..10).unwrap_or(date) < since.as_str() {
+                continue;
+            }
+        }
+        books.entry(entry.book_key()).or_default().push(entry);
+    }
+    books
+}
+
+/// Format of a file for the device: `kepub` for `.kepub.epub`, else the extension.
+fn device_format(entry: &Entry) -> &str {
+    if entry.format == "epub" && entry.path.to_lowercase().ends_with(".kepub.epub") {
+        "kepub"
+    } else {
+        &entry.format
+    }
+}
+
+fn best_format<'a>(files: &[&'a Entry], formats: &[String]) -> Option<&'a Entry> {
+    formats.iter().find_map(|format| files.iter().find(|e| device_format(e) == format).copied())
+}
+
+/// Name of the file in the library (the sorter's `<Title> - <First Last>.<ext>`, or the cleanup-filenames template).
+fn file_name(entry: &Entry) -> &str {
+    entry.path.rsplit('/').next().unwrap_or(&entry.path)
+}
+
+/// A name safe on the device's FAT/exFAT file system and at most `max` characters long; the end of the
+/// stem is cut (at a space when one is close), the extension is kept.
+fn device_name(name: &str, max: usize) -> String {
+    let name = sanitize_component(name);
+    if name.chars().count() <= max {
+        return name;
+    }
+    let dots = if name.to_lowercase().ends_with(".kepub.epub") { 2 } else { 1 };
+    let split = name.rmatch_indices('.').nth(dots - 1).map(|(i, _)| i).filter(|i| name.len() - i <= 12);
+    let (stem, ext) = match split {
+        Some(index) => name.split_at(index),
+        None => (name.as_str(), ""),
+    };
+    let keep = max.saturating_sub(ext.chars().count()).max(1);
+    let mut stem: String = stem.chars().take(keep).collect();
+    if let Some(space) = stem.rfind(' ').filter(|i| *i >= stem.len() * 3 / 4) {
+        stem.truncate(space);
+    }
+    sanitize_component(&format!("{}{ext}", stem.trim_end()))
+}
@@ -0,0 +1,239 @@
+use crate::catalog::{self, display_author, Entry, Sources};
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+use sortbook_common::matching::{normalize_isbn, BookIndex};
+use std::collections::hash_map::Entry::{Occupied, Vacant};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Columns of a Goodreads library export, the format Goodreads and StoryGraph both import.
+const GOODREADS_HEADER: [&str; 24] = [
+    "Book Id", "Title", "Author", "Author l-f", "Additional Authors", "ISBN", "ISBN13", "My Rating", "Average Rating",
+    "Publisher", "Binding", "Number of Pages", "Year Published", "Original Publication Year", "Date Read", "Date Added",
+    "Bookshelves", "Bookshelves with positions", "Exclusive Shelf", "My Review", "Spoiler", "Private Notes", "Read Count",
+    "Owned Copies",
+];
+
+/// Options of `sortbook goodreads`; the project, database and ledger come from the global options.
+#[derive(Args, Debug)]
+pub struct GoodreadsArgs {
+    #[command(subcommand)]
+    pub action: GoodreadsAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GoodreadsAction {
+    /// Write the library as a Goodreads CSV (imported by Goodreads and StoryGraph), one row per book
+    Export {
+        /// Library folder (default: <root>/output/sorted_books)
+        #[arg(long)]
+        library: Option<PathBuf>,
+        /// Output file (default: <root>/data/goodreads_export.csv); `-` writes to stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Shelf given to every book
+        #[arg(long, default_value = "owned")]
+        shelf: String,
+        /// Do not open EPUB files to read the ISBN of books the ledger has none for
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        no_epub: bool,
+    },
+    /// Match a Goodreads or StoryGraph export to the library and report which books are already owned
+    Import {
+        /// CSV exported by Goodreads (My Books > Import and export) or StoryGraph
+        csv: PathBuf,
+        /// Library folder (default: <root>/output/sorted_books)
+        #[arg(long)]
+        library: Option<PathBuf>,
+        /// Match report (default: <root>/data/goodreads_matches.csv)
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Also write the CSV back with the owned books tagged, ready to import again
+        #[arg(long)]
+        tagged: Option<PathBuf>,
+        /// Shelf (Goodreads) or tag (StoryGraph) added to the owned books in --tagged
+        #[arg(long, default_value = "owned")]
+        shelf: String,
+        /// Do not open EPUB files to read the ISBN of books the ledger has none for
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        no_epub: bool,
+    },
+}
+
+pub fn run(args: &GoodreadsArgs, root: &Path, db: Option<&Path>, ledger: Option<&Path>) -> Result<()> {
+    let sources = Sources::resolve(root, db, ledger)?;
+    match &args.action {
+        GoodreadsAction::Export { library, out, shelf, no_epub } => {
+            let library = library.clone().unwrap_or_else(|| root.join(crate::SORTED_DIR));
+            let entries = catalog::scan(&library, &sources, "", !no_epub)?;
+            let out = out.clone().unwrap_or_else(|| root.join("data/goodreads_export.csv"));
+            export(&books(&entries), &out, shelf)
+        }
+        GoodreadsAction::Import { csv, library, out, tagged, shelf, no_epub } => {
+            let library = library.clone().unwrap_or_else(|| root.join(crate::SORTED_DIR));
+            let entries = catalog::scan(&library, &sources, "", !no_epub)?;
+            let out = out.clone().unwrap_or_else(|| root.join("data/goodreads_matches.csv"));
+            import(csv, &books(&entries), &out, tagged.as_deref(), shelf)
+        }
+    }
+}
+
+/// One entry per book (`Entry::book_key`), the first format in path order unless a later one has an ISBN.
+fn books(entries: &[Entry]) -> Vec<&Entry> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut books: Vec<&Entry> = Vec::new();
+    for entry in entries {
+        match seen.entry(entry.book_key()) {
+            Vacant(slot) => {
+                slot.insert(books.len());
+                books.push(entry);
+            }
+            // A format with an ISBN describes the book better than one without
+            Occupied(slot) if books[*slot.get()].isbn.is_none() && entry.isbn.is_some() => books[*slot.get()] = entry,
+            Occupied(_) => {}
+        }
+    }
+    books
+}
+
+fn export(books: &[&Entry], out: &Path, shelf: &str) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(GOODREADS_HEADER)?;
+    for book in books {
+        let isbn13 = book.isbn.as_deref().and_then(normalize_isbn);
+        let isbn10 = book.isbn.as_deref().map(|isbn| isbn.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>()).filter(|isbn| isbn.len() == 10);
+        let added = book.sorted_at.as_deref().unwrap_or(&book.modified).get(..10).unwrap_or_default().replace('-', "/");
+        let mut row = vec![String::new(); GOODREADS_HEADER.len()];
+        for (column, value) in [
+            ("Title", book.title.clone()),
+            ("Author", display_author(&book.author)),
+            ("Author l-f", book.author.clone()),
+            // Spreadsheet quoting, as in Goodreads' own exports, so leading zeros survive
+            ("ISBN", isbn10.map(|isbn| format!("=\"{isbn}\"")).unwrap_or_default()),
+            ("ISBN13", isbn13.map(|isbn| format!("=\"{isbn}\"")).unwrap_or_default()),
+            ("My Rating", "0".to_string()),
+            ("Binding", "ebook".to_string()),
+            ("Date Added", added),
+            ("Bookshelves", shelf.to_string()),
+            ("Exclusive Shelf", "to-read".to_string()),
+            ("Read Count", "0".to_string()),
+            ("Owned Copies", "1".to_string()),
+        ] {
+            row[column_index(&GOODREADS_HEADER, column)] = value;
+        }
+        writer.write_record(&row)?;
+    }
+    if write(out, writer.into_inner().map_err(|e| e.into_error())?)? {
+        return Ok(());
+    }
+    let with_isbn = books.iter().filter(|b| b.isbn.is_some()).count();
+    println!("Goodreads CSV: {} book(s), {} with an ISBN -> {}", books.len(), with_isbn, out.display());
+    Ok(())
+}
+
+/// Columns of the export being read: Goodreads (`Author`, `ISBN`, `ISBN13`, `Bookshelves`, `Owned Copies`)
+/// or StoryGraph (`Authors`, `ISBN/UID`, `Tags`, `Owned?`).
+struct Columns {
+    title: usize,
+    author: usize,
+    isbns: Vec<usize>,
+    storygraph: bool,
+}
+
+fn import(path: &Path, books: &[&Entry], out: &Path, tagged: Option<&Path>, shelf: &str) -> Result<()> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_path(path).with_context(|| format!("Reading {}", path.display()))?;
+    let header: Vec<String> = reader.headers()?.iter().map(|h| h.trim_start_matches('\u{feff}').trim().to_string()).collect();
+    let find = |name: &str| header.iter().position(|h| h.eq_ignore_ascii_case(name));
+    let storygraph = find("ISBN/UID").is_some();
+    let columns = Columns {
+        title: find("Title").context("No Title column (not a Goodreads or StoryGraph export?)")?,
+        author: find(if storygraph { "Authors" } else { "Author" }).context("No Author column (not a Goodreads or StoryGraph export?)")?,
+        isbns: ["ISBN", "ISBN13", "ISBN/UID"].iter().filter_map(|name| find(name)).collect(),
+        storygraph,
+    };
+
+    let mut index = BookIndex::default();
+    for (id, book) in books.iter().enumerate() {
+        index.add(id, book.isbn.as_deref(), &book.title, &display_author(&book.author));
+    }
+
+    let mut report = csv::Writer::from_writer(Vec::new());
+    report.write_record(["title", "author", "isbn", "status", "match", "path"])?;
+    let mut rows = Vec::new();
+    let (mut owned, mut missing) = (0usize, 0usize);
+    for record in reader.records() {
+        let mut record: Vec<String> = record?.iter().map(String::from).collect();
+        record.resize(header.len(), String::new());
+        let title = &record[columns.title];
+        // StoryGraph lists co-authors in the same cell
+        let author = record[columns.author].split(", ").next().unwrap_or_default();
+        let isbns: Vec<&str> = columns.isbns.iter().map(|i| record[*i].as_str()).collect();
+        let found = index.find(&isbns, title, author);
+        let isbn = isbns.iter().find_map(|isbn| normalize_isbn(isbn)).unwrap_or_default();
+        match found {
+            Some((id, kind)) => {
+                owned += 1;
+                report.write_record([title, author, &isbn, "owned", kind.as_str(), &books[id].path])?;
+                tag(&mut record, &header, &columns, shelf);
+            }
+            None => {
+                missing += 1;
+                report.write_record([title, author, &isbn, "missing", "", ""])?;
+            }
+        }
+        rows.push(record);
+    }
+    let stdout = write(out, report.into_inner().map_err(|e| e.into_error())?)?;
+    if let Some(tagged) = tagged {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(&header)?;
+        for row in &rows {
+            writer.write_record(row)?;
+        }
+        if !write(tagged, writer.into_inner().map_err(|e| e.into_error())?)? {
+            println!("Tagged CSV ({}): {}", if columns.storygraph { "StoryGraph" } else { "Goodreads" }, tagged.display());
+        }
+    }
+    if !stdout {
+        println!("Import done: {owned} owned, {missing} missing -> {}", out.display());
+    }
+    Ok(())
+}
+
+/// Marks a row as owned: the shelf in `Bookshelves` and `Owned Copies` (Goodreads), or the tag in `Tags`
+/// and `Owned?` (StoryGraph); absent columns are left out.
+fn tag(record: &mut [String], header: &[String], columns: &Columns, shelf: &str) {
+    let position = |name: &str| header.iter().position(|h| h.eq_ignore_ascii_case(name));
+    let (list, flag, yes) = if columns.storygraph { ("Tags", "Owned?", "Yes") } else { ("Bookshelves", "Owned Copies", "1") };
+    if let Some(index) = position(list) {
+        let cell = &mut record[index];
+        if !cell.split(',').any(|s| s.trim().eq_ignore_ascii_case(shelf)) {
+            *cell = if cell.trim().is_empty() { shelf.to_string() } else { format!("{}, {shelf}", cell.trim()) };
+        }
+    }
+    if let Some(index) = position(flag) {
+        let cell = &mut record[index];
+        if cell.trim().is_empty() || cell.trim() == "0" || cell.trim().eq_ignore_ascii_case("no") {
+            *cell = yes.to_string();
+        }
+    }
+}
+
+fn column_index(header: &[&str], name: &str) -> usize {
+    header.iter().position(|h| *h == name).unwrap_or_default()
+}
+
+/// Writes a CSV to `out`, or to stdout for `-` (then returns true).
+fn write(out: &Path, body: Vec<u8>) -> Result<bool> {
+    if out.as_os_str() == "-" {
+        std::io::stdout().write_all(&body)?;
+        return Ok(true);
+    }
+    if let Some(parent) = out.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(out, body).with_context(|| format!("Writing {}", out.display()))?;
+    Ok(false)
+}
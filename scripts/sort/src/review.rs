@@ -0,0 +1,371 @@
+//! Progress of a sorter run: the usual progress bar, or with `--review` a terminal screen that
+//! streams the decisions and asks about low-confidence matches (author-only and raw file-name
+//! matches) before they are copied.
+
+use anyhow::{bail, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Gauge, List, ListItem, Paragraph};
+use ratatui::Frame;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Decisions kept on screen.
+const HISTORY: usize = 500;
+
+/// A low-confidence match, as the sorter would copy it.
+pub(crate) struct Proposal {
+    pub mode: &'static str,
+    pub file: String,
+    /// Guessed title (the file stem when the LLM gave none), prefilled when correcting.
+    pub title: String,
+    pub first: String,
+    pub last: String,
+    pub dest: PathBuf,
+}
+
+/// Title and author typed in review, replacing the match.
+pub(crate) struct Correction {
+    pub title: String,
+    pub first: String,
+    pub last: String,
+}
+
+pub(crate) enum Verdict {
+    Approve,
+    Correct(Correction),
+    /// Not copied and not recorded, so the next run tries the file again.
+    Defer,
+}
+
+enum Message {
+    Start(String),
+    Decision(String),
+    Inc(u64),
+    Review(Proposal, oneshot::Sender<Verdict>),
+    Done,
+}
+
+struct Screen {
+    tx: mpsc::Sender<Message>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Same calls as the progress bar it replaces (`inc`, `set_message`, `finish_with_message`).
+pub(crate) struct Progress {
+    bar: ProgressBar,
+    screen: Option<Screen>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Progress {
+    pub fn new(len: u64, review: bool) -> Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        if !review {
+            let bar = ProgressBar::new(len);
+            // Main progress bar (global progress)
+            bar.set_style(
+                ProgressStyle::with_template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}")
+                    .unwrap(),
+            );
+            return Ok(Self { bar, screen: None, stop });
+        }
+        if !std::io::stdout().is_terminal() {
+            bail!("--review needs a terminal");
+        }
+        let (tx, rx) = mpsc::channel();
+        let app = App::new(len, stop.clone());
+        let thread = std::thread::spawn(move || app.run(rx));
+        Ok(Self { bar: ProgressBar::hidden(), screen: Some(Screen { tx, thread: Some(thread) }), stop })
+    }
+
+    /// Announces the file being processed.
+    pub fn start(&self, idx: usize, mode: &str, filename: &str) {
+        match &self.screen {
+            Some(screen) => {
+                let _ = screen.tx.send(Message::Start(format!("#{idx} [{mode}] {filename}")));
+            }
+            None => sortbook_common::say!("→ File #{idx} [{mode}]: {}", filename),
+        }
+    }
+
+    pub fn inc(&self, delta: u64) {
+        self.bar.inc(delta);
+        if let Some(screen) = &self.screen {
+            let _ = screen.tx.send(Message::Inc(delta));
+        }
+    }
+
+    pub fn set_message(&self, message: String) {
+        if let Some(screen) = &self.screen {
+            let _ = screen.tx.send(Message::Decision(message.clone()));
+        }
+        self.bar.set_message(message);
+    }
+
+    pub fn finish_with_message(&mut self, message: &'static str) {
+        self.bar.finish_with_message(message);
+        self.close();
+    }
+
+    /// The user asked to stop after the current file.
+    pub fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    /// Waits for the user's verdict on a low-confidence match; without `--review` it is approved.
+    pub async fn review(&self, proposal: Proposal) -> Verdict {
+        let Some(screen) = &self.screen else { return Verdict::Approve };
+        let (tx, rx) = oneshot::channel();
+        if screen.tx.send(Message::Review(proposal, tx)).is_err() {
+            return Verdict::Defer;
+        }
+        rx.await.unwrap_or(Verdict::Defer)
+    }
+
+    fn close(&mut self) {
+        if let Some(mut screen) = self.screen.take() {
+            let _ = screen.tx.send(Message::Done);
+            if let Some(thread) = screen.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+impl Drop for Progress {
+    // Restores the terminal when the run ends early on an error.
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+struct Pending {
+    proposal: Proposal,
+    reply: oneshot::Sender<Verdict>,
+    /// Title, first name and last name being typed, and the field with the cursor.
+    editing: Option<([String; 3], usize)>,
+}
+
+struct App {
+    total: u64,
+    done: u64,
+    current: String,
+    decisions: Vec<String>,
+    pending: Option<Pending>,
+    stop: Arc<AtomicBool>,
+    approved: usize,
+    corrected: usize,
+    deferred: usize,
+}
+
+impl App {
+    fn new(total: u64, stop: Arc<AtomicBool>) -> Self {
+        Self {
+            total,
+            done: 0,
+            current: String::new(),
+            decisions: Vec::new(),
+            pending: None,
+            stop,
+            approved: 0,
+            corrected: 0,
+            deferred: 0,
+        }
+    }
+
+    fn run(mut self, rx: mpsc::Receiver<Message>) {
+        let mut terminal = ratatui::init();
+        'screen: loop {
+            loop {
+                match rx.try_recv() {
+                    Ok(Message::Done) | Err(mpsc::TryRecvError::Disconnected) => break 'screen,
+                    Ok(message) => self.apply(message),
+                    Err(mpsc::TryRecvError::Empty) => break,
+                }
+            }
+            let _ = terminal.draw(|frame| self.draw(frame));
+            if event::poll(Duration::from_millis(100)).unwrap_or(false) {
+                if let Ok(Event::Key(key)) = event::read() {
+                    if key.kind == KeyEventKind::Press {
+                        self.key(key);
+                    }
+                }
+            }
+        }
+        ratatui::restore();
+        println!(
+            "Review: {}/{} file(s) processed, {} approved, {} corrected, {} deferred{}",
+            self.done,
+            self.total,
+            self.approved,
+            self.corrected,
+            self.deferred,
+            if self.stop.load(Ordering::Relaxed) { " (stopped)" } else { "" }
+        );
+    }
+
+    fn apply(&mut self, message: Message) {
+        match message {
+            Message::Start(current) => self.current = current,
+            Message::Decision(decision) => {
+                let name = self.current.split_once("] ").map(|(_, name)| name).unwrap_or("");
+                self.decisions.push(format!("{decision}  {name}"));
+                if self.decisions.len() > HISTORY {
+                    self.decisions.remove(0);
+                }
+            }
+            Message::Inc(delta) => self.done += delta,
+            Message::Review(proposal, reply) => self.pending = Some(Pending { proposal, reply, editing: None }),
+            Message::Done => {}
+        }
+    }
+
+    fn reply(&mut self, verdict: Verdict) {
+        if let Some(pending) = self.pending.take() {
+            match verdict {
+                Verdict::Approve => self.approved += 1,
+                Verdict::Correct(_) => self.corrected += 1,
+                Verdict::Defer => self.deferred += 1,
+            }
+            let _ = pending.reply.send(verdict);
+        }
+    }
+
+    fn key(&mut self, key: KeyEvent) {
+        let ctrl_c = key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c');
+        let Some(pending) = &mut self.pending else {
+            if ctrl_c || matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                self.stop.store(true, Ordering::Relaxed);
+            }
+            return;
+        };
+        if ctrl_c {
+            self.stop.store(true, Ordering::Relaxed);
+            self.reply(Verdict::Defer);
+            return;
+        }
+        if let Some((fields, focus)) = &mut pending.editing {
+            match key.code {
+                KeyCode::Esc => pending.editing = None,
+                KeyCode::Tab | KeyCode::Down => *focus = (*focus + 1) % 3,
+                KeyCode::BackTab | KeyCode::Up => *focus = (*focus + 2) % 3,
+                KeyCode::Backspace => {
+                    fields[*focus].pop();
+                }
+                KeyCode::Char(ch) if !key.modifiers.contains(KeyModifiers::CONTROL) => fields[*focus].push(ch),
+                KeyCode::Enter => {
+                    let [title, first, last] = fields.clone().map(|f| f.trim().to_string());
+                    if !title.is_empty() && !last.is_empty() {
+                        self.reply(Verdict::Correct(Correction { title, first, last }));
+                    }
+                }
+                _ => {}
+            }
+            return;
+        }
+        match key.code {
+            KeyCode::Char('a') | KeyCode::Enter => self.reply(Verdict::Approve),
+            KeyCode::Char('c') => {
+                let p = &pending.proposal;
+                pending.editing = Some(([p.title.clone(), p.first.clone(), p.last.clone()], 0));
+            }
+            KeyCode::Char('d') => self.reply(Verdict::Defer),
+            KeyCode::Char('q') | KeyCode::Esc => {
+                self.stop.store(true, Ordering::Relaxed);
+                self.reply(Verdict::Defer);
+            }
+            _ => {}
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let panel = match &self.pending {
+            Some(Pending { editing: Some(_), .. }) => 8,
+            Some(_) => 5,
+            None => 0,
+        };
+        let [header, gauge, list, review, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Min(3),
+            Constraint::Length(panel),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let status = if self.stop.load(Ordering::Relaxed) { "  stopping after the current file" } else { "" };
+        frame.render_widget(
+            Paragraph::new(Line::from(vec![
+                Span::styled("sortbook --review  ", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(format!(
+                    "{} approved, {} corrected, {} deferred  ",
+                    self.approved, self.corrected, self.deferred
+                )),
+                Span::styled(status, Style::default().fg(Color::Yellow)),
+            ])),
+            header,
+        );
+        let ratio = if self.total == 0 { 1.0 } else { (self.done as f64 / self.total as f64).min(1.0) };
+        frame.render_widget(
+            Gauge::default()
+                .gauge_style(Style::default().fg(Color::Cyan))
+                .ratio(ratio)
+                .label(format!("{}/{}  {}", self.done, self.total, self.current)),
+            gauge,
+        );
+
+        let height = list.height.saturating_sub(2) as usize;
+        let items: Vec<ListItem> = self.decisions[self.decisions.len().saturating_sub(height)..]
+            .iter()
+            .map(|decision| {
+                let color = if decision.contains(" OK") {
+                    Color::Green
+                } else if decision.contains("deferred") || decision.contains("already processed") {
+                    Color::Yellow
+                } else {
+                    Color::Red
+                };
+                ListItem::new(decision.as_str()).style(Style::default().fg(color))
+            })
+            .collect();
+        frame.render_widget(List::new(items).block(Block::default().borders(Borders::ALL).title("Decisions")), list);
+
+        let keys = match &self.pending {
+            Some(pending) => {
+                let p = &pending.proposal;
+                let mut lines = vec![
+                    Line::from(format!("File: {}", p.file)),
+                    Line::from(format!("Match ({}): {} {} — {}", p.mode, p.first, p.last, p.title)),
+                    Line::from(format!("Copy to: {}", p.dest.display())),
+                ];
+                if let Some((fields, focus)) = &pending.editing {
+                    for (i, (label, value)) in ["Title", "First name", "Last name"].iter().zip(fields).enumerate() {
+                        let style = if i == *focus { Style::default().add_modifier(Modifier::REVERSED) } else { Style::default() };
+                        lines.push(Line::from(vec![Span::raw(format!("{label:>10}: ")), Span::styled(format!("{value} "), style)]));
+                    }
+                }
+                frame.render_widget(
+                    Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Low-confidence match")),
+                    review,
+                );
+                if pending.editing.is_some() {
+                    "Tab/↑↓ field  Enter file under this title/author  Esc back"
+                } else {
+                    "a/Enter approve  c correct  d defer to a later run  q defer and stop"
+                }
+            }
+            None => "q stop after the current file",
+        };
+        frame.render_widget(Paragraph::new(keys).style(Style::default().fg(Color::DarkGray)), footer);
+    }
+}
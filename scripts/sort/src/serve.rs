@@ -0,0 +1,286 @@
+use crate::catalog::{self, Entry, FeedLink, Sources, ACQUISITION_TYPE, NAVIGATION_TYPE};
+use crate::dashboard::{blocking, internal, ApiError};
+use anyhow::{anyhow, bail, Result};
+use axum::body::Body;
+use axum::extract::{Path as UrlPath, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use clap::Args;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_util::io::ReaderStream;
+
+/// Entries per page of the author list and of the book feeds.
+const PAGE_SIZE: usize = 100;
+/// Books in the "recently added" feed.
+const RECENT: usize = 50;
+
+/// Options of `sortbook serve`; the library, ledger and database come from the global options.
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Serve the library as an OPDS catalog (browse by author, search by title, download)
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub opds: bool,
+
+    /// Address to listen on; use 0.0.0.0:8080 to reach it from other devices of the network
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    pub listen: String,
+
+    /// Library folder (default: <root>/output/sorted_books)
+    #[arg(long)]
+    pub library: Option<PathBuf>,
+
+    /// Extensions to serve, comma separated (default: every file but hidden ones, covers and metadata)
+    #[arg(long, default_value = "")]
+    pub exts: String,
+
+    /// Seconds before the library is scanned again for new, renamed or removed books
+    #[arg(long, default_value_t = 300)]
+    pub refresh: u64,
+
+    /// Do not open EPUB files to read the ISBN of books the ledger has none for
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub no_epub: bool,
+}
+
+/// Library scanned on demand, at most once per `refresh`.
+struct Library {
+    dir: PathBuf,
+    sources: Sources,
+    exts: String,
+    epub: bool,
+    refresh: Duration,
+    cache: Mutex<Option<(Instant, Arc<Vec<Entry>>)>>,
+}
+
+impl Library {
+    fn entries(&self) -> Result<Arc<Vec<Entry>>> {
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((at, entries)) = cache.as_ref() {
+            if at.elapsed() < self.refresh {
+                return Ok(entries.clone());
+            }
+        }
+        let entries = Arc::new(catalog::scan(&self.dir, &self.sources, &self.exts, self.epub)?);
+        *cache = Some((Instant::now(), entries.clone()));
+        Ok(entries)
+    }
+}
+
+/// Serves until the process is stopped.
+pub fn run(args: &ServeArgs, root: &Path, db: Option<&Path>, ledger: Option<&Path>) -> Result<()> {
+    if !args.opds {
+        bail!("Nothing to serve: pass --opds");
+    }
+    let library = Arc::new(Library {
+        dir: args.library.clone().unwrap_or_else(|| root.join(crate::SORTED_DIR)),
+        sources: Sources::resolve(root, db, ledger)?,
+        exts: args.exts.clone(),
+        epub: !args.no_epub,
+        refresh: Duration::from_secs(args.refresh),
+        cache: Mutex::new(None),
+    });
+    let count = library.entries()?.len();
+    let app = Router::new()
+        .route("/", get(start))
+        .route("/opds", get(start))
+        .route("/opds/opensearch.xml", get(|| async { xml("application/opensearchdescription+xml", opensearch()) }))
+        .route("/opds/authors", get(authors))
+        .route("/opds/authors/{author}", get(author))
+        .route("/opds/recent", get(recent))
+        .route("/opds/search", get(search))
+        .route("/books/{*path}", get(book))
+        .fallback(|| async { (StatusCode::NOT_FOUND, "Not found") })
+        .with_state(library.clone());
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let listener = tokio::net::TcpListener::bind(&args.listen).await.map_err(|e| anyhow!("Listening on {}: {e}", args.listen))?;
+        println!("OPDS catalog of {} ({count} book(s)) on http://{}/opds", library.dir.display(), args.listen);
+        axum::serve(listener, app).await?;
+        Ok(())
+    })
+}
+
+type Params = Query<HashMap<String, String>>;
+
+/// The response `build` makes from the library, scanned again if `refresh` has passed (off the async workers).
+async fn feed(library: Arc<Library>, build: impl FnOnce(&[Entry]) -> Response + Send + 'static) -> Result<Response, ApiError> {
+    blocking(move || library.entries().map(|entries| build(&entries)).map_err(internal)).await
+}
+
+fn page(params: &HashMap<String, String>) -> usize {
+    params.get("page").and_then(|p| p.parse::<usize>().ok()).unwrap_or(0)
+}
+
+async fn start(State(library): State<Arc<Library>>) -> Result<Response, ApiError> {
+    feed(library, |entries| xml(NAVIGATION_TYPE, root_feed(entries))).await
+}
+
+async fn authors(State(library): State<Arc<Library>>, Query(params): Params) -> Result<Response, ApiError> {
+    feed(library, move |entries| xml(NAVIGATION_TYPE, authors_feed(entries, page(&params)))).await
+}
+
+async fn author(State(library): State<Arc<Library>>, UrlPath(author): UrlPath<String>, Query(params): Params) -> Result<Response, ApiError> {
+    feed(library, move |entries| {
+        let books: Vec<Entry> = entries.iter().filter(|e| e.author == author).cloned().collect();
+        if books.is_empty() {
+            return (StatusCode::NOT_FOUND, "Unknown author").into_response();
+        }
+        let href = format!("/opds/authors/{}", urlencoding::encode(&author));
+        let title = catalog::display_author(&author);
+        let id = format!("urn:sortbook:author:{}", urlencoding::encode(&author));
+        xml(ACQUISITION_TYPE, books_feed(&id, &title, &href, &books, page(&params)))
+    })
+    .await
+}
+
+async fn recent(State(library): State<Arc<Library>>) -> Result<Response, ApiError> {
+    feed(library, |entries| {
+        let mut books: Vec<Entry> = entries.to_vec();
+        books.sort_by(|a, b| b.sorted_at.as_ref().unwrap_or(&b.modified).cmp(a.sorted_at.as_ref().unwrap_or(&a.modified)));
+        books.truncate(RECENT);
+        xml(ACQUISITION_TYPE, books_feed("urn:sortbook:recent", "Recently added", "/opds/recent", &books, 0))
+    })
+    .await
+}
+
+async fn search(State(library): State<Arc<Library>>, Query(params): Params) -> Result<Response, ApiError> {
+    feed(library, move |entries| {
+        let terms = params.get("q").cloned().unwrap_or_default();
+        let words: Vec<String> = crate::normalize_text(&terms).split_whitespace().map(String::from).collect();
+        let books: Vec<Entry> = entries
+            .iter()
+            .filter(|e| {
+                let title = crate::normalize_text(&e.title);
+                !words.is_empty() && words.iter().all(|w| title.split_whitespace().any(|t| t.starts_with(w.as_str())))
+            })
+            .cloned()
+            .collect();
+        let href = format!("/opds/search?q={}", urlencoding::encode(&terms));
+        xml(ACQUISITION_TYPE, books_feed("urn:sortbook:search", &format!("Search: {terms}"), &href, &books, page(&params)))
+    })
+    .await
+}
+
+/// Download of a book; only files of the catalog are served, so no path can leave the library.
+async fn book(State(library): State<Arc<Library>>, UrlPath(relative): UrlPath<String>) -> Result<Response, ApiError> {
+    let lookup = library.clone();
+    let relative = relative.trim_matches('/').to_string();
+    let found = blocking(move || {
+        let entries = lookup.entries().map_err(internal)?;
+        Ok(entries.iter().find(|e| e.path == relative).map(|entry| (entry.path.clone(), entry.format.clone())))
+    })
+    .await?;
+    let Some((path, format)) = found else {
+        return Ok((StatusCode::NOT_FOUND, "Unknown book").into_response());
+    };
+    let file = tokio::fs::File::open(library.dir.join(&path)).await.map_err(|e| internal(e.into()))?;
+    let name = path.rsplit('/').next().unwrap_or(&path);
+    Ok((
+        [
+            (header::CONTENT_TYPE, catalog::mime_type(&format).to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename*=UTF-8''{}", urlencoding::encode(name))),
+        ],
+        Body::from_stream(ReaderStream::new(file)),
+    )
+        .into_response())
+}
+
+/// Start page: authors, recently added and search.
+fn root_feed(entries: &[Entry]) -> String {
+    let authors = entries.iter().map(|e| e.author.as_str()).collect::<std::collections::BTreeSet<_>>().len();
+    let mut xml = catalog::feed_header("urn:sortbook:root", "sortbook library", "/", &start_links("/opds", NAVIGATION_TYPE));
+    nav_entry(&mut xml, "urn:sortbook:authors", "By author", &format!("{authors} author(s)"), "/opds/authors", NAVIGATION_TYPE);
+    nav_entry(&mut xml, "urn:sortbook:recent", "Recently added", &format!("{} latest book(s)", RECENT.min(entries.len())), "/opds/recent", ACQUISITION_TYPE);
+    xml.push_str("</feed>\n");
+    xml
+}
+
+/// Author folders in name order, with their number of books.
+fn authors_feed(entries: &[Entry], page: usize) -> String {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for entry in entries {
+        *counts.entry(entry.author.as_str()).or_default() += 1;
+    }
+    let authors: Vec<(&str, usize)> = counts.into_iter().collect();
+    let mut links = start_links("/opds/authors", NAVIGATION_TYPE);
+    links.extend(page_links("/opds/authors", page, authors.len(), NAVIGATION_TYPE));
+    let mut xml = catalog::feed_header("urn:sortbook:authors", "By author", "/", &links);
+    for (author, count) in authors.iter().skip(page * PAGE_SIZE).take(PAGE_SIZE) {
+        let encoded = urlencoding::encode(author);
+        nav_entry(
+            &mut xml,
+            &format!("urn:sortbook:author:{encoded}"),
+            &catalog::display_author(author),
+            &format!("{count} book(s)"),
+            &format!("/opds/authors/{encoded}"),
+            ACQUISITION_TYPE,
+        );
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+/// One page of an acquisition feed; book links resolve under `/books/`.
+fn books_feed(id: &str, title: &str, href: &str, books: &[Entry], page: usize) -> String {
+    let mut links = start_links(href, ACQUISITION_TYPE);
+    links.push(("up", "/opds".to_string(), NAVIGATION_TYPE));
+    links.extend(page_links(href, page, books.len(), ACQUISITION_TYPE));
+    let shown: Vec<Entry> = books.iter().skip(page * PAGE_SIZE).take(PAGE_SIZE).cloned().collect();
+    catalog::opds_feed(id, title, "/books/", &links, &shown)
+}
+
+fn start_links<'a>(href: &str, kind: &'a str) -> Vec<FeedLink<'a>> {
+    vec![
+        ("self", href.to_string(), kind),
+        ("start", "/opds".to_string(), NAVIGATION_TYPE),
+        ("search", "/opds/opensearch.xml".to_string(), "application/opensearchdescription+xml"),
+    ]
+}
+
+/// `previous`/`next` links of page `page` of a list of `total` items.
+fn page_links<'a>(href: &str, page: usize, total: usize, kind: &'a str) -> Vec<FeedLink<'a>> {
+    let join = if href.contains('?') { '&' } else { '?' };
+    let mut links = Vec::new();
+    if page > 0 {
+        links.push(("previous", format!("{href}{join}page={}", page - 1), kind));
+    }
+    if (page + 1) * PAGE_SIZE < total {
+        links.push(("next", format!("{href}{join}page={}", page + 1), kind));
+    }
+    links
+}
+
+fn nav_entry(xml: &mut String, id: &str, title: &str, content: &str, href: &str, kind: &str) {
+    xml.push_str("  <entry>\n");
+    xml.push_str(&format!("    <title>{}</title>\n", catalog::escape(title)));
+    xml.push_str(&format!("    <id>{}</id>\n", catalog::escape(id)));
+    xml.push_str(&format!("    <updated>{}</updated>\n", chrono::Utc::now().to_rfc3339()));
+    xml.push_str(&format!("    <content type=\"text\">{}</content>\n", catalog::escape(content)));
+    xml.push_str(&format!("    <link rel=\"subsection\" href=\"{}\" type=\"{}\"/>\n", catalog::escape(href), kind));
+    xml.push_str("  </entry>\n");
+}
+
+fn opensearch() -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <OpenSearchDescription xmlns=\"http://a9.com/-/spec/opensearch/1.1/\">\n  \
+         <ShortName>sortbook</ShortName>\n  <Description>Search the library by title</Description>\n  \
+         <Url type=\"{ACQUISITION_TYPE}\" template=\"/opds/search?q={{searchTerms}}\"/>\n\
+         </OpenSearchDescription>\n"
+    )
+}
+
+/// An XML feed; Atom and other `+xml` types get an explicit UTF-8 charset.
+fn xml(content_type: &str, body: String) -> Response {
+    let content_type = if content_type.starts_with("application/atom+xml") || content_type.ends_with("+xml") {
+        format!("{content_type};charset=utf-8")
+    } else {
+        content_type.to_string()
+    };
+    ([(header::CONTENT_TYPE, content_type)], body).into_response()
+}
@@ -0,0 +1,250 @@
+//! `sortbook.toml`: shared defaults for every tool.
+//!
+//! Values are turned into command-line options and inserted after the program name, only for the
+//! options the command line (or their environment variable) does not already set, so flags always win.
+//! Without `--config` and without `./sortbook.toml`, the command line is left untouched.
+use clap::{value_parser, Arg, ArgAction, Command, Parser};
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// Config file looked up in the current directory when `--config` is not given.
+pub const CONFIG_NAME: &str = "sortbook.toml";
+
+/// Library folder of cleanup, aliases and filenames, relative to `[paths] root`.
+const LIBRARY_DIR: &str = "output/sorted_books";
+
+/// Sections understood in the file; each tool only reads `[paths]`, `[model]` and its own.
+const SECTIONS: [&str; 6] = ["paths", "model", "sort", "cleanup", "aliases", "filenames"];
+
+/// Unreadable file, invalid TOML, or a key that is not an option of the tool.
+#[derive(Debug)]
+pub struct ConfigError(String);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Parses the command line of `tool` once completed by the config file. Adds `--config <path>` to the tool's options.
+pub fn parse<P: Parser>(tool: &str, args: Vec<OsString>) -> Result<P, ConfigError> {
+    let mut command = P::command().arg(
+        Arg::new("config")
+            .long("config")
+            .value_name("PATH")
+            .value_parser(value_parser!(PathBuf))
+            .help(format!("Shared settings file (default: ./{CONFIG_NAME} when present); options on the command line win")),
+    );
+    let args = apply(&command, tool, args)?;
+    let matches = command.clone().get_matches_from(args);
+    Ok(P::from_arg_matches(&matches)
+        .map_err(|e| e.format(&mut command))
+        .unwrap_or_else(|e| e.exit()))
+}
+
+/// Command line of `tool` ("sort", "cleanup", "aliases" or "filenames") completed with the values of the
+/// config file. `command` is the tool's clap command, used to name and shape the options.
+fn apply(command: &Command, tool: &str, args: Vec<OsString>) -> Result<Vec<OsString>, ConfigError> {
+    let path = match explicit_path(&args) {
+        Some(path) => path,
+        None if Path::new(CONFIG_NAME).is_file() => PathBuf::from(CONFIG_NAME),
+        None => return Ok(args),
+    };
+    let text = fs::read_to_string(&path)
+        .map_err(|e| ConfigError(format!("Reading {}: {e}", path.display())))?;
+    let table: Table = text
+        .parse()
+        .map_err(|e| ConfigError(format!("Parsing {}: {e}", path.display())))?;
+    let values = values_for(&table, tool, &path)?;
+
+    let mut extra = Vec::new();
+    for (section, key, value) in values {
+        let long = key.replace('_', "-");
+        let Some(arg) = command
+            .get_arguments()
+            .find(|a| a.get_long() == Some(long.as_str()))
+        else {
+            return Err(ConfigError(format!(
+                "{}: [{section}] {key} is not an option of {tool}",
+                path.display()
+            )));
+        };
+        if given(&args, &long, arg.get_short())
+            || arg
+                .get_env()
+                .is_some_and(|env| std::env::var_os(env).is_some())
+        {
+            continue;
+        }
+        let flag = OsString::from(format!("--{long}"));
+        let invalid = |expected: &str| {
+            ConfigError(format!(
+                "{}: [{section}] {key} expects {expected}",
+                path.display()
+            ))
+        };
+        match arg.get_action() {
+            ArgAction::SetTrue | ArgAction::SetFalse => {
+                let Value::Boolean(on) = value else {
+                    return Err(invalid("true or false"));
+                };
+                if on == matches!(arg.get_action(), ArgAction::SetTrue) {
+                    extra.push(flag);
+                }
+            }
+            ArgAction::Count => {
+                let Value::Integer(times) = value else {
+                    return Err(invalid("a number"));
+                };
+                extra.extend((0..times.max(0)).map(|_| flag.clone()));
+            }
+            ArgAction::Append => {
+                let items = match value {
+                    Value::Array(items) => items,
+                    scalar => vec![scalar],
+                };
+                for item in items {
+                    extra.push(flag.clone());
+                    extra.push(
+                        scalar(&item)
+                            .ok_or_else(|| invalid("a value or a list of values"))?
+                            .into(),
+                    );
+                }
+            }
+            _ => {
+                let text = match &value {
+                    Value::Array(items) => items
+                        .iter()
+                        .map(scalar)
+                        .collect::<Option<Vec<_>>>()
+                        .map(|v| v.join(",")),
+                    other => scalar(other),
+                };
+                extra.push(flag);
+                extra.push(text.ok_or_else(|| invalid("a value"))?.into());
+            }
+        }
+    }
+
+    let mut out = Vec::with_capacity(args.len() + extra.len());
+    let mut args = args.into_iter();
+    out.extend(args.next());
+    out.extend(extra);
+    out.extend(args);
+    Ok(out)
+}
+
+/// True when `args` already sets the option `--long` (or `-short`), as `--long value`, `--long=value` or `-s`.
+pub fn given(args: &[OsString], long: &str, short: Option<char>) -> bool {
+    let flag = format!("--{long}");
+    let short = short.map(|c| format!("-{c}"));
+    args.iter().skip(1).any(|arg| {
+        let text = arg.to_string_lossy();
+        text == flag
+            || text.starts_with(&format!("{flag}="))
+            || short
+                .as_deref()
+                .is_some_and(|s| text.starts_with(s) && !text.starts_with("--"))
+    })
+}
+
+fn explicit_path(args: &[OsString]) -> Option<PathBuf> {
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        if arg.as_os_str() == OsStr::new("--config") {
+            return iter.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.to_str().and_then(|a| a.strip_prefix("--config=")) {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+/// (section, key, value) for `tool`: `[paths]` and `[model]` mapped to the tool's options, then its own
+/// section, which wins on the same key.
+fn values_for(
+    table: &Table,
+    tool: &str,
+    path: &Path,
+) -> Result<Vec<(String, String, Value)>, ConfigError> {
+    if let Some(unknown) = table.keys().find(|k| !SECTIONS.contains(&k.as_str())) {
+        return Err(ConfigError(format!(
+            "{}: unknown section [{unknown}]",
+            path.display()
+        )));
+    }
+    let section = |name: &str| -> Result<Table, ConfigError> {
+        match table.get(name) {
+            None => Ok(Table::new()),
+            Some(Value::Table(t)) => Ok(t.clone()),
+            Some(_) => Err(ConfigError(format!(
+                "{}: {name} must be a [section]",
+                path.display()
+            ))),
+        }
+    };
+    let paths = section("paths")?;
+    let model = section("model")?;
+    for (name, keys, known) in [
+        ("paths", &paths, &["root", "library", "db", "ledger"][..]),
+        ("model", &model, &["name"][..]),
+    ] {
+        if let Some(unknown) = keys.keys().find(|k| !known.contains(&k.as_str())) {
+            return Err(ConfigError(format!(
+                "{}: unknown key [{name}] {unknown}",
+                path.display()
+            )));
+        }
+    }
+
+    let mut values: Vec<(String, String, Value)> = Vec::new();
+    let shared = |key: &str, value: Value| ("paths".to_string(), key.to_string(), value);
+    if tool == "sort" {
+        values.extend(paths.get("root").cloned().map(|v| shared("root", v)));
+        values.extend(
+            model
+                .get("name")
+                .cloned()
+                .map(|v| ("model".to_string(), "model".to_string(), v)),
+        );
+    } else {
+        let library = match (paths.get("library"), paths.get("root")) {
+            (Some(library), _) => Some(library.clone()),
+            (None, Some(Value::String(root))) => Some(Value::String(
+                Path::new(root)
+                    .join(LIBRARY_DIR)
+                    .to_string_lossy()
+                    .into_owned(),
+            )),
+            _ => None,
+        };
+        values.extend(library.map(|v| shared("root", v)));
+    }
+    values.extend(paths.get("db").cloned().map(|v| shared("db", v)));
+    values.extend(paths.get("ledger").cloned().map(|v| shared("ledger", v)));
+
+    for (key, value) in section(tool)? {
+        let long = key.replace('_', "-");
+        values.retain(|(_, k, _)| k.replace('_', "-") != long);
+        values.push((tool.to_string(), key, value));
+    }
+    Ok(values)
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Integer(i) => Some(i.to_string()),
+        Value::Float(f) => Some(f.to_string()),
+        Value::Boolean(b) => Some(b.to_string()),
+        Value::Datetime(d) => Some(d.to_string()),
+        Value::Array(_) | Value::Table(_) => None,
+    }
+}
@@ -0,0 +1,149 @@
+//! `author_identity`: what is known to be the same author — OpenLibrary author_id, Wikidata QID, VIAF, BnF, ISNI,
+//! names and aliases, local folders — as rows `(identity, scheme, value)` of the OpenLibrary database. Written by
+//! `author-alias-online --write-identity`, read by the sorter (`--library-authors`) and `cleanup --identity`, so an
+//! identity decision is made once.
+use rusqlite::{params, Connection, OptionalExtension};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+pub const IDENTITY_TABLE: &str = "author_identity";
+
+pub const IDENTITY_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS author_identity (
+     identity TEXT NOT NULL,
+     scheme TEXT NOT NULL,
+     value TEXT NOT NULL,
+     value_normalized TEXT NOT NULL,
+     source TEXT NOT NULL,
+     updated_at TEXT NOT NULL,
+     PRIMARY KEY (identity, scheme, value)
+ );
+ CREATE INDEX IF NOT EXISTS idx_author_identity_value ON author_identity(scheme, value_normalized);";
+
+/// Schemes of ids; the identity of an author is `<scheme>:<id>` of the first id it was recorded with.
+pub const ID_SCHEMES: [&str; 5] = ["wikidata", "openlibrary", "viaf", "bnf", "isni"];
+/// A local author folder (name under the library root).
+pub const FOLDER: &str = "folder";
+/// A name of the author: label, alias, pen name, former folder name.
+pub const ALIAS: &str = "alias";
+
+/// Everything one tool learned about an author in one decision.
+#[derive(Debug, Default, Clone)]
+pub struct IdentityRecord {
+    /// `(scheme, id)` pairs, most authoritative first (one of `ID_SCHEMES`).
+    pub ids: Vec<(&'static str, String)>,
+    pub folders: Vec<String>,
+    pub names: Vec<String>,
+}
+
+/// Lowercase, accents removed, punctuation as spaces (the cleanup tool's name normalization).
+pub fn normalize_key(value: &str) -> String {
+    value
+        .nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect::<String>()
+        .to_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                ' '
+            }
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn table_exists(conn: &Connection) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        [IDENTITY_TABLE],
+        |row| row.get(0),
+    )
+}
+
+/// Identity a value of this scheme belongs to (compared normalized), if recorded.
+pub fn identity_of(
+    conn: &Connection,
+    scheme: &str,
+    value: &str,
+) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT identity FROM author_identity WHERE scheme = ?1 AND value_normalized = ?2 ORDER BY updated_at DESC LIMIT 1",
+        params![scheme, normalize_key(value)],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Identity of an author name or folder: a recorded folder or alias, as written or, for `Last, First`, as `First Last`.
+pub fn identity_of_name(conn: &Connection, name: &str) -> rusqlite::Result<Option<String>> {
+    let mut forms = vec![name.to_string()];
+    if let Some((last, first)) = name.split_once(',') {
+        forms.push(format!("{} {}", first.trim(), last.trim()));
+    }
+    for form in &forms {
+        for scheme in [FOLDER, ALIAS] {
+            if let Some(identity) = identity_of(conn, scheme, form)? {
+                return Ok(Some(identity));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Values of one scheme of an identity, most recently recorded first.
+pub fn values(conn: &Connection, identity: &str, scheme: &str) -> rusqlite::Result<Vec<String>> {
+    let mut stmt =
+        conn.prepare("SELECT value FROM author_identity WHERE identity = ?1 AND scheme = ?2 ORDER BY updated_at DESC, value")?;
+    let rows = stmt.query_map(params![identity, scheme], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// Stores a record: it joins the identity one of its ids or folders already belongs to, else starts
+/// `<scheme>:<id>` of its first id. Returns the identity, or `None` for a record without ids.
+pub fn record(
+    conn: &mut Connection,
+    record: &IdentityRecord,
+    source: &str,
+) -> rusqlite::Result<Option<String>> {
+    conn.execute_batch(IDENTITY_SCHEMA)?;
+    let Some((scheme, id)) = record.ids.first() else {
+        return Ok(None);
+    };
+    let mut identity = None;
+    for (scheme, value) in &record.ids {
+        identity = identity_of(conn, scheme, value)?;
+        if identity.is_some() {
+            break;
+        }
+    }
+    for folder in &record.folders {
+        if identity.is_some() {
+            break;
+        }
+        identity = identity_of(conn, FOLDER, folder)?;
+    }
+    let identity = identity.unwrap_or_else(|| format!("{scheme}:{id}"));
+    let now = chrono::Utc::now().to_rfc3339();
+    let tx = conn.transaction()?;
+    let rows = record
+        .ids
+        .iter()
+        .map(|(scheme, value)| (*scheme, value))
+        .chain(record.folders.iter().map(|folder| (FOLDER, folder)))
+        .chain(record.names.iter().map(|name| (ALIAS, name)));
+    for (scheme, value) in rows {
+        if value.trim().is_empty() {
+            continue;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO author_identity (identity, scheme, value, value_normalized, source, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![identity, scheme, value, normalize_key(value), source, now],
+        )?;
+    }
+    tx.commit()?;
+    Ok(Some(identity))
+}
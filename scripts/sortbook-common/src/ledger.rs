@@ -0,0 +1,563 @@
+//! `data/sortbook_state.sqlite3`: one ledger of every file operation made by the tools.
+//!
+//! Each live run of a tool opened with `--ledger <path>` gets a row in `runs` (tool, command line,
+//! start/end, status); each copy, move, trash, deletion or folder removal it makes gets a row in
+//! `operations`. The ledger comes on top of the per-tool JSONL/CSV outputs, which are unchanged.
+//! Books the sorter copied get a row in `books`: work_id, author_id and ISBN of a match, read by `sortbook catalog`,
+//! or the LLM guess of a file left in a fail folder, read by `sortbook dashboard`.
+//! `sortbook ledger` lists runs, shows the history of a path and undoes a run.
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Ledger location suggested in the docs and in `sortbook.example.toml`.
+pub const DEFAULT_LEDGER: &str = "data/sortbook_state.sqlite3";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    run_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tool TEXT NOT NULL,
+    args TEXT NOT NULL,
+    started TEXT NOT NULL,
+    finished TEXT,
+    status TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS operations (
+    op_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    run_id INTEGER NOT NULL REFERENCES runs(run_id),
+    ts TEXT NOT NULL,
+    op TEXT NOT NULL,
+    src TEXT NOT NULL,
+    dst TEXT,
+    size INTEGER,
+    detail TEXT NOT NULL DEFAULT ''
+);
+CREATE INDEX IF NOT EXISTS idx_operations_run ON operations(run_id);
+CREATE INDEX IF NOT EXISTS idx_operations_src ON operations(src);
+CREATE INDEX IF NOT EXISTS idx_operations_dst ON operations(dst);
+CREATE TABLE IF NOT EXISTS books (
+    path TEXT PRIMARY KEY,
+    op_id INTEGER,
+    run_id INTEGER NOT NULL REFERENCES runs(run_id),
+    ts TEXT NOT NULL,
+    work_id TEXT,
+    author_id TEXT,
+    isbn TEXT,
+    title TEXT,
+    author TEXT
+);
+";
+
+/// OpenLibrary identifiers, title and author ("First Last") of a book, as known when it was sorted.
+#[derive(Debug, Clone, Default)]
+pub struct BookMeta {
+    pub work_id: Option<String>,
+    pub author_id: Option<String>,
+    pub isbn: Option<String>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+}
+
+/// Kind of file operation. `detail` in the ledger keeps the tool's own wording (`rename_dir`, `full-normal`...).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// `src` copied to `dst`, the source stays (the sorter).
+    Copy,
+    /// `src` renamed or moved to `dst` (quarantine included).
+    Move,
+    /// `src` sent to the system trash.
+    Trash,
+    /// `src` deleted for good.
+    Delete,
+    /// Empty folder `src` removed.
+    RemoveDir,
+}
+
+impl Op {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Op::Copy => "copy",
+            Op::Move => "move",
+            Op::Trash => "trash",
+            Op::Delete => "delete",
+            Op::RemoveDir => "remove_dir",
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        [Op::Copy, Op::Move, Op::Trash, Op::Delete, Op::RemoveDir]
+            .into_iter()
+            .find(|op| op.as_str() == text)
+    }
+}
+
+/// Open run of a tool; disabled (no-op) without `--ledger` and in dry-run.
+pub struct Ledger {
+    conn: Option<Mutex<Connection>>,
+    run_id: i64,
+    finished: bool,
+}
+
+impl Ledger {
+    pub fn disabled() -> Self {
+        Self {
+            conn: None,
+            run_id: 0,
+            finished: false,
+        }
+    }
+
+    /// Opens (creating it if needed) the ledger at `path` and starts a run of `tool`.
+    pub fn open(path: &Path, tool: &str, args: &[String]) -> rusqlite::Result<Self> {
+        let conn = connect(path)?;
+        conn.execute(
+            "INSERT INTO runs (tool, args, started, status) VALUES (?1, ?2, ?3, 'running')",
+            params![tool, args.join(" "), now()],
+        )?;
+        let run_id = conn.last_insert_rowid();
+        Ok(Self {
+            conn: Some(Mutex::new(conn)),
+            run_id,
+            finished: false,
+        })
+    }
+
+    /// `--ledger` of a tool: disabled when absent or in dry-run, otherwise a new run with the process command line.
+    pub fn for_run(path: Option<&Path>, tool: &str, dry_run: bool) -> rusqlite::Result<Self> {
+        match path {
+            Some(path) if !dry_run => Self::open(path, tool, &std::env::args().collect::<Vec<_>>()),
+            _ => Ok(Self::disabled()),
+        }
+    }
+
+    pub fn run_id(&self) -> Option<i64> {
+        self.conn.as_ref().map(|_| self.run_id)
+    }
+
+    /// Records one operation; paths are stored absolute, `size` is the file's size after the operation.
+    pub fn record(
+        &self,
+        op: Op,
+        src: &Path,
+        dst: Option<&Path>,
+        detail: &str,
+    ) -> rusqlite::Result<()> {
+        let Some(conn) = &self.conn else {
+            return Ok(());
+        };
+        let size = fs::metadata(dst.unwrap_or(src))
+            .ok()
+            .filter(|m| m.is_file())
+            .map(|m| m.len() as i64);
+        let conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+        conn.execute(
+            "INSERT INTO operations (run_id, ts, op, src, dst, size, detail) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![self.run_id, now(), op.as_str(), text(src), dst.map(text), size, detail],
+        )?;
+        Ok(())
+    }
+
+    /// Attaches `meta` to the file at `path`, just recorded as the destination of an operation of this run.
+    pub fn describe(&self, path: &Path, meta: &BookMeta) -> rusqlite::Result<()> {
+        let Some(conn) = &self.conn else {
+            return Ok(());
+        };
+        let conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+        let path = text(path);
+        let op_id: Option<i64> = conn.query_row(
+            "SELECT MAX(op_id) FROM operations WHERE run_id = ?1 AND dst = ?2",
+            params![self.run_id, path],
+            |row| row.get(0),
+        )?;
+        conn.execute(
+            "INSERT OR REPLACE INTO books (path, op_id, run_id, ts, work_id, author_id, isbn, title, author)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![path, op_id, self.run_id, now(), meta.work_id, meta.author_id, meta.isbn, meta.title, meta.author],
+        )?;
+        Ok(())
+    }
+
+    /// Sources already copied by any run with one of the `details` (the sorter's success modes), plus the
+    /// sources whose copy in a fail folder was later filed by the triage dashboard, for resume.
+    pub fn copied_sources(&self, details: &[&str]) -> rusqlite::Result<Vec<String>> {
+        let Some(conn) = &self.conn else {
+            return Ok(Vec::new());
+        };
+        let conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT c.src, c.detail,
+                    EXISTS(SELECT 1 FROM operations m WHERE m.op = 'move' AND m.detail = 'triage' AND m.src = c.dst AND m.op_id > c.op_id)
+             FROM operations c WHERE c.op = 'copy'",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, bool>(2)?,
+            ))
+        })?;
+        let mut sources = Vec::new();
+        for row in rows {
+            let (src, detail, triaged) = row?;
+            if triaged || details.contains(&detail.as_str()) {
+                sources.push(src);
+            }
+        }
+        Ok(sources)
+    }
+
+    /// Closes the run with `status` ("ok", "stopped"...). A run dropped without it (error return) is marked "failed";
+    /// a killed process leaves it "running".
+    pub fn finish(&mut self, status: &str) -> rusqlite::Result<()> {
+        self.finished = true;
+        let Some(conn) = &self.conn else {
+            return Ok(());
+        };
+        let conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+        conn.execute(
+            "UPDATE runs SET finished = ?1, status = ?2 WHERE run_id = ?3",
+            params![now(), status, self.run_id],
+        )?;
+        Ok(())
+    }
+}
+
+impl Drop for Ledger {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.finish("failed").ok();
+        }
+    }
+}
+
+/// One row of `runs`, with its number of operations.
+#[derive(Debug)]
+pub struct RunRow {
+    pub run_id: i64,
+    pub tool: String,
+    pub args: String,
+    pub started: String,
+    pub finished: Option<String>,
+    pub status: String,
+    pub operations: i64,
+}
+
+/// One row of `operations`.
+#[derive(Debug)]
+pub struct OpRow {
+    pub op_id: i64,
+    pub run_id: i64,
+    pub tool: String,
+    pub ts: String,
+    pub op: String,
+    pub src: String,
+    pub dst: Option<String>,
+    pub size: Option<i64>,
+    pub detail: String,
+}
+
+/// One row of `books`, with the path where the file is now.
+#[derive(Debug, Clone)]
+pub struct BookRow {
+    /// Current path: the recorded one, followed through the later moves and folder renames of the ledger.
+    pub path: String,
+    /// File the sorter copied it from, when known.
+    pub source: Option<String>,
+    pub ts: String,
+    pub meta: BookMeta,
+}
+
+/// Every described book, oldest description first (a later one of the same file wins when collected into a
+/// map). Moves recorded after a book (renames by cleanup-filenames, folder renames and
+/// merges by cleanup or aliases) are applied to its path, exact or as a parent folder, in ledger order.
+pub fn books(path: &Path) -> rusqlite::Result<Vec<BookRow>> {
+    let conn = connect(path)?;
+    let mut stmt = conn.prepare(
+        "SELECT b.path, COALESCE(b.op_id, 0), b.ts, b.work_id, b.author_id, b.isbn, b.title,
+                (SELECT o.src FROM operations o WHERE o.op_id = b.op_id AND o.op = 'copy'), b.author
+         FROM books b ORDER BY COALESCE(b.op_id, 0), b.path",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let op_id: i64 = row.get(1)?;
+        let book = BookRow {
+            path: row.get(0)?,
+            source: row.get(7)?,
+            ts: row.get(2)?,
+            meta: BookMeta {
+                work_id: row.get(3)?,
+                author_id: row.get(4)?,
+                isbn: row.get(5)?,
+                title: row.get(6)?,
+                author: row.get(8)?,
+            },
+        };
+        Ok((op_id, book))
+    })?;
+    let rows: Vec<(i64, BookRow)> = rows.collect::<rusqlite::Result<_>>()?;
+    let Some(first) = rows.iter().map(|(op_id, _)| *op_id).min() else {
+        return Ok(Vec::new());
+    };
+
+    // Current path -> books there (indexes into `rows`); a BTreeMap so a folder move finds everything under it.
+    let mut at: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (index, (_, book)) in rows.iter().enumerate() {
+        at.entry(book.path.clone()).or_default().push(index);
+    }
+    let mut paths: Vec<String> = rows.iter().map(|(_, book)| book.path.clone()).collect();
+    let mut moves = conn.prepare("SELECT op_id, src, dst FROM operations WHERE op = 'move' AND op_id > ?1 AND dst IS NOT NULL ORDER BY op_id")?;
+    let moves = moves.query_map([first], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+        ))
+    })?;
+    let separator = std::path::MAIN_SEPARATOR.to_string();
+    for step in moves {
+        let (op_id, src, dst) = step?;
+        let prefix = format!("{src}{separator}");
+        let mut hits: Vec<String> = at
+            .range(prefix.clone()..)
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .map(|(key, _)| key.clone())
+            .collect();
+        if at.contains_key(&src) {
+            hits.push(src.clone());
+        }
+        for key in hits {
+            let Some(indexes) = at.remove(&key) else {
+                continue;
+            };
+            // Only books recorded before the move follow it.
+            let (follow, stay): (Vec<usize>, Vec<usize>) =
+                indexes.into_iter().partition(|&i| rows[i].0 < op_id);
+            if !stay.is_empty() {
+                at.insert(key.clone(), stay);
+            }
+            let moved = format!("{dst}{}", &key[src.len()..]);
+            for &i in &follow {
+                paths[i] = moved.clone();
+            }
+            if !follow.is_empty() {
+                at.entry(moved).or_default().extend(follow);
+            }
+        }
+    }
+    Ok(rows
+        .into_iter()
+        .zip(paths)
+        .map(|((_, book), path)| BookRow { path, ..book })
+        .collect())
+}
+
+/// Latest runs first; `limit` 0 = all.
+pub fn runs(path: &Path, limit: usize) -> rusqlite::Result<Vec<RunRow>> {
+    let conn = connect(path)?;
+    let mut stmt = conn.prepare(
+        "SELECT r.run_id, r.tool, r.args, r.started, r.finished, r.status,
+                (SELECT COUNT(*) FROM operations o WHERE o.run_id = r.run_id)
+         FROM runs r ORDER BY r.run_id DESC LIMIT ?1",
+    )?;
+    let limit = if limit == 0 { -1 } else { limit as i64 };
+    let rows = stmt.query_map([limit], |row| {
+        Ok(RunRow {
+            run_id: row.get(0)?,
+            tool: row.get(1)?,
+            args: row.get(2)?,
+            started: row.get(3)?,
+            finished: row.get(4)?,
+            status: row.get(5)?,
+            operations: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Operations of one run, in order.
+pub fn operations(path: &Path, run_id: i64) -> rusqlite::Result<Vec<OpRow>> {
+    select_ops(&connect(path)?, "o.run_id = ?1", params![run_id])
+}
+
+/// Every operation whose source or destination is `file` (or lies under it, for a folder), oldest first.
+pub fn history(path: &Path, file: &Path) -> rusqlite::Result<Vec<OpRow>> {
+    let target = text(file);
+    // GLOB (not LIKE) for the "under this folder" test; brackets and wildcards in the path are escaped.
+    let escaped: String = target
+        .chars()
+        .map(|c| {
+            if matches!(c, '*' | '?' | '[') {
+                format!("[{c}]")
+            } else {
+                c.to_string()
+            }
+        })
+        .collect();
+    let under = format!("{}{}*", escaped, std::path::MAIN_SEPARATOR);
+    select_ops(
+        &connect(path)?,
+        "o.src = ?1 OR o.dst = ?1 OR o.src GLOB ?2 OR o.dst GLOB ?2",
+        params![target, under],
+    )
+}
+
+fn select_ops(
+    conn: &Connection,
+    filter: &str,
+    args: impl rusqlite::Params,
+) -> rusqlite::Result<Vec<OpRow>> {
+    let sql = format!(
+        "SELECT o.op_id, o.run_id, r.tool, o.ts, o.op, o.src, o.dst, o.size, o.detail
+         FROM operations o JOIN runs r ON r.run_id = o.run_id WHERE {filter} ORDER BY o.op_id"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(args, |row| {
+        Ok(OpRow {
+            op_id: row.get(0)?,
+            run_id: row.get(1)?,
+            tool: row.get(2)?,
+            ts: row.get(3)?,
+            op: row.get(4)?,
+            src: row.get(5)?,
+            dst: row.get(6)?,
+            size: row.get(7)?,
+            detail: row.get(8)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Undoes `run_id` backwards: moves go back to their source, removed folders are recreated, and copies
+/// are deleted when their source is still there with the same size. Trashed and deleted files are only
+/// reported. A live undo is itself a run of tool `ledger-undo`, and the undone run gets status `undone`.
+/// Returns (restored, skipped).
+pub fn undo(
+    path: &Path,
+    run_id: i64,
+    dry_run: bool,
+) -> Result<(usize, usize), Box<dyn std::error::Error + Send + Sync>> {
+    let conn = connect(path)?;
+    let tool: Option<String> = conn
+        .query_row("SELECT tool FROM runs WHERE run_id = ?1", [run_id], |row| {
+            row.get(0)
+        })
+        .optional()?;
+    let Some(tool) = tool else {
+        return Err(format!("No run {run_id} in {}", path.display()).into());
+    };
+    let ops = operations(path, run_id)?;
+    println!(
+        "Undoing run {run_id} ({tool}, {} operation(s)){}",
+        ops.len(),
+        if dry_run { " (dry-run)" } else { "" }
+    );
+
+    let mut ledger = if dry_run {
+        Ledger::disabled()
+    } else {
+        Ledger::open(
+            path,
+            "ledger-undo",
+            &["undo".to_string(), run_id.to_string()],
+        )?
+    };
+    let (mut restored, mut skipped) = (0usize, 0usize);
+    for row in ops.iter().rev() {
+        let src = PathBuf::from(&row.src);
+        let dst = row.dst.as_ref().map(PathBuf::from);
+        match (Op::parse(&row.op), dst) {
+            (Some(Op::Move), Some(dst)) => {
+                if !dst.exists() || src.exists() {
+                    println!(
+                        "  skip {} -> {} (target missing or source occupied)",
+                        dst.display(),
+                        src.display()
+                    );
+                    skipped += 1;
+                    continue;
+                }
+                if dry_run {
+                    println!("[DRY-RUN] restore {} -> {}", dst.display(), src.display());
+                } else {
+                    if let Some(parent) = src.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    if fs::rename(&dst, &src).is_err() {
+                        fs::copy(&dst, &src)?;
+                        fs::remove_file(&dst)?;
+                    }
+                    ledger.record(Op::Move, &dst, Some(&src), &format!("undo {}", row.op_id))?;
+                }
+                restored += 1;
+            }
+            (Some(Op::Copy), Some(dst)) => {
+                let same = match (fs::metadata(&src), fs::metadata(&dst)) {
+                    (Ok(a), Ok(b)) => a.is_file() && b.is_file() && a.len() == b.len(),
+                    _ => false,
+                };
+                if !same {
+                    println!(
+                        "  skip copy {} (source gone, or copy missing or changed)",
+                        dst.display()
+                    );
+                    skipped += 1;
+                    continue;
+                }
+                if dry_run {
+                    println!("[DRY-RUN] remove copy {}", dst.display());
+                } else {
+                    fs::remove_file(&dst)?;
+                    ledger.record(Op::Delete, &dst, None, &format!("undo {}", row.op_id))?;
+                }
+                restored += 1;
+            }
+            (Some(Op::RemoveDir), _) => {
+                if src.exists() {
+                    skipped += 1;
+                    continue;
+                }
+                if dry_run {
+                    println!("[DRY-RUN] recreate {}", src.display());
+                } else {
+                    fs::create_dir_all(&src)?;
+                }
+                restored += 1;
+            }
+            _ => {
+                println!("  cannot restore {} {}", row.op, src.display());
+                skipped += 1;
+            }
+        }
+    }
+    if !dry_run {
+        ledger.finish("ok")?;
+        conn.execute(
+            "UPDATE runs SET status = 'undone' WHERE run_id = ?1",
+            [run_id],
+        )?;
+    }
+    println!("Undo done: {restored} restored, {skipped} skipped.");
+    Ok((restored, skipped))
+}
+
+fn connect(path: &Path) -> rusqlite::Result<Connection> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).ok();
+    }
+    let conn = Connection::open(path)?;
+    conn.busy_timeout(std::time::Duration::from_secs(10))?;
+    conn.execute_batch(SCHEMA)?;
+    Ok(conn)
+}
+
+fn text(path: &Path) -> String {
+    std::path::absolute(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339()
+}
@@ -0,0 +1,11 @@
+//! Helpers shared by the sortbook tools: name sanitization, `sortbook.toml` loading, the operations
+//! ledger, the `--output json` events, the author identity table, the incremental scan index,
+//! the `--snapshot` layouts restored by `sortbook restore` and the ISBN/title matching of outside book lists.
+pub mod config;
+pub mod identity;
+pub mod ledger;
+pub mod matching;
+pub mod output;
+pub mod sanitize;
+pub mod scan;
+pub mod snapshot;
@@ -0,0 +1,232 @@
+//! Matching books from other sources (reading-list CSVs, catalogs) to the files of the library: by ISBN first,
+//! then by title and author, then by a title only one book has. Keys ignore case, accents, punctuation, the
+//! subtitle and a Goodreads-style series suffix (`Title (Series, #2)`), so `The Stand: Complete Edition` by
+//! `King, Stephen` and `the stand` by `Stephen King` are the same book.
+use crate::identity::normalize_key;
+use std::collections::{BTreeSet, HashMap};
+
+/// How a book was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchKind {
+    Isbn,
+    TitleAuthor,
+    /// Same title, other author name (pen name, alias merged by the library), and no other book has that title.
+    Title,
+}
+
+impl MatchKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MatchKind::Isbn => "isbn",
+            MatchKind::TitleAuthor => "title-author",
+            MatchKind::Title => "title",
+        }
+    }
+}
+
+/// ISBN-13 of an ISBN-10 or ISBN-13, whatever the hyphens, spaces or spreadsheet quoting (`="0553380958"`).
+pub fn normalize_isbn(value: &str) -> Option<String> {
+    let chars: String = value
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == 'X' || *c == 'x')
+        .collect::<String>()
+        .to_ascii_uppercase();
+    match chars.len() {
+        13 if chars.bytes().all(|b| b.is_ascii_digit()) => Some(chars),
+        10 if chars[..9].bytes().all(|b| b.is_ascii_digit()) => {
+            let body = format!("978{}", &chars[..9]);
+            let sum: u32 = body
+                .bytes()
+                .enumerate()
+                .map(|(i, b)| (b - b'0') as u32 * if i % 2 == 0 { 1 } else { 3 })
+                .sum();
+            Some(format!("{body}{}", (10 - sum % 10) % 10))
+        }
+        _ => None,
+    }
+}
+
+/// Title without subtitle (after `:`, or `_ ` once sanitized in a file name) nor trailing `(Series, #n)`.
+pub fn title_key(title: &str) -> String {
+    let mut title = title.trim();
+    if let Some(open) = title.rfind('(').filter(|_| title.ends_with(')')) {
+        if title[open..].contains('#') {
+            title = title[..open].trim_end();
+        }
+    }
+    let short = [":", "_ "]
+        .iter()
+        .filter_map(|sep| title.find(sep))
+        .min()
+        .map(|i| &title[..i])
+        .unwrap_or(title);
+    let key = normalize_key(short);
+    if key.is_empty() {
+        normalize_key(title)
+    } else {
+        key
+    }
+}
+
+/// Words of an author name, in any order (`King, Stephen` = `Stephen King`).
+pub fn author_words(name: &str) -> BTreeSet<String> {
+    normalize_key(name)
+        .split_whitespace()
+        .map(String::from)
+        .collect()
+}
+
+/// Whether two author names are the same person: the words of one are all in the other
+/// (`J. R. R. Tolkien` = `Tolkien, J.R.R.`; `Stephen King` = `Stephen Edwin King`).
+pub fn same_author(a: &str, b: &str) -> bool {
+    let (a, b) = (author_words(a), author_words(b));
+    !a.is_empty() && !b.is_empty() && (a.is_subset(&b) || b.is_subset(&a))
+}
+
+/// The books of one side (usually the library), looked up by the other's ISBN, title and author.
+#[derive(Debug, Default)]
+pub struct BookIndex {
+    by_isbn: HashMap<String, usize>,
+    by_title: HashMap<String, Vec<(usize, String)>>,
+}
+
+impl BookIndex {
+    /// Adds book `id`; the first book added with an ISBN keeps it.
+    pub fn add(&mut self, id: usize, isbn: Option<&str>, title: &str, author: &str) {
+        if let Some(isbn) = isbn.and_then(normalize_isbn) {
+            self.by_isbn.entry(isbn).or_insert(id);
+        }
+        let key = title_key(title);
+        if key.is_empty() {
+            return;
+        }
+        let titled = self.by_title.entry(key).or_default();
+        if !titled.iter().any(|(known, _)| *known == id) {
+            titled.push((id, author.to_string()));
+        }
+    }
+
+    /// Best book for a row: any of its ISBNs, else its title with the same author, else its title alone when
+    /// only one book has it. A title made only of punctuation matches nothing but by ISBN.
+    pub fn find(&self, isbns: &[&str], title: &str, author: &str) -> Option<(usize, MatchKind)> {
+        if let Some(id) = isbns
+            .iter()
+            .filter_map(|isbn| normalize_isbn(isbn))
+            .find_map(|isbn| self.by_isbn.get(&isbn))
+        {
+            return Some((*id, MatchKind::Isbn));
+        }
+        let key = title_key(title);
+        if key.is_empty() {
+            return None;
+        }
+        let titled = self.by_title.get(&key)?;
+        if let Some((id, _)) = titled.iter().find(|(_, known)| same_author(known, author)) {
+            return Some((*id, MatchKind::TitleAuthor));
+        }
+        match titled.as_slice() {
+            [(id, _)] => Some((*id, MatchKind::Title)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn isbn10_gets_the_isbn13_check_digit() {
+        assert_eq!(
+            normalize_isbn("0451196716").as_deref(),
+            Some("9780451196712")
+        );
+        assert_eq!(
+            normalize_isbn("0-553-38095-8").as_deref(),
+            Some("9780553380958")
+        );
+        assert_eq!(
+            normalize_isbn("080442957X").as_deref(),
+            Some("9780804429573")
+        );
+    }
+
+    #[test]
+    fn isbn_spreadsheet_quoting_is_ignored() {
+        assert_eq!(
+            normalize_isbn("=\"0451196716\"").as_deref(),
+            Some("9780451196712")
+        );
+        assert_eq!(
+            normalize_isbn("=\"9780451196712\"").as_deref(),
+            Some("9780451196712")
+        );
+        assert_eq!(normalize_isbn("=\"\""), None);
+        assert_eq!(normalize_isbn("12345"), None);
+    }
+
+    #[test]
+    fn title_key_drops_subtitle_and_series() {
+        assert_eq!(
+            title_key("The Stand: Complete Edition"),
+            title_key("the stand")
+        );
+        assert_eq!(
+            title_key("The Gunslinger (The Dark Tower, #1)"),
+            title_key("The Gunslinger")
+        );
+        assert_eq!(title_key("Dune_ Deluxe Edition"), title_key("Dune"));
+        // Parentheses without a series number are part of the title
+        assert_ne!(title_key("Carrie (Movie Tie-In)"), title_key("Carrie"));
+    }
+
+    fn library() -> BookIndex {
+        let mut index = BookIndex::default();
+        index.add(0, Some("0451196716"), "The Stand", "King, Stephen");
+        index.add(1, None, "Misery", "King, Stephen");
+        index.add(2, None, "It", "King, Stephen");
+        index.add(3, None, "It", "Someone Else");
+        index
+    }
+
+    #[test]
+    fn find_by_isbn_first() {
+        let index = library();
+        assert_eq!(
+            index.find(&["", "=\"9780451196712\""], "Whatever", "Nobody"),
+            Some((0, MatchKind::Isbn))
+        );
+    }
+
+    #[test]
+    fn find_by_title_and_author() {
+        let index = library();
+        assert_eq!(
+            index.find(&[], "Misery: A Novel", "Stephen King"),
+            Some((1, MatchKind::TitleAuthor))
+        );
+        assert_eq!(
+            index.find(&[], "It", "Stephen Edwin King"),
+            Some((2, MatchKind::TitleAuthor))
+        );
+    }
+
+    #[test]
+    fn find_by_unique_title() {
+        let index = library();
+        assert_eq!(
+            index.find(&[], "Misery", "Richard Bachman"),
+            Some((1, MatchKind::Title))
+        );
+        // Two books are titled "It": the author must match
+        assert_eq!(index.find(&[], "It", "Richard Bachman"), None);
+    }
+
+    #[test]
+    fn empty_title_matches_nothing() {
+        let mut index = library();
+        index.add(4, None, "", "Anonymous");
+        assert_eq!(index.find(&[], "", "Anonymous"), None);
+        assert_eq!(index.find(&[], "...", "Anonymous"), None);
+    }
+}
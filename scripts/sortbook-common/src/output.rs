@@ -0,0 +1,117 @@
+//! `--output json`: one JSON event per line on stdout, with the same fields for every tool
+//! (`tool`, `action`, `path`, `result`, `score`, plus `target`/`detail` when known and `ts`), so runs
+//! can be scripted and aggregated. Messages meant for people then go to stderr (see [`say!`]).
+
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Value of `--output`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Messages for people on stdout (historical behavior)
+    #[default]
+    Text,
+    /// Line-delimited JSON events on stdout, messages on stderr
+    Json,
+}
+
+/// Selects the output of the whole process; call once, right after parsing the command line.
+pub fn init(format: OutputFormat) {
+    JSON.store(format == OutputFormat::Json, Ordering::Relaxed);
+}
+
+/// True with `--output json`.
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// One decision or file operation of a tool.
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    /// Same name as in the ledger: `sortbook`, `cleanup`, `cleanup-filenames`, `author-alias-online`.
+    pub tool: &'static str,
+    /// What was decided or done: `sort`, `match`, `merge`, `rename`, `discard`, `alias`...
+    pub action: &'static str,
+    /// File or folder acted on (absolute).
+    pub path: String,
+    /// `ok`, `planned` (dry-run), `fail`, `skipped`, `error`...
+    pub result: &'static str,
+    /// Match confidence (0-1) when the tool computes one.
+    pub score: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub ts: String,
+}
+
+impl Event {
+    pub fn new(
+        tool: &'static str,
+        action: &'static str,
+        path: impl AsRef<Path>,
+        result: &'static str,
+    ) -> Self {
+        Self {
+            tool,
+            action,
+            path: text(path.as_ref()),
+            result,
+            score: None,
+            target: None,
+            detail: None,
+            ts: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn target(mut self, target: impl AsRef<Path>) -> Self {
+        self.target = Some(text(target.as_ref()));
+        self
+    }
+
+    pub fn score(mut self, score: f64) -> Self {
+        self.score = Some(score);
+        self
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// Writes the event on stdout with `--output json`; does nothing otherwise.
+    pub fn emit(self) {
+        if !json() {
+            return;
+        }
+        if let Ok(line) = serde_json::to_string(&self) {
+            let mut stdout = std::io::stdout().lock();
+            let _ = writeln!(stdout, "{line}");
+            let _ = stdout.flush();
+        }
+    }
+}
+
+/// `println!` for messages meant for people: stdout as before, or stderr with `--output json` so
+/// stdout only carries events.
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if $crate::output::json() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+fn text(path: &Path) -> String {
+    std::path::absolute(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
@@ -0,0 +1,50 @@
+//! File and folder names that are valid on Windows and SMB shares as well as on Unix.
+
+/// Characters Windows refuses in a name.
+pub const INVALID_FILENAME_CHARS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Device names Windows reserves, alone or followed by an extension (`con`, `nul.txt`).
+pub const WINDOWS_RESERVED: &[&str] = &[
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+    "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// One path component made safe: leading/trailing dots and spaces trimmed, invalid and control
+/// characters replaced by `_`, reserved device names prefixed with `_`. An empty result becomes `_`.
+pub fn sanitize_component(value: &str) -> String {
+    let mut cleaned = value
+        .trim_matches(|ch: char| ch == '.' || ch.is_whitespace())
+        .to_string();
+    if cleaned.is_empty() {
+        return "_".into();
+    }
+    cleaned = cleaned
+        .chars()
+        .map(|ch| {
+            if INVALID_FILENAME_CHARS.contains(&ch) || ch.is_control() {
+                '_'
+            } else {
+                ch
+            }
+        })
+        .collect();
+    cleaned = cleaned.trim().to_string();
+    if cleaned.is_empty() {
+        return "_".into();
+    }
+    if is_reserved(&cleaned) {
+        return format!("_{}", cleaned);
+    }
+    cleaned
+}
+
+/// `CON`, `con.epub` and `Nul .txt` are reserved: Windows only looks at the part before the first dot.
+pub fn is_reserved(name: &str) -> bool {
+    let device = name
+        .split('.')
+        .next()
+        .unwrap_or(name)
+        .trim()
+        .to_ascii_lowercase();
+    WINDOWS_RESERVED.contains(&device.as_str())
+}
@@ -0,0 +1,221 @@
+//! `--scan-index <path>` (e.g. `data/scan_index.sqlite3`): the files each tool has already handled, with their
+//! size, mtime and SHA-256, so a run on a large library only touches what is new or modified since that tool's
+//! last run. One SQLite file serves every tool; rows are kept per `scope` (the tool, plus its options when they
+//! change the result). A file whose mtime changed but whose size and hash did not counts as unchanged.
+use rusqlite::{params, Connection};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS files (
+     scope TEXT NOT NULL,
+     path TEXT NOT NULL,
+     size INTEGER NOT NULL,
+     mtime INTEGER NOT NULL,
+     hash TEXT,
+     seen_at TEXT NOT NULL,
+     PRIMARY KEY (scope, path)
+ );";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    New,
+    Modified,
+    Unchanged,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    size: u64,
+    mtime: i64,
+    hash: Option<String>,
+}
+
+/// The index of one scope, loaded in memory; `record`/`forget` are written by `save`.
+pub struct ScanIndex {
+    conn: Connection,
+    scope: String,
+    known: HashMap<PathBuf, Entry>,
+    pending: HashMap<PathBuf, Option<Entry>>,
+}
+
+impl ScanIndex {
+    pub fn open(path: &Path, scope: &str) -> rusqlite::Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).ok();
+        }
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(std::time::Duration::from_secs(10))?;
+        conn.execute_batch(SCHEMA)?;
+        let known = {
+            let mut stmt =
+                conn.prepare("SELECT path, size, mtime, hash FROM files WHERE scope = ?1")?;
+            let rows = stmt.query_map([scope], |row| {
+                let path: String = row.get(0)?;
+                Ok((
+                    PathBuf::from(path),
+                    Entry {
+                        size: row.get::<_, i64>(1)? as u64,
+                        mtime: row.get(2)?,
+                        hash: row.get(3)?,
+                    },
+                ))
+            })?;
+            rows.collect::<rusqlite::Result<HashMap<_, _>>>()?
+        };
+        Ok(Self {
+            conn,
+            scope: scope.to_string(),
+            known,
+            pending: HashMap::new(),
+        })
+    }
+
+    /// Files indexed for this scope.
+    pub fn len(&self) -> usize {
+        self.known.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.known.is_empty()
+    }
+
+    /// How a file differs from its last `record`.
+    pub fn status(&self, path: &Path) -> io::Result<Change> {
+        let Some(known) = self.known.get(&absolute(path)) else {
+            return Ok(Change::New);
+        };
+        let (size, mtime) = stat(path)?;
+        if size != known.size {
+            return Ok(Change::Modified);
+        }
+        if mtime == known.mtime {
+            return Ok(Change::Unchanged);
+        }
+        // Touched (copied back, restored...): same content keeps it unchanged.
+        match &known.hash {
+            Some(hash) if *hash == digest(path)? => Ok(Change::Unchanged),
+            _ => Ok(Change::Modified),
+        }
+    }
+
+    /// Whether anything under `dir` is new, modified or gone since the files under it were recorded.
+    pub fn folder_changed(&self, dir: &Path) -> io::Result<bool> {
+        let mut present = 0usize;
+        for entry in WalkDir::new(dir)
+            .min_depth(1)
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            present += 1;
+            if self.status(entry.path())? != Change::Unchanged {
+                return Ok(true);
+            }
+        }
+        let dir = absolute(dir);
+        Ok(self
+            .known
+            .keys()
+            .filter(|path| path.starts_with(&dir))
+            .count()
+            != present)
+    }
+
+    /// Records the current state of a file (hashed when new or modified).
+    pub fn record(&mut self, path: &Path) -> io::Result<()> {
+        let key = absolute(path);
+        let (size, mtime) = stat(path)?;
+        let hash = match self.known.get(&key) {
+            Some(known) if known.size == size && known.mtime == mtime && known.hash.is_some() => {
+                known.hash.clone()
+            }
+            _ => Some(digest(path)?),
+        };
+        let entry = Entry { size, mtime, hash };
+        self.known.insert(key.clone(), entry.clone());
+        self.pending.insert(key, Some(entry));
+        Ok(())
+    }
+
+    /// Records every file under `dir` and forgets the indexed ones that are gone.
+    pub fn record_folder(&mut self, dir: &Path) -> io::Result<()> {
+        let root = absolute(dir);
+        let gone: Vec<PathBuf> = self
+            .known
+            .keys()
+            .filter(|path| path.starts_with(&root) && !path.is_file())
+            .cloned()
+            .collect();
+        for path in gone {
+            self.forget(&path);
+        }
+        for entry in WalkDir::new(dir)
+            .min_depth(1)
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            if entry.file_type().is_file() {
+                self.record(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn forget(&mut self, path: &Path) {
+        let key = absolute(path);
+        self.known.remove(&key);
+        self.pending.insert(key, None);
+    }
+
+    /// Writes the recorded and forgotten files in one transaction.
+    pub fn save(&mut self) -> Result<(), Error> {
+        let now = chrono::Utc::now().to_rfc3339();
+        let tx = self.conn.transaction()?;
+        for (path, entry) in self.pending.drain() {
+            let path = path.to_string_lossy();
+            match entry {
+                Some(entry) => tx.execute(
+                    "INSERT OR REPLACE INTO files (scope, path, size, mtime, hash, seen_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![self.scope, path, entry.size as i64, entry.mtime, entry.hash, now],
+                )?,
+                None => tx.execute("DELETE FROM files WHERE scope = ?1 AND path = ?2", params![self.scope, path])?,
+            };
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn stat(path: &Path) -> io::Result<(u64, i64)> {
+    let meta = fs::metadata(path)?;
+    let mtime = meta
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as i64)
+        .unwrap_or(0);
+    Ok((meta.len(), mtime))
+}
+
+/// SHA-256 (hex) of a file's content.
+fn digest(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
+}
@@ -0,0 +1,302 @@
+//! `--snapshot <dir>` (e.g. `data/snapshots`): the complete layout of the roots a live cleanup run is about to
+//! change, taken before its first change, with `sortbook restore` to put the files back where they were.
+//!
+//! Each snapshot is a folder `<dir>/<tool>_<YYYYmmdd_HHMMSS>/` holding `layout.jsonl` (a header line with the
+//! tool and roots, then one line per folder and file: root, relative path, size, mtime and file identity) and,
+//! with `--snapshot-keep-deleted`, `files/<root>/<relative path>`: hard links (copies across file systems) of the
+//! files the run deleted or trashed. Files are found again by identity (device and inode), so renames and moves
+//! made by any tool are followed. Unlike the undo journals, this does not depend on what the run recorded.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+pub const LAYOUT_NAME: &str = "layout.jsonl";
+const FILES_DIR: &str = "files";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    tool: String,
+    created_at: String,
+    roots: Vec<PathBuf>,
+    keep_deleted: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    root: usize,
+    path: PathBuf,
+    dir: bool,
+    #[serde(default)]
+    size: u64,
+    #[serde(default)]
+    mtime: i64,
+    /// `(device, inode)`; absent where the platform has none.
+    #[serde(default)]
+    id: Option<(u64, u64)>,
+}
+
+/// A snapshot taken for one run; `keep` is safe to call from several threads.
+pub struct Snapshot {
+    dir: PathBuf,
+    roots: Vec<PathBuf>,
+    keep_deleted: bool,
+    kept: AtomicUsize,
+}
+
+impl Snapshot {
+    /// Records the layout of `roots` in a new folder under `base`. Returns the snapshot and the number of files.
+    pub fn take(
+        base: &Path,
+        tool: &str,
+        roots: &[PathBuf],
+        keep_deleted: bool,
+    ) -> Result<(Self, usize), Error> {
+        let roots: Vec<PathBuf> = roots.iter().map(|root| absolute(root)).collect();
+        let stamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+        let mut dir = absolute(&base.join(format!("{tool}_{stamp}")));
+        let mut index = 2;
+        while dir.exists() {
+            dir = absolute(&base.join(format!("{tool}_{stamp}_{index}")));
+            index += 1;
+        }
+        fs::create_dir_all(&dir)?;
+        let mut out = BufWriter::new(fs::File::create(dir.join(LAYOUT_NAME))?);
+        let header = Header {
+            tool: tool.to_string(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            roots: roots.clone(),
+            keep_deleted,
+        };
+        writeln!(out, "{}", serde_json::to_string(&header)?)?;
+        let mut files = 0usize;
+        for (root_index, root) in roots.iter().enumerate() {
+            // A snapshot folder under a root is not part of the library
+            for entry in WalkDir::new(root)
+                .min_depth(1)
+                .into_iter()
+                .filter_entry(|e| !e.path().starts_with(&dir))
+            {
+                let entry = entry?;
+                let meta = entry.metadata()?;
+                if !meta.is_dir() && !meta.is_file() {
+                    continue;
+                }
+                let rel = entry.path().strip_prefix(root)?.to_path_buf();
+                let line = if meta.is_dir() {
+                    Entry {
+                        root: root_index,
+                        path: rel,
+                        dir: true,
+                        size: 0,
+                        mtime: 0,
+                        id: None,
+                    }
+                } else {
+                    files += 1;
+                    Entry {
+                        root: root_index,
+                        path: rel,
+                        dir: false,
+                        size: meta.len(),
+                        mtime: mtime(&meta),
+                        id: file_id(&meta),
+                    }
+                };
+                writeln!(out, "{}", serde_json::to_string(&line)?)?;
+            }
+        }
+        out.flush()?;
+        Ok((
+            Self {
+                dir,
+                roots,
+                keep_deleted,
+                kept: AtomicUsize::new(0),
+            },
+            files,
+        ))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Files kept so far by `keep`.
+    pub fn kept(&self) -> usize {
+        self.kept.load(Ordering::Relaxed)
+    }
+
+    /// With `--snapshot-keep-deleted`, keeps a hard link (else a copy) of a file about to be deleted or
+    /// trashed; does nothing otherwise, or for a file outside the roots.
+    pub fn keep(&self, path: &Path) -> io::Result<()> {
+        if !self.keep_deleted {
+            return Ok(());
+        }
+        let path = absolute(path);
+        let Some((index, rel)) = self
+            .roots
+            .iter()
+            .enumerate()
+            .find_map(|(i, root)| path.strip_prefix(root).ok().map(|rel| (i, rel)))
+        else {
+            return Ok(());
+        };
+        let target = self.dir.join(FILES_DIR).join(index.to_string()).join(rel);
+        if target.exists() {
+            return Ok(());
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if fs::hard_link(&path, &target).is_err() {
+            fs::copy(&path, &target)?;
+        }
+        self.kept.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Puts the files of a snapshot back at their recorded paths: missing folders are recreated, files found
+/// elsewhere in the roots (same identity) are moved back, deleted files kept by the snapshot are linked back.
+/// Nothing is overwritten or deleted; files added since stay where they are. Returns (restored, skipped).
+pub fn restore(snapshot: &Path, dry_run: bool) -> Result<(usize, usize), Error> {
+    let layout = if snapshot.is_dir() {
+        snapshot.join(LAYOUT_NAME)
+    } else {
+        snapshot.to_path_buf()
+    };
+    let snapshot_dir = layout.parent().unwrap_or(Path::new("")).to_path_buf();
+    let mut lines =
+        BufReader::new(fs::File::open(&layout).map_err(|e| format!("{}: {e}", layout.display()))?)
+            .lines();
+    let header: Header = match lines.next() {
+        Some(line) => serde_json::from_str(&line?)?,
+        None => return Err(format!("{} is empty", layout.display()).into()),
+    };
+    let entries = lines
+        .map(|line| Ok(serde_json::from_str::<Entry>(&line?)?))
+        .collect::<Result<Vec<Entry>, Error>>()?;
+    println!(
+        "Restoring snapshot of {} taken {} ({} entries){}",
+        header.tool,
+        header.created_at,
+        entries.len(),
+        if dry_run { " (dry-run)" } else { "" }
+    );
+
+    // Where every file of the roots is now, by identity
+    let mut current: HashMap<(u64, u64), PathBuf> = HashMap::new();
+    for root in header.roots.iter().filter(|root| root.is_dir()) {
+        for entry in WalkDir::new(root)
+            .into_iter()
+            .filter_entry(|e| !e.path().starts_with(&snapshot_dir))
+            .filter_map(|e| e.ok())
+        {
+            if let Some(id) = entry
+                .metadata()
+                .ok()
+                .filter(|meta| meta.is_file())
+                .and_then(|meta| file_id(&meta))
+            {
+                current.insert(id, entry.into_path());
+            }
+        }
+    }
+
+    let (mut restored, mut skipped) = (0usize, 0usize);
+    for entry in entries.iter().filter(|entry| entry.dir) {
+        let path = header.roots[entry.root].join(&entry.path);
+        if path.is_dir() {
+            continue;
+        }
+        if dry_run {
+            println!("[DRY-RUN] recreate {}", path.display());
+        } else {
+            fs::create_dir_all(&path)?;
+        }
+        restored += 1;
+    }
+    for entry in entries.iter().filter(|entry| !entry.dir) {
+        let path = header.roots[entry.root].join(&entry.path);
+        if let Ok(meta) = fs::metadata(&path) {
+            let same = match (entry.id, file_id(&meta)) {
+                (Some(recorded), Some(now)) => recorded == now,
+                _ => meta.len() == entry.size,
+            };
+            if !same {
+                println!("  skip {} (occupied by another file)", path.display());
+                skipped += 1;
+            }
+            continue;
+        }
+        let moved = entry
+            .id
+            .and_then(|id| current.get(&id))
+            .filter(|found| **found != path);
+        let kept = snapshot_dir
+            .join(FILES_DIR)
+            .join(entry.root.to_string())
+            .join(&entry.path);
+        let (source, link) = match moved {
+            Some(found) => (found.clone(), false),
+            None if kept.is_file() => (kept, true),
+            None => {
+                println!("  cannot restore {} (not found)", path.display());
+                skipped += 1;
+                continue;
+            }
+        };
+        if dry_run {
+            println!(
+                "[DRY-RUN] restore {} -> {}",
+                source.display(),
+                path.display()
+            );
+        } else {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            if link {
+                if fs::hard_link(&source, &path).is_err() {
+                    fs::copy(&source, &path)?;
+                }
+            } else if fs::rename(&source, &path).is_err() {
+                fs::copy(&source, &path)?;
+                fs::remove_file(&source)?;
+            }
+        }
+        restored += 1;
+    }
+    println!("Restore done: {restored} restored, {skipped} skipped.");
+    Ok((restored, skipped))
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn mtime(meta: &fs::Metadata) -> i64 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(unix)]
+fn file_id(meta: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}